@@ -13,6 +13,7 @@ use cw_utils::must_pay;
 use itertools::Itertools;
 
 use crate::cosmwasm_ext::DecimalToInteger;
+use crate::error::ContractError;
 use crate::querier::{
     query_balance, query_token_balance, query_token_precision, query_token_symbol,
 };
@@ -196,6 +197,14 @@ impl Asset {
         }
     }
 
+    /// Builds the transfer message with [`cw_asset`] for crates which interoperate with it.
+    /// Produces the same message as [`Asset::into_msg`].
+    pub fn transfer_msg_via_cw_asset(&self, recipient: impl Into<String>) -> StdResult<CosmosMsg> {
+        CwAsset::from(self.clone())
+            .transfer_msg(recipient)
+            .map_err(|err| StdError::generic_err(err.to_string()))
+    }
+
     /// Same as [`Asset::into_msg`] but allows to handle errors/msg response data in contract's reply endpoint.
     /// If `reply_params` is None then the reply is disabled.
     /// Returns a [`SubMsg`] object.
@@ -286,7 +295,7 @@ pub trait CoinsExt {
         &self,
         assets: &[Asset],
         pool_asset_infos: &[AssetInfo],
-    ) -> StdResult<()>;
+    ) -> Result<(), ContractError>;
 }
 
 impl CoinsExt for Vec<Coin> {
@@ -294,7 +303,7 @@ impl CoinsExt for Vec<Coin> {
         &self,
         input_assets: &[Asset],
         pool_asset_infos: &[AssetInfo],
-    ) -> StdResult<()> {
+    ) -> Result<(), ContractError> {
         ensure!(
             !input_assets.is_empty(),
             StdError::generic_err("Empty input assets")
@@ -302,7 +311,7 @@ impl CoinsExt for Vec<Coin> {
 
         ensure!(
             input_assets.iter().map(|asset| &asset.info).all_unique(),
-            StdError::generic_err("Duplicated assets in the input")
+            ContractError::DuplicatedAssets {}
         );
 
         input_assets.iter().try_for_each(|input| {
@@ -343,7 +352,9 @@ impl CoinsExt for Vec<Coin> {
                     coin.denom
                 )))
             }
-        })
+        })?;
+
+        Ok(())
     }
 }
 
@@ -351,7 +362,7 @@ impl CoinsExt for Vec<Coin> {
 /// ## Examples
 /// ```
 /// # use cosmwasm_std::Addr;
-/// # use palomadex::asset::AssetInfo::{NativeToken, Token};
+/// # use palomadex_incentives::asset::AssetInfo::{NativeToken, Token};
 /// Token { contract_addr: Addr::unchecked("stake...") };
 /// NativeToken { denom: String::from("uluna") };
 /// ```
@@ -749,6 +760,47 @@ pub fn determine_asset_info(maybe_asset_info: &str, api: &dyn Api) -> StdResult<
     }
 }
 
+/// Validated LP token identifier. Either cw20 LP token address or token factory denom
+/// in the format factory/{lp_minter}/{token_name}.
+/// Built once at the message boundary so that handlers don't classify raw strings again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LpToken(AssetInfo);
+
+impl LpToken {
+    pub fn new(lp_token: &str, api: &dyn Api) -> StdResult<Self> {
+        let asset_info = determine_asset_info(lp_token, api)?;
+        if let AssetInfo::NativeToken { denom } = &asset_info {
+            lp_minter(denom, api)?;
+        }
+
+        Ok(Self(asset_info))
+    }
+
+    pub fn asset_info(&self) -> &AssetInfo {
+        &self.0
+    }
+}
+
+impl fmt::Display for LpToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Returns LP minter address from token factory LP denom.
+/// Denom must follow the format: factory/{lp_minter}/{token_name}
+/// where lp_minter is a valid bech32 address on the current chain.
+pub fn lp_minter(denom: &str, api: &dyn Api) -> StdResult<Addr> {
+    let parts = denom.split('/').collect_vec();
+    if denom.starts_with("factory") && parts.len() >= 3 {
+        api.addr_validate(parts[1])
+    } else {
+        Err(StdError::generic_err(format!(
+            "LP token {denom} doesn't follow token factory format: factory/{{lp_minter}}/{{token_name}}",
+        )))
+    }
+}
+
 /// Returns [`PairInfo`] by specified pool address.
 ///
 /// * **pool_addr** address of the pool.
@@ -872,3 +924,65 @@ impl Decimal256Ext for Decimal256 {
             .map_err(|_| StdError::generic_err("Decimal256 range exceeded"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::MockApi;
+    use cosmwasm_std::Empty;
+
+    use super::*;
+
+    #[test]
+    fn lp_token_validation() {
+        let api = MockApi::default();
+        let pair = api.addr_make("pair");
+
+        let cw20_lp = LpToken::new(pair.as_str(), &api).unwrap();
+        assert_eq!(cw20_lp.asset_info(), &AssetInfo::cw20(pair.clone()));
+
+        let denom = format!("factory/{pair}/lp");
+        let native_lp = LpToken::new(&denom, &api).unwrap();
+        assert_eq!(native_lp.asset_info(), &AssetInfo::native(&denom));
+        assert_eq!(native_lp.to_string(), denom);
+        assert_eq!(lp_minter(&denom, &api).unwrap(), pair);
+
+        for invalid in ["uatom", "factory/invalid/lp", "factory/lp", ""] {
+            LpToken::new(invalid, &api).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn cw_asset_conversion_is_lossless() {
+        let api = MockApi::default();
+        let recipient = api.addr_make("recipient");
+        for asset in [
+            Asset::native("uatom", 100u128),
+            Asset::cw20(api.addr_make("token"), 200u128),
+        ] {
+            let cw_asset = CwAsset::from(asset.clone());
+            assert_eq!(Asset::try_from(cw_asset).unwrap(), asset);
+            assert_eq!(
+                asset.transfer_msg_via_cw_asset(&recipient).unwrap(),
+                asset.clone().into_msg::<Empty>(&recipient).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn duplicated_coins_return_typed_error() {
+        let reward = AssetInfo::native("ureward");
+        let err = vec![coin(200, "ureward")]
+            .assert_coins_properly_sent(
+                &[reward.with_balance(100u128), reward.with_balance(100u128)],
+                std::slice::from_ref(&reward),
+            )
+            .unwrap_err();
+        assert_eq!(err, ContractError::DuplicatedAssets {});
+
+        // Other validation errors stay generic
+        let err = vec![]
+            .assert_coins_properly_sent(&[], &[reward])
+            .unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+    }
+}
@@ -1,10 +1,17 @@
 pub const MAX_REWARD_TOKENS: u8 = 5;
+/// Upper bound for per-pool reward tokens limit set by owner
+pub const MAX_POOL_REWARD_TOKENS: u8 = 20;
 pub const MAX_PROPOSAL_TTL: u64 = 1209600;
 /// Max items per page in queries
 pub const MAX_PAGE_LIMIT: u8 = 50;
+/// Max number of pools returned by TopPools query
+pub const MAX_TOP_POOLS_LIMIT: u8 = 10;
 
+/// Default maximum number of periods per external reward schedule
 pub const MAX_PERIODS: u64 = 25;
 pub const EPOCHS_START: u64 = 1696809600;
 pub const EPOCH_LENGTH: u64 = 86400 * 7;
 
 pub const MAX_ORPHANED_REWARD_LIMIT: u8 = 10;
+/// Max number of claim history records kept per user
+pub const MAX_CLAIM_HISTORY_LEN: u64 = 100;
@@ -4,7 +4,7 @@ use cosmwasm_std::{
 use cw_utils::PaymentError;
 use thiserror::Error;
 
-use crate::constants::MAX_REWARD_TOKENS;
+use crate::constants::MAX_POOL_REWARD_TOKENS;
 use crate::types::PairType;
 
 #[derive(Error, Debug, PartialEq)]
@@ -27,23 +27,137 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Ownership can't be claimed before {earliest_claim_ts}")]
+    OwnershipClaimTooEarly { earliest_claim_ts: u64 },
+
+    #[error("Token {token} is an active reward in pool {lp_token}. Remove it from the pool before blocking")]
+    BlockedTokenIsActiveReward { token: String, lp_token: String },
+
+    #[error("Pool {assets} with LP token {lp_token} is not registered in factory")]
+    PoolNotRegistered { lp_token: String, assets: String },
+
+    #[error("{lp_token} is not a token factory LP token")]
+    NativeLpTokenExpected { lp_token: String },
+
+    #[error("Pair {pair} doesn't have {lp_token} as its LP token")]
+    LpTokenPairMismatch { lp_token: String, pair: String },
+
+    #[error("Failed to send incentivization fee {fee} to {fee_receiver}: {reason}")]
+    FeeTransferFailed {
+        fee: String,
+        fee_receiver: String,
+        reason: String,
+    },
+
+    #[error("Failed to create PADEX denom {denom}: {reason}")]
+    PadexDenomCreationFailed { denom: String, reason: String },
+
+    #[error("Reward {reward} is an LP token of a registered pool and is not whitelisted by owner")]
+    LpTokenAsReward { reward: String },
+
+    #[error("Schedule lasts {duration} seconds which is less than minimum {min_duration} seconds")]
+    ScheduleTooShort { duration: u64, min_duration: u64 },
+
+    #[error("Maximum number of orphaned rewards entries {max_orphaned_entries} reached. Orphaned rewards must be claimed first")]
+    OrphanedEntriesLimitReached { max_orphaned_entries: u32 },
+
+    #[error("IBC token {reward} can't be used as reward")]
+    IbcRewardBlocked { reward: String },
+
+    #[error(
+        "Refund address for reward {reward} can only be set by the schedule adding it to the pool"
+    )]
+    RefundAddressNotAllowed { reward: String },
+
+    #[error("Reward {reward} is already frozen")]
+    RewardAlreadyFrozen { reward: String },
+
+    #[error("Reward {reward} is not frozen")]
+    RewardNotFrozen { reward: String },
+
+    #[error("Reward {reward} is frozen")]
+    RewardFrozen { reward: String },
+
+    #[error("Reward {reward} can't be remapped: only cw20 rewards can be remapped")]
+    RewardRemapNotCw20 { reward: String },
+
+    #[error("Reward {reward} is already used in pool {lp_token}")]
+    RewardRemapCollision { reward: String, lp_token: String },
+
+    #[error("At most {max} rewards can be removed at once")]
+    TooManyRewardsToRemove { max: u8 },
+
+    #[error("Contract is paused")]
+    ContractPaused {},
+
+    #[error("Receiver {receiver} is not allowed to receive rewards of the user")]
+    ReceiverNotAllowed { receiver: String },
+
+    #[error("PADEX rewards can be bridged only by the user claiming for themselves")]
+    BridgeOnBehalfNotAllowed {},
+
+    #[error("LP token {lp_token} is not bridgeable to {chain_reference_id}")]
+    LpTokenNotBridgeable {
+        lp_token: String,
+        chain_reference_id: String,
+    },
+
+    #[error("vePADEX contract is not set")]
+    VepadexNotSet {},
+
+    #[error("Reward distribution can be changed only while pool {lp_token} has no stakers")]
+    PoolHasStakers { lp_token: String },
+
+    #[error("No LP tokens sent. Deposit requires exactly one LP coin or non-zero cw20 LP amount")]
+    NoLpSent {},
+
+    #[error("Deposit requires exactly one LP coin but received {denoms}. Don't attach other coins e.g. fee tokens")]
+    MultipleCoinsSent { denoms: String },
+
+    #[error(
+        "User can be staked in at most {max_user_pools} pools. Withdraw from another pool first"
+    )]
+    TooManyPositions { max_user_pools: u32 },
+
     #[error("Duplicated pool found")]
     DuplicatedPoolFound {},
 
+    #[error("Duplicated assets in the input")]
+    DuplicatedAssets {},
+
     #[error("Amount to withdraw {withdraw_amount} exceeds balance {available}")]
     AmountExceedsBalance {
         available: Uint128,
         withdraw_amount: Uint128,
     },
 
+    #[error("Pool {lp_token} accounting is corrupted: withdraw amount {withdraw_amount} exceeds total staked {total_lp}")]
+    PoolAccountingMismatch {
+        lp_token: String,
+        total_lp: Uint128,
+        withdraw_amount: Uint128,
+    },
+
     #[error("User {user} doesn't have position in {lp_token}")]
     PositionDoesntExist { user: String, lp_token: String },
 
     #[error("Pool {pool} doesn't have {reward} reward")]
     RewardNotFound { pool: String, reward: String },
 
-    #[error("Too many reward tokens in pool {lp_token}. Maximum allowed is {MAX_REWARD_TOKENS}")]
-    TooManyRewardTokens { lp_token: String },
+    #[error("Too many reward tokens in pool {lp_token}. Maximum allowed is {max_reward_tokens}")]
+    TooManyRewardTokens {
+        lp_token: String,
+        max_reward_tokens: u8,
+    },
+
+    #[error("First deposit into pool {lp_token} must be at least {min_amount}")]
+    FirstDepositTooSmall {
+        lp_token: String,
+        min_amount: Uint128,
+    },
+
+    #[error("Pool reward tokens limit must be between 1 and {MAX_POOL_REWARD_TOKENS}")]
+    InvalidRewardTokensLimit {},
 
     #[error("Incentivization fee {fee} expected as you are trying to add new reward token {new_reward_token} for pool {lp_token}")]
     IncentivizationFeeExpected {
@@ -55,6 +169,9 @@ pub enum ContractError {
     #[error("Token {token} is blocked")]
     BlockedToken { token: String },
 
+    #[error("LP token {lp_token} is blocked")]
+    BlockedLpToken { lp_token: String },
+
     #[error("Pair type {pair_type} is blocked")]
     BlockedPairType { pair_type: PairType },
 
@@ -64,6 +181,15 @@ pub enum ContractError {
     #[error("No orphaned rewards to claim")]
     NoOrphanedRewards {},
 
+    #[error("Sum of pools allocation points overflows")]
+    AllocPointsOverflow {},
+
+    #[error("Total allocation points {total_alloc_points} are less than {alloc_points} being removed. Active pools state is inconsistent. Owner can fix it with ReconcileAllocPoints")]
+    AllocPointsMismatch {
+        total_alloc_points: Uint128,
+        alloc_points: Uint128,
+    },
+
     #[error("Failed to set 0 alloc point for pool {lp_token}")]
     ZeroAllocPoint { lp_token: String },
 
@@ -72,4 +198,25 @@ pub enum ContractError {
 
     #[error("PADEX is not native coin")]
     PADEXNotNativeCoin {},
+
+    #[error("Maximum number of periods must be greater than 0")]
+    ZeroMaxPeriods {},
+
+    #[error("LP token {lp_token} is not registered in factory. Deposits are disabled, staked tokens can still be withdrawn")]
+    StaleLpToken { lp_token: String },
+
+    #[error("{role} address can't be the contract itself")]
+    SelfReferencingAddress { role: String },
+
+    #[error("Keeper bounty is not set")]
+    KeeperBountyNotSet {},
+
+    #[error("Can't withdraw {amount} from the keeper bounty fund of {fund}")]
+    KeeperBountyFundTooLow { fund: Uint128, amount: Uint128 },
+
+    #[error("Effective time {effective_ts} of PADEX rate change must be in the future")]
+    PastEffectiveTime { effective_ts: u64 },
+
+    #[error("Pool with LP token {lp_token} not found. It was never staked in or incentivized")]
+    PoolNotFound { lp_token: String },
 }
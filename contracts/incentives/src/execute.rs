@@ -1,25 +1,36 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, ensure, from_json, Addr, CosmosMsg, DepsMut, Env, MessageInfo, Response, StdError,
-    StdResult, Uint128,
+    attr, ensure, from_json, to_json_binary, Addr, BankMsg, Coin, CosmosMsg, DepsMut, Env,
+    MessageInfo, Response, StdError, StdResult, Storage, SubMsg, Uint128,
 };
-use cw_utils::one_coin;
+use cw_utils::{must_pay, one_coin, PaymentError};
 use itertools::Itertools;
 
-use crate::asset::{determine_asset_info, validate_native_denom, Asset, AssetInfo, AssetInfoExt};
+use crate::asset::{validate_native_denom, Asset, AssetInfo, AssetInfoExt, LpToken, PairInfo};
+use crate::constants::MAX_POOL_REWARD_TOKENS;
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, FactoryQueryMsg};
+use crate::msg::ExecuteMsg;
+use crate::reply::WITHDRAW_TRANSFER_REPLY_ID;
 use crate::state::{
-    Op, PoolInfo, UserInfo, ACTIVE_POOLS, BLOCKED_TOKENS, CONFIG, OWNERSHIP_PROPOSAL,
+    apply_scheduled_padex_rate, padex_rps_distribution, save_scheduled_pools_rps, Op, PoolInfo,
+    UserInfo, ACTIVE_POOLS, ALLOWED_RECEIVERS, BLOCKED_TOKENS, CONFIG, CUSTODY_RECEIVERS,
+    FROZEN_REWARDS, KEEPER_BOUNTY_FUNDS, LP_REWARD_WHITELIST, LP_TOKEN_BRIDGES, LP_TOKEN_PAIRS,
+    OWNERSHIP_PROPOSAL, POOLS, SCHEDULED_PADEX_RATE,
+};
+use crate::types::{
+    BridgeTarget, Cw20Msg, IncentivizationFeeInfo, InputSchedule, PairQueryMsg, PalomaMsg,
+    RewardDistribution, ScheduledPadexRate, SendTx, SetErc20ToDenom, WithdrawTransferInfo,
 };
-use crate::types::{Cw20Msg, IncentivizationFeeInfo, PairType, PalomaMsg, SetErc20ToDenom};
 use crate::utils::{
     asset_info_key, claim_orphaned_rewards, claim_ownership, claim_rewards,
-    deactivate_blocked_pools, deactivate_pool, drop_ownership_proposal, incentivize,
-    is_pool_registered, propose_new_owner, query_pair_info, remove_reward_from_pool,
+    deactivate_blocked_pools, deactivate_pool, donate, drop_ownership_proposal, expire_schedule,
+    find_pool_with_active_reward, incentivize, is_pool_registered, load_pool_or_registered,
+    propose_new_owner, query_pair_info, query_pair_info_cached, reduce_total_alloc_points,
+    remap_reward_token, remove_reward_from_pool, remove_rewards_from_pool, rewards_receiver,
+    sync_user_weight, validate_setup_pools,
 };
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -31,19 +42,29 @@ pub fn execute(
 ) -> Result<Response<PalomaMsg>, ContractError> {
     match msg {
         ExecuteMsg::SetupPools { pools } => setup_pools(deps, env, info, pools),
-        ExecuteMsg::ClaimRewards { lp_tokens, user } => {
+        ExecuteMsg::ClaimRewards {
+            lp_tokens,
+            user,
+            bridge,
+        } => {
+            ensure_not_paused(deps.storage)?;
+            ensure!(
+                user.is_none() || bridge.is_none(),
+                ContractError::BridgeOnBehalfNotAllowed {}
+            );
+
             // Check for duplicated pools
             ensure!(
                 lp_tokens.iter().all_unique(),
                 ContractError::DuplicatedPoolFound {}
             );
 
-            let user = if user.is_some() {
-                assert!(
-                    info.sender == CONFIG.load(deps.storage)?.trader,
-                    "User address must match sender address"
+            let user = if let Some(user) = user {
+                ensure!(
+                    Some(&info.sender) == CONFIG.load(deps.storage)?.trader.as_ref(),
+                    ContractError::Unauthorized {}
                 );
-                user.unwrap()
+                user
             } else {
                 info.sender.to_string()
             };
@@ -52,8 +73,8 @@ pub fn execute(
             let mut tuples = lp_tokens
                 .into_iter()
                 .map(|lp_token| {
-                    let lp_asset = determine_asset_info(&lp_token, deps.api)?;
-                    let pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
+                    let lp_asset = LpToken::new(&lp_token, deps.api)?.asset_info().clone();
+                    let pool_info = PoolInfo::load_existing(deps.storage, &lp_asset)?;
                     let user_pos = UserInfo::load_position(deps.storage, &user, &lp_asset)?;
                     Ok((lp_asset, pool_info, user_pos))
                 })
@@ -66,17 +87,22 @@ pub fn execute(
                 .collect_vec();
 
             // Compose response. Return early in case of error
-            let response = claim_rewards(deps.storage, env, info.sender, &user, mut_tuples)?;
+            let receiver = rewards_receiver(deps.storage, info.sender, &user)?;
+            let response = claim_rewards(deps.storage, env, receiver, &user, mut_tuples, bridge)?;
 
             // Save updates in state
-            for (lp_asset, pool_info, user_pos) in tuples {
-                pool_info.save(deps.storage, &lp_asset)?;
+            let mut events = vec![];
+            for (lp_asset, mut pool_info, mut user_pos) in tuples {
+                sync_user_weight(deps.as_ref(), &user, &mut pool_info, &mut user_pos)?;
+                events.extend(pool_info.save(deps.storage, &lp_asset)?);
                 user_pos.save(deps.storage, &user, &lp_asset)?;
             }
 
-            Ok(response)
+            Ok(response.add_events(events))
         }
         ExecuteMsg::Receive(cw20msg) => {
+            ensure_not_paused(deps.storage)?;
+            ensure!(!cw20msg.amount.is_zero(), ContractError::NoLpSent {});
             let maybe_lp = Asset::cw20(info.sender, cw20msg.amount);
             let recipient = match from_json(&cw20msg.msg)? {
                 Cw20Msg::Deposit { recipient } => recipient,
@@ -92,7 +118,13 @@ pub fn execute(
             )
         }
         ExecuteMsg::Deposit { recipient } => {
-            let maybe_lp_coin = one_coin(&info)?;
+            ensure_not_paused(deps.storage)?;
+            let maybe_lp_coin = one_coin(&info).map_err(|err| match err {
+                PaymentError::MultipleDenoms {} => ContractError::MultipleCoinsSent {
+                    denoms: info.funds.iter().map(|coin| &coin.denom).join(", "),
+                },
+                _ => ContractError::NoLpSent {},
+            })?;
             let maybe_lp = Asset::native(maybe_lp_coin.denom, maybe_lp_coin.amount);
 
             deposit(deps, env, maybe_lp, info.sender, recipient)
@@ -101,38 +133,142 @@ pub fn execute(
             lp_token,
             amount,
             user,
-        } => withdraw(deps, env, info, lp_token, amount, user),
-        ExecuteMsg::SetTokensPerSecond { amount } => set_tokens_per_second(deps, env, info, amount),
+            bridge,
+        } => {
+            ensure_not_paused(deps.storage)?;
+            ensure!(
+                user.is_none() || bridge.is_none(),
+                ContractError::BridgeOnBehalfNotAllowed {}
+            );
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            withdraw(deps, env, info, lp_token, amount, user, bridge)
+        }
+        ExecuteMsg::EmergencyWithdraw { lp_token } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            emergency_withdraw(deps, env, info, lp_token)
+        }
+        ExecuteMsg::SetTokensPerSecond {
+            amount,
+            effective_ts,
+        } => set_tokens_per_second(deps, env, info, amount, effective_ts),
+        ExecuteMsg::ReconcileAllocPoints {} => reconcile_alloc_points(deps, env, info),
         ExecuteMsg::Incentivize { lp_token, schedule } => {
-            incentivize(deps, info, env, lp_token, schedule)
+            ensure_not_paused(deps.storage)?;
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            incentivize(deps, info, env, lp_token, schedule, false)
+        }
+        ExecuteMsg::IncentivizePerEpoch { lp_token, schedule } => {
+            ensure_not_paused(deps.storage)?;
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            let max_periods = CONFIG.load(deps.storage)?.max_periods;
+            let schedule = InputSchedule::per_epoch(&env, &schedule, max_periods)?;
+            incentivize(deps, info, env, lp_token, schedule, true)
         }
         ExecuteMsg::RemoveRewardFromPool {
             lp_token,
             reward,
             bypass_upcoming_schedules,
             receiver,
-        } => remove_reward_from_pool(
-            deps,
-            info,
-            env,
+        } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            remove_reward_from_pool(
+                deps,
+                info,
+                env,
+                lp_token,
+                reward,
+                bypass_upcoming_schedules,
+                receiver,
+            )
+        }
+        ExecuteMsg::Donate { lp_token } => {
+            ensure_not_paused(deps.storage)?;
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            donate(deps, env, info, lp_token)
+        }
+        ExecuteMsg::RemoveRewardsFromPool {
             lp_token,
-            reward,
+            rewards,
             bypass_upcoming_schedules,
             receiver,
-        ),
+        } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            remove_rewards_from_pool(
+                deps,
+                info,
+                env,
+                lp_token,
+                rewards,
+                bypass_upcoming_schedules,
+                receiver,
+            )
+        }
+        ExecuteMsg::ExpireSchedule { lp_token, reward } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            expire_schedule(deps, info, env, lp_token, reward)
+        }
+        ExecuteMsg::RemapRewardToken { old, new } => remap_reward_token(deps, info, old, new),
         ExecuteMsg::ClaimOrphanedRewards { limit, receiver } => {
             claim_orphaned_rewards(deps, info, limit, receiver)
         }
         ExecuteMsg::UpdateConfig {
             generator_controller,
             incentivization_fee_info,
-        } => update_config(deps, info, generator_controller, incentivization_fee_info),
+            max_periods,
+            claim_history_enabled,
+            vepadex,
+            max_user_pools,
+            block_ibc_rewards,
+            min_schedule_duration,
+            max_orphaned_entries,
+            keeper_bounty,
+        } => update_config(
+            deps,
+            info,
+            generator_controller,
+            incentivization_fee_info,
+            max_periods,
+            claim_history_enabled,
+            vepadex,
+            max_user_pools,
+            block_ibc_rewards,
+            min_schedule_duration,
+            max_orphaned_entries,
+            keeper_bounty,
+        ),
         ExecuteMsg::UpdateBlockedTokenslist { add, remove } => {
             update_blocked_pool_tokens(deps, env, info, add, remove)
         }
-        ExecuteMsg::DeactivatePool { lp_token } => deactivate_pool(deps, info, env, lp_token),
-        ExecuteMsg::DeactivateBlockedPools {} => deactivate_blocked_pools(deps, env),
-        ExecuteMsg::ProposeNewOwner { owner, expires_in } => {
+        ExecuteMsg::FreezeReward { reward } => freeze_reward(deps, info, reward, true),
+        ExecuteMsg::UnfreezeReward { reward } => freeze_reward(deps, info, reward, false),
+        ExecuteMsg::UpdateLpRewardWhitelist { add, remove } => {
+            update_lp_reward_whitelist(deps, info, add, remove)
+        }
+        ExecuteMsg::SetIncentivizationFee {
+            fee,
+            fee_receiver,
+            clear,
+        } => set_incentivization_fee(deps, info, fee, fee_receiver, clear),
+        ExecuteMsg::SetPaused { paused } => set_paused(deps, info, paused),
+        ExecuteMsg::UpdateFeeExemptList { add, remove } => {
+            update_fee_exempt_list(deps, info, add, remove)
+        }
+        ExecuteMsg::DeactivatePool { lp_token } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            deactivate_pool(deps, info, env, lp_token)
+        }
+        ExecuteMsg::DeactivateBlockedPools {} => deactivate_blocked_pools(deps, env, info),
+        ExecuteMsg::FundKeeperBounty {} => fund_keeper_bounty(deps, info),
+        ExecuteMsg::WithdrawKeeperBountyFund {
+            denom,
+            amount,
+            receiver,
+        } => withdraw_keeper_bounty_fund(deps, info, denom, amount, receiver),
+        ExecuteMsg::ProposeNewOwner {
+            owner,
+            expires_in,
+            delay,
+        } => {
             let config = CONFIG.load(deps.storage)?;
 
             propose_new_owner(
@@ -141,6 +277,7 @@ pub fn execute(
                 env,
                 owner,
                 expires_in,
+                delay.unwrap_or_default(),
                 config.owner,
                 OWNERSHIP_PROPOSAL,
             )
@@ -161,12 +298,48 @@ pub fn execute(
                     })
                     .map(|_| ())
             })
-            .map_err(Into::into)
         }
         ExecuteMsg::SetBridge {
             erc20_address,
             chain_reference_id,
         } => set_bridge(deps, info, erc20_address, chain_reference_id),
+        ExecuteMsg::SetLpTokenBridge {
+            lp_token,
+            erc20_address,
+            chain_reference_id,
+        } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            set_lp_token_bridge(deps, info, lp_token, erc20_address, chain_reference_id)
+        }
+        ExecuteMsg::SetCustodyReceiver { receiver } => set_custody_receiver(deps, info, receiver),
+        ExecuteMsg::UpdateAllowedReceivers { add, remove } => {
+            update_allowed_receivers(deps, info, add, remove)
+        }
+        ExecuteMsg::SetLpTokenPair { lp_token, pair } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            set_lp_token_pair(deps, info, lp_token, pair)
+        }
+        ExecuteMsg::SetPoolDistribution {
+            lp_token,
+            distribution,
+        } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            set_pool_distribution(deps, env, info, lp_token, distribution)
+        }
+        ExecuteMsg::SetPoolMaxRewardTokens {
+            lp_token,
+            max_reward_tokens,
+        } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            set_pool_max_reward_tokens(deps, env, info, lp_token, max_reward_tokens)
+        }
+        ExecuteMsg::SetPoolMinFirstDeposit {
+            lp_token,
+            min_amount,
+        } => {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            set_pool_min_first_deposit(deps, env, info, lp_token, min_amount)
+        }
     }
 }
 
@@ -177,42 +350,74 @@ fn deposit(
     sender: Addr,
     recipient: Option<String>,
 ) -> Result<Response<PalomaMsg>, ContractError> {
-    let staker = if recipient.is_some() {
-        assert!(
-            sender == CONFIG.load(deps.storage)?.trader,
-            "User address must match sender address"
-        );
-        recipient.unwrap()
-    } else {
-        sender.to_string()
+    // In cw20 Receive path sender is the cw20 sender taken from the hook message rather than info.sender
+    let staker = match recipient {
+        Some(recipient) if recipient != sender.as_str() => {
+            ensure!(
+                Some(&sender) == CONFIG.load(deps.storage)?.trader.as_ref(),
+                ContractError::Unauthorized {}
+            );
+            deps.api.addr_validate(&recipient)?.to_string()
+        }
+        _ => sender.to_string(),
     };
 
-    let pair_info = query_pair_info(deps.as_ref(), &maybe_lp.info)?;
     let config = CONFIG.load(deps.storage)?;
-    is_pool_registered(
-        deps.querier,
-        &config,
-        &pair_info,
-        &maybe_lp.info.to_string(),
-    )?;
+    query_pair_info(deps.as_ref(), &maybe_lp.info)
+        .map_err(ContractError::from)
+        .and_then(|pair_info| {
+            is_pool_registered(
+                deps.querier,
+                &config,
+                &pair_info,
+                &maybe_lp.info.to_string(),
+            )
+        })
+        .map_err(|err| {
+            // Known pool which doesn't match factory registration anymore
+            if POOLS.has(deps.storage, &maybe_lp.info) {
+                ContractError::StaleLpToken {
+                    lp_token: maybe_lp.info.to_string(),
+                }
+            } else {
+                err
+            }
+        })?;
 
     let mut pool_info = PoolInfo::may_load(deps.storage, &maybe_lp.info)?.unwrap_or_default();
-    let mut user_info = UserInfo::may_load_position(deps.storage, &staker, &maybe_lp.info)?
-        .unwrap_or_else(|| UserInfo::new(&env));
+    if let Some(min_amount) = pool_info.min_first_deposit {
+        ensure!(
+            !pool_info.total_lp.is_zero() || maybe_lp.amount >= min_amount,
+            ContractError::FirstDepositTooSmall {
+                lp_token: maybe_lp.info.to_string(),
+                min_amount,
+            }
+        );
+    }
+    let mut user_info = match UserInfo::may_load_position(deps.storage, &staker, &maybe_lp.info)? {
+        Some(user_info) => user_info,
+        None => {
+            UserInfo::open_position(deps.storage, &staker, config.max_user_pools)?;
+            UserInfo::new(&env)
+        }
+    };
 
+    let receiver = rewards_receiver(deps.storage, sender, &staker)?;
     let response = claim_rewards(
         deps.storage,
         env,
-        sender,
+        receiver,
         &staker,
         vec![(&maybe_lp.info, &mut pool_info, &mut user_info)],
+        None,
     )?;
 
     user_info.update_and_sync_position(Op::Add(maybe_lp.amount), &mut pool_info);
-    pool_info.save(deps.storage, &maybe_lp.info)?;
+    sync_user_weight(deps.as_ref(), &staker, &mut pool_info, &mut user_info)?;
+    let events = pool_info.save(deps.storage, &maybe_lp.info)?;
     user_info.save(deps.storage, &staker, &maybe_lp.info)?;
 
-    Ok(response.add_attributes([
+    Ok(response.add_events(events).add_attributes([
         attr("action", "deposit"),
         attr("lp_token", maybe_lp.info.to_string()),
         attr("user", staker.as_str()),
@@ -224,22 +429,34 @@ fn withdraw(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    lp_token: String,
+    lp_token: LpToken,
     amount: Uint128,
     user: Option<String>,
+    bridge: Option<BridgeTarget>,
 ) -> Result<Response<PalomaMsg>, ContractError> {
-    let lp_token_asset = determine_asset_info(&lp_token, deps.api)?;
-    let user = if user.is_some() {
-        assert!(
-            info.sender == CONFIG.load(deps.storage)?.trader,
-            "User address must match sender address"
+    let lp_token_asset = lp_token.asset_info();
+    if let Some(target) = &bridge {
+        ensure!(
+            matches!(lp_token_asset, AssetInfo::NativeToken { denom }
+                if LP_TOKEN_BRIDGES.has(deps.storage, (denom, &target.chain_reference_id))),
+            ContractError::LpTokenNotBridgeable {
+                lp_token: lp_token_asset.to_string(),
+                chain_reference_id: target.chain_reference_id.clone(),
+            }
         );
-        user.unwrap()
+    }
+    let user = if let Some(user) = user {
+        ensure!(
+            Some(&info.sender) == CONFIG.load(deps.storage)?.trader.as_ref(),
+            ContractError::Unauthorized {}
+        );
+        user
     } else {
         info.sender.to_string()
     };
 
-    let mut user_info = UserInfo::load_position(deps.storage, &user, &lp_token_asset)?;
+    let mut pool_info = PoolInfo::load_existing(deps.storage, lp_token_asset)?;
+    let mut user_info = UserInfo::load_position(deps.storage, &user, lp_token_asset)?;
 
     if user_info.amount < amount {
         Err(ContractError::AmountExceedsBalance {
@@ -247,33 +464,111 @@ fn withdraw(
             withdraw_amount: amount,
         })
     } else {
-        let mut pool_info = PoolInfo::load(deps.storage, &lp_token_asset)?;
+        // Position amounts must always sum up to pool total. Fail gracefully if state drifted.
+        ensure!(
+            pool_info.total_lp >= amount,
+            ContractError::PoolAccountingMismatch {
+                lp_token: lp_token_asset.to_string(),
+                total_lp: pool_info.total_lp,
+                withdraw_amount: amount,
+            }
+        );
 
+        let receiver = rewards_receiver(deps.storage, info.sender.clone(), &user)?;
         let response = claim_rewards(
             deps.storage,
             env,
-            info.sender.clone(),
+            receiver,
             &user,
-            vec![(&lp_token_asset, &mut pool_info, &mut user_info)],
+            vec![(lp_token_asset, &mut pool_info, &mut user_info)],
+            None,
         )?;
 
         user_info.update_and_sync_position(Op::Sub(amount), &mut pool_info);
-        pool_info.save(deps.storage, &lp_token_asset)?;
+        sync_user_weight(deps.as_ref(), &user, &mut pool_info, &mut user_info)?;
+        let events = pool_info.save(deps.storage, lp_token_asset)?;
         if user_info.amount.is_zero() {
             // If user has withdrawn all LP tokens, we can remove his position
-            user_info.remove(deps.storage, &user, &lp_token_asset);
+            user_info.remove(deps.storage, &user, lp_token_asset)?;
         } else {
-            user_info.save(deps.storage, &user, &lp_token_asset)?;
+            user_info.save(deps.storage, &user, lp_token_asset)?;
         }
 
-        let transfer_msg = lp_token_asset.with_balance(amount).into_msg(info.sender)?;
+        let transfer_msg = if let Some(target) = bridge {
+            // LP tokens are held by the contract thus Skyway can bridge them right away
+            SubMsg::new(CosmosMsg::Custom(PalomaMsg::SkywayMsg {
+                send_tx: Some(SendTx {
+                    remote_chain_destination_address: target.remote_chain_destination_address,
+                    amount: format!("{amount}{lp_token_asset}"),
+                    chain_reference_id: target.chain_reference_id,
+                }),
+                set_erc20_to_denom: None,
+            }))
+        } else if lp_token_asset.is_native_token() {
+            SubMsg::new(lp_token_asset.with_balance(amount).into_msg(info.sender)?)
+        } else {
+            // Position is restored in reply if cw20 LP token reverts the transfer
+            let transfer_msg = lp_token_asset.with_balance(amount).into_msg(info.sender)?;
+            SubMsg::reply_on_error(transfer_msg, WITHDRAW_TRANSFER_REPLY_ID).with_payload(
+                to_json_binary(&WithdrawTransferInfo {
+                    user,
+                    lp_token: lp_token_asset.clone(),
+                    amount,
+                })?,
+            )
+        };
+
+        Ok(response
+            .add_submessage(transfer_msg)
+            .add_events(events)
+            .add_attributes([
+                attr("action", "withdraw"),
+                attr("lp_token", lp_token_asset.to_string()),
+                attr("amount", amount),
+            ]))
+    }
+}
+
+/// Withdraws the whole sender's position without claiming any rewards.
+/// Doesn't query external contracts so it works even if they are broken.
+fn emergency_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lp_token: LpToken,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let lp_token_asset = lp_token.asset_info();
+    let user = info.sender.to_string();
+
+    let mut user_info = UserInfo::load_position(deps.storage, &user, lp_token_asset)?;
+    let mut pool_info = PoolInfo::load(deps.storage, lp_token_asset)?;
+    let amount = user_info.amount;
+    ensure!(
+        pool_info.total_lp >= amount,
+        ContractError::PoolAccountingMismatch {
+            lp_token: lp_token_asset.to_string(),
+            total_lp: pool_info.total_lp,
+            withdraw_amount: amount,
+        }
+    );
+
+    // Accrue rewards for the remaining stakers before pool total changes
+    pool_info.update_rewards(deps.storage, &env, lp_token_asset)?;
+    user_info.update_and_sync_position(Op::Sub(amount), &mut pool_info);
+    user_info.set_weight(Uint128::zero(), &mut pool_info)?;
+    let events = pool_info.save(deps.storage, lp_token_asset)?;
+    user_info.remove(deps.storage, &user, lp_token_asset)?;
 
-        Ok(response.add_message(transfer_msg).add_attributes([
-            attr("action", "withdraw"),
+    let transfer_msg = lp_token_asset.with_balance(amount).into_msg(info.sender)?;
+
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_events(events)
+        .add_attributes([
+            attr("action", "emergency_withdraw"),
             attr("lp_token", lp_token_asset.to_string()),
             attr("amount", amount),
         ]))
-    }
 }
 
 pub fn setup_pools(
@@ -282,78 +577,47 @@ pub fn setup_pools(
     info: MessageInfo,
     pools: Vec<(String, Uint128)>,
 ) -> Result<Response<PalomaMsg>, ContractError> {
+    let mut events = apply_scheduled_padex_rate(deps.storage, &env)?;
     let mut config = CONFIG.load(deps.storage)?;
     if info.sender != config.owner && Some(info.sender) != config.generator_controller {
         return Err(ContractError::Unauthorized {});
     }
 
-    let mut pools_set: HashSet<_> = Default::default();
-    for (pool, alloc_points) in &pools {
-        if alloc_points.is_zero() {
-            return Err(ContractError::ZeroAllocPoint {
-                lp_token: pool.to_owned(),
-            });
-        }
-
-        if !pools_set.insert(pool) {
-            return Err(ContractError::DuplicatedPoolFound {});
-        }
-    }
-
-    let blacklisted_pair_types: Vec<PairType> = deps
-        .querier
-        .query_wasm_smart(&config.factory, &FactoryQueryMsg::BlacklistedPairTypes {})?;
-
-    let setup_pools = pools
-        .into_iter()
-        .map(|(lp_token, alloc_point)| {
-            let maybe_lp = determine_asset_info(&lp_token, deps.api)?;
-            let pair_info = query_pair_info(deps.as_ref(), &maybe_lp)?;
-
-            is_pool_registered(deps.querier, &config, &pair_info, &lp_token)?;
-
-            // check if assets in the blocked list
-            for asset in &pair_info.asset_infos {
-                if BLOCKED_TOKENS.has(deps.storage, &asset_info_key(asset)) {
-                    return Err(ContractError::BlockedToken {
-                        token: asset.to_string(),
-                    });
-                }
-            }
-
-            // check if pair type is blacklisted
-            if blacklisted_pair_types.contains(&pair_info.pair_type) {
-                return Err(ContractError::BlockedPairType {
-                    pair_type: pair_info.pair_type,
-                });
-            }
-
-            Ok((maybe_lp, alloc_point))
-        })
-        .collect::<Result<Vec<_>, ContractError>>()?;
+    let (setup_pools, total_alloc_points) = validate_setup_pools(deps.as_ref(), &config, pools)?;
 
-    // Update all reward indexes and remove padex rewards from old active pools
+    // Update all reward indexes and remove padex rewards from old active pools.
+    // Pools which stay active are updated below so their activation time is kept
     for (lp_token_asset, _) in ACTIVE_POOLS.load(deps.storage)? {
+        if setup_pools
+            .iter()
+            .any(|(lp_token, _)| lp_token == &lp_token_asset)
+        {
+            continue;
+        }
         let mut pool_info = PoolInfo::load(deps.storage, &lp_token_asset)?;
         pool_info.update_rewards(deps.storage, &env, &lp_token_asset)?;
         pool_info.disable_padex_rewards();
-        pool_info.save(deps.storage, &lp_token_asset)?;
+        events.extend(pool_info.save(deps.storage, &lp_token_asset)?);
     }
 
-    config.total_alloc_points = setup_pools.iter().map(|(_, alloc)| alloc).sum();
+    config.total_alloc_points = total_alloc_points;
 
     // Set padex rewards for new active pools
-    for (active_pool, alloc_points) in &setup_pools {
+    let distribution = padex_rps_distribution(&config, &setup_pools);
+    for ((active_pool, _), rps) in setup_pools.iter().zip(distribution) {
         let mut pool_info = PoolInfo::may_load(deps.storage, active_pool)?.unwrap_or_default();
         pool_info.update_rewards(deps.storage, &env, active_pool)?;
-        pool_info.set_padex_rewards(&config, *alloc_points);
-        pool_info.save(deps.storage, active_pool)?;
+        pool_info.set_padex_rewards(&config, rps);
+        events.extend(pool_info.save(deps.storage, active_pool)?);
     }
 
     ACTIVE_POOLS.save(deps.storage, &setup_pools)?;
+    save_scheduled_pools_rps(deps.storage, &config, &setup_pools)?;
     CONFIG.save(deps.storage, &config)?;
 
-    Ok(Response::new().add_attribute("action", "setup_pools"))
+    Ok(Response::new()
+        .add_events(events)
+        .add_attribute("action", "setup_pools"))
 }
 
 fn set_tokens_per_second(
@@ -361,7 +625,9 @@ fn set_tokens_per_second(
     env: Env,
     info: MessageInfo,
     amount: Uint128,
+    effective_ts: Option<u64>,
 ) -> Result<Response<PalomaMsg>, ContractError> {
+    let mut events = apply_scheduled_padex_rate(deps.storage, &env)?;
     let mut config = CONFIG.load(deps.storage)?;
 
     // Permission check
@@ -369,33 +635,170 @@ fn set_tokens_per_second(
         return Err(ContractError::Unauthorized {});
     }
 
-    let pool_infos = ACTIVE_POOLS
-        .load(deps.storage)?
-        .into_iter()
-        .map(|(lp_token, alloc_points)| {
-            let mut pool_info = PoolInfo::load(deps.storage, &lp_token)?;
-            pool_info.update_rewards(deps.storage, &env, &lp_token)?;
-            Ok((pool_info, lp_token, alloc_points))
+    if let Some(effective_ts) = effective_ts {
+        ensure!(
+            effective_ts > env.block.time.seconds(),
+            ContractError::PastEffectiveTime { effective_ts }
+        );
+        SCHEDULED_PADEX_RATE.save(
+            deps.storage,
+            &ScheduledPadexRate {
+                padex_per_second: amount,
+                effective_ts,
+            },
+        )?;
+        let active_pools = ACTIVE_POOLS.load(deps.storage)?;
+        save_scheduled_pools_rps(deps.storage, &config, &active_pools)?;
+
+        return Ok(Response::new().add_events(events).add_attributes([
+            attr("action", "schedule_tokens_per_second"),
+            attr("padex_per_second", amount),
+            attr("effective_ts", effective_ts.to_string()),
+        ]));
+    }
+
+    let active_pools = ACTIVE_POOLS.load(deps.storage)?;
+    let pool_infos = active_pools
+        .iter()
+        .map(|(lp_token, _)| {
+            let mut pool_info = PoolInfo::load(deps.storage, lp_token)?;
+            pool_info.update_rewards(deps.storage, &env, lp_token)?;
+            Ok((pool_info, lp_token))
         })
         .collect::<StdResult<Vec<_>>>()?;
 
     config.padex_per_second = amount;
 
-    for (mut pool_info, lp_token, alloc_points) in pool_infos {
-        pool_info.set_padex_rewards(&config, alloc_points);
-        pool_info.save(deps.storage, &lp_token)?;
+    let distribution = padex_rps_distribution(&config, &active_pools);
+    for ((mut pool_info, lp_token), rps) in pool_infos.into_iter().zip(distribution) {
+        pool_info.set_padex_rewards(&config, rps);
+        events.extend(pool_info.save(deps.storage, lp_token)?);
+    }
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_events(events)
+        .add_attribute("action", "set_tokens_per_second"))
+}
+
+fn fund_keeper_bounty(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let bounty = CONFIG
+        .load(deps.storage)?
+        .keeper_bounty
+        .ok_or(ContractError::KeeperBountyNotSet {})?;
+    let amount = must_pay(&info, &bounty.denom)?;
+
+    let fund = KEEPER_BOUNTY_FUNDS.update::<_, StdError>(deps.storage, &bounty.denom, |fund| {
+        Ok(fund.unwrap_or_default() + amount)
+    })?;
+
+    Ok(Response::new().add_attributes([
+        attr("action", "fund_keeper_bounty"),
+        attr("amount", amount),
+        attr("keeper_bounty_fund", fund),
+    ]))
+}
+
+fn withdraw_keeper_bounty_fund(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    amount: Option<Uint128>,
+    receiver: Option<String>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure!(info.sender == config.owner, ContractError::Unauthorized {});
+
+    let receiver = receiver
+        .map(|receiver| deps.api.addr_validate(&receiver))
+        .transpose()?
+        .unwrap_or(config.owner);
+    let fund = KEEPER_BOUNTY_FUNDS
+        .may_load(deps.storage, &denom)?
+        .unwrap_or_default();
+    let amount = amount.unwrap_or(fund);
+    ensure!(
+        !amount.is_zero() && amount <= fund,
+        ContractError::KeeperBountyFundTooLow { fund, amount }
+    );
+
+    let remaining = fund - amount;
+    if remaining.is_zero() {
+        KEEPER_BOUNTY_FUNDS.remove(deps.storage, &denom);
+    } else {
+        KEEPER_BOUNTY_FUNDS.save(deps.storage, &denom, &remaining)?;
+    }
+
+    let withdrawn = Coin::new(amount, denom);
+    Ok(Response::new()
+        .add_attributes([
+            attr("action", "withdraw_keeper_bounty_fund"),
+            attr("amount", withdrawn.to_string()),
+            attr("receiver", &receiver),
+            attr("keeper_bounty_fund", remaining),
+        ])
+        .add_message(BankMsg::Send {
+            to_address: receiver.to_string(),
+            amount: vec![withdrawn],
+        }))
+}
+
+fn reconcile_alloc_points(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let mut events = apply_scheduled_padex_rate(deps.storage, &env)?;
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let active_pools = ACTIVE_POOLS.load(deps.storage)?;
+    let previous_total = config.total_alloc_points;
+    config.total_alloc_points = active_pools
+        .iter()
+        .map(|(_, alloc_points)| alloc_points)
+        .sum();
+
+    let distribution = padex_rps_distribution(&config, &active_pools);
+    for ((lp_asset, _), rps) in active_pools.iter().zip(distribution) {
+        let mut pool_info = PoolInfo::load(deps.storage, lp_asset)?;
+        pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+        pool_info.set_padex_rewards(&config, rps);
+        events.extend(pool_info.save(deps.storage, lp_asset)?);
     }
 
+    save_scheduled_pools_rps(deps.storage, &config, &active_pools)?;
     CONFIG.save(deps.storage, &config)?;
 
-    Ok(Response::new().add_attribute("action", "set_tokens_per_second"))
+    Ok(Response::new().add_events(events).add_attributes([
+        attr("action", "reconcile_alloc_points"),
+        attr("previous_total_alloc_points", previous_total),
+        attr("total_alloc_points", config.total_alloc_points),
+    ]))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_config(
     deps: DepsMut,
     info: MessageInfo,
     generator_controller: Option<String>,
     incentivization_fee_info: Option<IncentivizationFeeInfo>,
+    max_periods: Option<u64>,
+    claim_history_enabled: Option<bool>,
+    vepadex: Option<String>,
+    max_user_pools: Option<u32>,
+    block_ibc_rewards: Option<bool>,
+    min_schedule_duration: Option<u64>,
+    max_orphaned_entries: Option<u32>,
+    keeper_bounty: Option<Coin>,
 ) -> Result<Response<PalomaMsg>, ContractError> {
     let mut config = CONFIG.load(deps.storage)?;
 
@@ -423,19 +826,71 @@ fn update_config(
         config.incentivization_fee_info = Some(new_info);
     }
 
+    if let Some(max_periods) = max_periods {
+        if max_periods == 0 {
+            return Err(ContractError::ZeroMaxPeriods {});
+        }
+        config.max_periods = max_periods;
+        attrs.push(attr("new_max_periods", max_periods.to_string()));
+    }
+
+    if let Some(claim_history_enabled) = claim_history_enabled {
+        config.claim_history_enabled = claim_history_enabled;
+        attrs.push(attr(
+            "claim_history_enabled",
+            claim_history_enabled.to_string(),
+        ));
+    }
+
+    if let Some(vepadex) = vepadex {
+        config.vepadex = Some(deps.api.addr_validate(&vepadex)?);
+        attrs.push(attr("new_vepadex", vepadex));
+    }
+
+    if let Some(max_user_pools) = max_user_pools {
+        config.max_user_pools = (max_user_pools > 0).then_some(max_user_pools);
+        attrs.push(attr("new_max_user_pools", max_user_pools.to_string()));
+    }
+
+    if let Some(block_ibc_rewards) = block_ibc_rewards {
+        config.block_ibc_rewards = block_ibc_rewards;
+        attrs.push(attr("block_ibc_rewards", block_ibc_rewards.to_string()));
+    }
+
+    if let Some(min_schedule_duration) = min_schedule_duration {
+        config.min_schedule_duration = min_schedule_duration;
+        attrs.push(attr(
+            "new_min_schedule_duration",
+            min_schedule_duration.to_string(),
+        ));
+    }
+
+    if let Some(max_orphaned_entries) = max_orphaned_entries {
+        config.max_orphaned_entries = (max_orphaned_entries > 0).then_some(max_orphaned_entries);
+        attrs.push(attr(
+            "new_max_orphaned_entries",
+            max_orphaned_entries.to_string(),
+        ));
+    }
+
+    if let Some(keeper_bounty) = keeper_bounty {
+        validate_native_denom(&keeper_bounty.denom)?;
+        attrs.push(attr("new_keeper_bounty", keeper_bounty.to_string()));
+        config.keeper_bounty = (!keeper_bounty.amount.is_zero()).then_some(keeper_bounty);
+    }
+
     CONFIG.save(deps.storage, &config)?;
 
     Ok(Response::new().add_attributes(attrs))
 }
 
-fn update_blocked_pool_tokens(
+fn update_lp_reward_whitelist(
     deps: DepsMut,
-    env: Env,
     info: MessageInfo,
     add: Vec<AssetInfo>,
     remove: Vec<AssetInfo>,
 ) -> Result<Response<PalomaMsg>, ContractError> {
-    let mut config = CONFIG.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
 
     // Permission check
     if info.sender != config.owner {
@@ -448,67 +903,286 @@ fn update_blocked_pool_tokens(
         StdError::generic_err("Duplicated tokens found")
     );
 
-    // Remove tokens from blocklist
-    for asset_info in remove {
-        let asset_info_key = asset_info_key(&asset_info);
-        ensure!(
-            BLOCKED_TOKENS.has(deps.storage, &asset_info_key),
-            StdError::generic_err(format!(
-                "Token {asset_info} wasn't found in the blocked list",
-            ))
-        );
-
-        BLOCKED_TOKENS.remove(deps.storage, &asset_info_key);
+    for token in &remove {
+        LP_REWARD_WHITELIST.remove(deps.storage, &asset_info_key(token));
+    }
+    for token in &add {
+        token.check(deps.api)?;
+        LP_REWARD_WHITELIST.save(deps.storage, &asset_info_key(token), &())?;
     }
 
-    // Add tokens to blocklist
-    if !add.is_empty() {
-        let active_pools = ACTIVE_POOLS
-            .load(deps.storage)?
-            .into_iter()
-            .map(|(lp_asset, alloc_points)| {
-                let asset_infos = query_pair_info(deps.as_ref(), &lp_asset)?.asset_infos;
-                Ok((lp_asset, asset_infos, alloc_points))
-            })
-            .collect::<StdResult<Vec<_>>>()?;
+    let mut attrs = vec![attr("action", "update_lp_reward_whitelist")];
+    attrs.extend(add.iter().map(|token| attr("add", token.to_string())));
+    attrs.extend(remove.iter().map(|token| attr("remove", token.to_string())));
 
-        let mut to_disable = vec![];
+    Ok(Response::new().add_attributes(attrs))
+}
 
-        for token_to_block in &add {
-            let asset_info_key = asset_info_key(token_to_block);
-            if !BLOCKED_TOKENS.has(deps.storage, &asset_info_key) {
-                if token_to_block.eq(&config.padex_token) {
-                    return Err(StdError::generic_err(format!(
-                        "Blocking PADEX token {token_to_block} is prohibited",
-                    ))
-                    .into());
-                }
+fn freeze_reward(
+    deps: DepsMut,
+    info: MessageInfo,
+    reward: AssetInfo,
+    freeze: bool,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
 
-                for (lp_asset, asset_infos, alloc_points) in &active_pools {
-                    if asset_infos.contains(token_to_block) {
-                        to_disable.push((lp_asset.clone(), alloc_points));
-                    }
-                }
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
 
-                BLOCKED_TOKENS.save(deps.storage, &asset_info_key, &())?;
-            } else {
-                return Err(StdError::generic_err(format!(
-                    "Token {token_to_block} is already in the blocked list",
-                ))
-                .into());
+    let key = asset_info_key(&reward);
+    let action = if freeze {
+        reward.check(deps.api)?;
+        ensure!(
+            !FROZEN_REWARDS.has(deps.storage, &key),
+            ContractError::RewardAlreadyFrozen {
+                reward: reward.to_string()
             }
-        }
+        );
+        FROZEN_REWARDS.save(deps.storage, &key, &())?;
+        "freeze_reward"
+    } else {
+        ensure!(
+            FROZEN_REWARDS.has(deps.storage, &key),
+            ContractError::RewardNotFrozen {
+                reward: reward.to_string()
+            }
+        );
+        FROZEN_REWARDS.remove(deps.storage, &key);
+        "unfreeze_reward"
+    };
+
+    Ok(
+        Response::new()
+            .add_attributes([attr("action", action), attr("reward", reward.to_string())]),
+    )
+}
+
+fn ensure_not_paused(storage: &dyn Storage) -> Result<(), ContractError> {
+    ensure!(
+        !CONFIG.load(storage)?.paused,
+        ContractError::ContractPaused {}
+    );
+    Ok(())
+}
+
+fn set_incentivization_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee: Option<Coin>,
+    fee_receiver: Option<String>,
+    clear: bool,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if clear {
+        ensure!(
+            fee.is_none() && fee_receiver.is_none(),
+            StdError::generic_err("Clearing incentivization fee can't be combined with new values")
+        );
+        config.incentivization_fee_info = None;
+        CONFIG.save(deps.storage, &config)?;
+
+        return Ok(Response::new().add_attributes([
+            attr("action", "set_incentivization_fee"),
+            attr("incentivization_fee", "none"),
+        ]));
+    }
+
+    let fee_receiver = fee_receiver
+        .map(|receiver| deps.api.addr_validate(&receiver))
+        .transpose()?;
+    if let Some(fee) = &fee {
+        validate_native_denom(&fee.denom)?;
+    }
+
+    let new_info = match (config.incentivization_fee_info, fee, fee_receiver) {
+        (Some(current), fee, fee_receiver) => IncentivizationFeeInfo {
+            fee_receiver: fee_receiver.unwrap_or(current.fee_receiver),
+            fee: fee.unwrap_or(current.fee),
+        },
+        (None, Some(fee), Some(fee_receiver)) => IncentivizationFeeInfo { fee_receiver, fee },
+        (None, ..) => {
+            return Err(StdError::generic_err(
+                "Incentivization fee is not set. Both fee and fee_receiver must be provided",
+            )
+            .into())
+        }
+    };
+
+    let attrs = [
+        attr("action", "set_incentivization_fee"),
+        attr("new_incentivization_fee_receiver", &new_info.fee_receiver),
+        attr("new_incentivization_fee", new_info.fee.to_string()),
+    ];
+    config.incentivization_fee_info = Some(new_info);
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+fn set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    config.paused = paused;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes([
+        attr("action", "set_paused"),
+        attr("paused", paused.to_string()),
+    ]))
+}
+
+fn update_fee_exempt_list(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Checking for duplicates
+    ensure!(
+        remove.iter().chain(add.iter()).all_unique(),
+        StdError::generic_err("Duplicated addresses found")
+    );
+
+    for addr in &remove {
+        let addr = deps.api.addr_validate(addr)?;
+        config.fee_exempt.retain(|exempt| exempt != addr);
+    }
+    for addr in &add {
+        let addr = deps.api.addr_validate(addr)?;
+        if !config.fee_exempt.contains(&addr) {
+            config.fee_exempt.push(addr);
+        }
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    let mut attrs = vec![attr("action", "update_fee_exempt_list")];
+    attrs.extend(add.into_iter().map(|addr| attr("add", addr)));
+    attrs.extend(remove.into_iter().map(|addr| attr("remove", addr)));
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+fn update_blocked_pool_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    add: Vec<AssetInfo>,
+    remove: Vec<AssetInfo>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let mut events = apply_scheduled_padex_rate(deps.storage, &env)?;
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Checking for duplicates
+    ensure!(
+        remove.iter().chain(add.iter()).all_unique(),
+        StdError::generic_err("Duplicated tokens found")
+    );
+
+    // Remove tokens from blocklist
+    for asset_info in remove {
+        let asset_info_key = asset_info_key(&asset_info);
+        ensure!(
+            BLOCKED_TOKENS.has(deps.storage, &asset_info_key),
+            StdError::generic_err(format!(
+                "Token {asset_info} wasn't found in the blocked list",
+            ))
+        );
+
+        BLOCKED_TOKENS.remove(deps.storage, &asset_info_key);
+    }
+
+    // Add tokens to blocklist
+    if !add.is_empty() {
+        let mut pair_infos = HashMap::new();
+        let active_pools = ACTIVE_POOLS
+            .load(deps.storage)?
+            .into_iter()
+            .map(|(lp_asset, alloc_points)| {
+                let asset_infos =
+                    query_pair_info_cached(deps.as_ref(), &mut pair_infos, &lp_asset)?.asset_infos;
+                Ok((lp_asset, asset_infos, alloc_points))
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut to_disable = vec![];
+
+        for token_to_block in &add {
+            let asset_info_key = asset_info_key(token_to_block);
+            if !BLOCKED_TOKENS.has(deps.storage, &asset_info_key) {
+                if token_to_block.eq(&config.padex_token) {
+                    return Err(StdError::generic_err(format!(
+                        "Blocking PADEX token {token_to_block} is prohibited",
+                    ))
+                    .into());
+                }
+
+                // Active schedules must be deregistered explicitly via remove_reward_from_pool
+                // so that remaining rewards are returned to the receiver
+                if let Some(lp_asset) =
+                    find_pool_with_active_reward(deps.as_ref(), &env, token_to_block)?
+                {
+                    return Err(ContractError::BlockedTokenIsActiveReward {
+                        token: token_to_block.to_string(),
+                        lp_token: lp_asset.to_string(),
+                    });
+                }
+
+                for (lp_asset, asset_infos, alloc_points) in &active_pools {
+                    if asset_infos.contains(token_to_block) {
+                        to_disable.push((lp_asset.clone(), alloc_points));
+                    }
+                }
+
+                BLOCKED_TOKENS.save(deps.storage, &asset_info_key, &())?;
+            } else {
+                return Err(StdError::generic_err(format!(
+                    "Token {token_to_block} is already in the blocked list",
+                ))
+                .into());
+            }
+        }
 
         if !to_disable.is_empty() {
-            let mut reduce_total_alloc_points = Uint128::zero();
+            let mut disabled_alloc_points = Uint128::zero();
 
             // Update all reward indexes and remove padex rewards from disabled pools
             for (lp_token_asset, alloc_points) in &to_disable {
                 let mut pool_info = PoolInfo::load(deps.storage, lp_token_asset)?;
                 pool_info.update_rewards(deps.storage, &env, lp_token_asset)?;
                 pool_info.disable_padex_rewards();
-                pool_info.save(deps.storage, lp_token_asset)?;
-                reduce_total_alloc_points += *alloc_points;
+                events.extend(pool_info.save(deps.storage, lp_token_asset)?);
+                disabled_alloc_points = disabled_alloc_points
+                    .checked_add(**alloc_points)
+                    .map_err(|_| ContractError::AllocPointsOverflow {})?;
             }
 
             let new_active_pools = active_pools
@@ -525,24 +1199,232 @@ fn update_blocked_pool_tokens(
                 })
                 .collect_vec();
 
-            config.total_alloc_points = config
-                .total_alloc_points
-                .checked_sub(reduce_total_alloc_points)?;
+            reduce_total_alloc_points(&mut config, disabled_alloc_points)?;
 
-            for (lp_asset, alloc_points) in &new_active_pools {
+            let distribution = padex_rps_distribution(&config, &new_active_pools);
+            for ((lp_asset, _), rps) in new_active_pools.iter().zip(distribution) {
                 let mut pool_info = PoolInfo::load(deps.storage, lp_asset)?;
                 pool_info.update_rewards(deps.storage, &env, lp_asset)?;
-                pool_info.set_padex_rewards(&config, *alloc_points);
-                pool_info.save(deps.storage, lp_asset)?;
+                pool_info.set_padex_rewards(&config, rps);
+                events.extend(pool_info.save(deps.storage, lp_asset)?);
             }
 
             ACTIVE_POOLS.save(deps.storage, &new_active_pools)?;
+            save_scheduled_pools_rps(deps.storage, &config, &new_active_pools)?;
         }
     }
 
     CONFIG.save(deps.storage, &config)?;
 
-    Ok(Response::new().add_attribute("action", "update_tokens_blocklist"))
+    Ok(Response::new()
+        .add_events(events)
+        .add_attribute("action", "update_tokens_blocklist"))
+}
+
+fn set_custody_receiver(
+    deps: DepsMut,
+    info: MessageInfo,
+    receiver: Option<String>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let user = info.sender.to_string();
+    let mut attrs = vec![attr("action", "set_custody_receiver"), attr("user", &user)];
+
+    if let Some(receiver) = receiver {
+        let receiver = deps.api.addr_validate(&receiver)?;
+        CUSTODY_RECEIVERS.save(deps.storage, &user, &receiver)?;
+        attrs.push(attr("receiver", receiver));
+    } else {
+        CUSTODY_RECEIVERS.remove(deps.storage, &user);
+    }
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+fn update_allowed_receivers(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let user = info.sender.to_string();
+
+    // Checking for duplicates
+    ensure!(
+        remove.iter().chain(add.iter()).all_unique(),
+        StdError::generic_err("Duplicated addresses found")
+    );
+
+    for receiver in &remove {
+        let receiver = deps.api.addr_validate(receiver)?;
+        ALLOWED_RECEIVERS.remove(deps.storage, (&user, &receiver));
+    }
+    for receiver in &add {
+        let receiver = deps.api.addr_validate(receiver)?;
+        ALLOWED_RECEIVERS.save(deps.storage, (&user, &receiver), &())?;
+    }
+
+    let mut attrs = vec![
+        attr("action", "update_allowed_receivers"),
+        attr("user", &user),
+    ];
+    attrs.extend(add.iter().map(|receiver| attr("add", receiver)));
+    attrs.extend(remove.iter().map(|receiver| attr("remove", receiver)));
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+fn set_pool_distribution(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lp_token: LpToken,
+    distribution: RewardDistribution,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if distribution == RewardDistribution::VotingPowerWeighted && config.vepadex.is_none() {
+        return Err(ContractError::VepadexNotSet {});
+    }
+
+    let lp_asset = lp_token.asset_info();
+    let mut pool_info = load_pool_or_registered(deps.as_ref(), &config, &lp_token)?;
+
+    // PADEX index can't be converted between LP amount and vePADEX weight denominators
+    ensure!(
+        pool_info.total_lp.is_zero(),
+        ContractError::PoolHasStakers {
+            lp_token: lp_token.to_string(),
+        }
+    );
+
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+    pool_info.distribution = distribution;
+    let events = pool_info.save(deps.storage, lp_asset)?;
+
+    Ok(Response::new().add_events(events).add_attributes([
+        attr("action", "set_pool_distribution"),
+        attr("lp_token", lp_token.to_string()),
+        attr("distribution", format!("{distribution:?}")),
+    ]))
+}
+
+fn set_pool_max_reward_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lp_token: LpToken,
+    max_reward_tokens: Option<u8>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if let Some(limit) = max_reward_tokens {
+        ensure!(
+            (1..=MAX_POOL_REWARD_TOKENS).contains(&limit),
+            ContractError::InvalidRewardTokensLimit {}
+        );
+    }
+
+    let lp_asset = lp_token.asset_info();
+    let mut pool_info = load_pool_or_registered(deps.as_ref(), &config, &lp_token)?;
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+    pool_info.max_reward_tokens = max_reward_tokens;
+    let effective_limit = pool_info.max_reward_tokens();
+    let events = pool_info.save(deps.storage, lp_asset)?;
+
+    Ok(Response::new().add_events(events).add_attributes([
+        attr("action", "set_pool_max_reward_tokens"),
+        attr("lp_token", lp_token.to_string()),
+        attr("max_reward_tokens", effective_limit.to_string()),
+    ]))
+}
+
+fn set_pool_min_first_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lp_token: LpToken,
+    min_amount: Option<Uint128>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let lp_asset = lp_token.asset_info();
+    let mut pool_info = load_pool_or_registered(deps.as_ref(), &config, &lp_token)?;
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+    pool_info.min_first_deposit = min_amount;
+    let events = pool_info.save(deps.storage, lp_asset)?;
+
+    let mut attrs = vec![
+        attr("action", "set_pool_min_first_deposit"),
+        attr("lp_token", lp_token.to_string()),
+    ];
+    if let Some(min_amount) = min_amount {
+        attrs.push(attr("min_amount", min_amount));
+    }
+
+    Ok(Response::new().add_events(events).add_attributes(attrs))
+}
+
+fn set_lp_token_pair(
+    deps: DepsMut,
+    info: MessageInfo,
+    lp_token: LpToken,
+    pair: Option<String>,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Permission check
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let denom = match lp_token.asset_info() {
+        AssetInfo::NativeToken { denom } => denom,
+        AssetInfo::Token { .. } => {
+            return Err(ContractError::NativeLpTokenExpected {
+                lp_token: lp_token.to_string(),
+            })
+        }
+    };
+
+    if let Some(pair) = &pair {
+        let pair = deps.api.addr_validate(pair)?;
+        let pair_info: PairInfo = deps
+            .querier
+            .query_wasm_smart(&pair, &PairQueryMsg::Pair {})?;
+        ensure!(
+            pair_info.liquidity_token.as_str() == denom,
+            ContractError::LpTokenPairMismatch {
+                lp_token: lp_token.to_string(),
+                pair: pair.to_string(),
+            }
+        );
+        is_pool_registered(deps.querier, &config, &pair_info, denom)?;
+
+        LP_TOKEN_PAIRS.save(deps.storage, denom, &pair)?;
+    } else {
+        LP_TOKEN_PAIRS.remove(deps.storage, denom);
+    }
+
+    Ok(Response::new().add_attributes([
+        attr("action", "set_lp_token_pair"),
+        attr("lp_token", lp_token.to_string()),
+        attr("pair", pair.unwrap_or_default()),
+    ]))
 }
 
 fn set_bridge(
@@ -560,11 +1442,4340 @@ fn set_bridge(
 
     Ok(Response::new()
         .add_message(CosmosMsg::Custom(PalomaMsg::SkywayMsg {
-            set_erc20_to_denom: SetErc20ToDenom {
+            send_tx: None,
+            set_erc20_to_denom: Some(SetErc20ToDenom {
                 erc20_address,
                 token_denom: config.padex_token.to_string(),
                 chain_reference_id,
-            },
+            }),
         }))
         .add_attribute("action", "set_bridge"))
 }
+
+fn set_lp_token_bridge(
+    deps: DepsMut,
+    info: MessageInfo,
+    lp_token: LpToken,
+    erc20_address: String,
+    chain_reference_id: String,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure!(info.sender == config.owner, ContractError::Unauthorized {});
+
+    let AssetInfo::NativeToken { denom } = lp_token.asset_info() else {
+        return Err(ContractError::LpTokenNotBridgeable {
+            lp_token: lp_token.to_string(),
+            chain_reference_id,
+        });
+    };
+    LP_TOKEN_BRIDGES.save(deps.storage, (denom, &chain_reference_id), &erc20_address)?;
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Custom(PalomaMsg::SkywayMsg {
+            send_tx: None,
+            set_erc20_to_denom: Some(SetErc20ToDenom {
+                erc20_address: erc20_address.clone(),
+                token_denom: denom.clone(),
+                chain_reference_id: chain_reference_id.clone(),
+            }),
+        }))
+        .add_attributes([
+            attr("action", "set_lp_token_bridge"),
+            attr("lp_token", denom),
+            attr("erc20_address", erc20_address),
+            attr("chain_reference_id", chain_reference_id),
+        ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
+    use cosmwasm_std::{
+        coin, coins, to_json_binary, to_json_string, BankMsg, ContractResult, CosmosMsg,
+        Decimal256, Deps, Order, Reply, ReplyOn, SubMsgResult, SystemError, SystemResult,
+        Timestamp, Uint256, WasmQuery,
+    };
+
+    use super::*;
+    use crate::asset::{PairInfo, MINIMUM_LIQUIDITY_AMOUNT};
+    use crate::constants::{EPOCH_LENGTH, MAX_CLAIM_HISTORY_LEN, MAX_REWARD_TOKENS};
+    use crate::msg::{FactoryQueryMsg, QueryMsg};
+    use crate::query::query;
+    use crate::reply::PADEX_BRIDGE_REPLY_ID;
+    use crate::state::{
+        accrue_orphaned_reward, add_orphaned_entry, push_claim_record, CLAIM_HISTORY,
+        EXTERNAL_REWARD_SCHEDULES, FINISHED_REWARD_INDEXES, HELD_REWARDS, ORPHANED_REWARDS,
+        REFUNDABLE_ORPHANED_REWARDS, REWARD_LIABILITIES, REWARD_REFUND_ADDRESSES,
+        REWARD_TOKEN_POOLS, USER_POSITIONS_COUNT,
+    };
+    use crate::testing::{block_token, claimed_amount, setup};
+    use crate::types::{
+        BridgeTarget, ClaimComplexityResponse, ClaimRecord, IncentivesSchedule, InputSchedule,
+        LockerResponse, MintMsg, PadexBridgeInfo, PadexRateResponse, PairType,
+        PendingRewardResponse, PerEpochSchedule, RewardType, ScheduleResponse,
+        SimulateIncentivizeResponse, TopPoolResponse, VepadexQueryMsg,
+    };
+
+    #[test]
+    fn blocking_pair_asset_deactivates_pool() {
+        let (mut deps, env, owner, lp_asset) = setup();
+
+        block_token(deps.as_mut(), env, &owner, AssetInfo::native("uatom")).unwrap();
+
+        assert_eq!(ACTIVE_POOLS.load(deps.as_ref().storage).unwrap(), vec![]);
+        assert_eq!(
+            CONFIG
+                .load(deps.as_ref().storage)
+                .unwrap()
+                .total_alloc_points,
+            Uint128::zero()
+        );
+        assert!(!PoolInfo::load(deps.as_ref().storage, &lp_asset)
+            .unwrap()
+            .is_active_pool());
+    }
+
+    #[test]
+    fn blocking_active_reward_token_is_rejected() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward");
+
+        let schedule = IncentivesSchedule::from_input(
+            &env,
+            &InputSchedule {
+                reward: reward.with_balance(Uint128::new(10_000_000_000)),
+                duration_periods: 1,
+                refund_address: None,
+            },
+            25,
+        )
+        .unwrap();
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info
+            .incentivize(deps.as_mut().storage, &lp_asset, &schedule)
+            .unwrap();
+        assert!(pool_info
+            .rewards
+            .iter()
+            .any(|r| r.rps > Decimal256::zero() && r.reward.asset_info() == &reward));
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+        // Only pools indexed under the token are checked
+        assert!(
+            REWARD_TOKEN_POOLS.has(deps.as_ref().storage, (&asset_info_key(&reward), &lp_asset))
+        );
+
+        let err = block_token(deps.as_mut(), env.clone(), &owner, reward.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BlockedTokenIsActiveReward {
+                token: reward.to_string(),
+                lp_token: lp_asset.to_string(),
+            }
+        );
+
+        // Finished schedules don't prevent blocking
+        env.block.time = env.block.time.plus_seconds(3 * EPOCH_LENGTH);
+        block_token(deps.as_mut(), env, &owner, reward).unwrap();
+    }
+
+    #[test]
+    fn expire_schedule_moves_remainder_to_orphaned() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward");
+
+        let schedule = IncentivesSchedule::from_input(
+            &env,
+            &InputSchedule {
+                reward: reward.with_balance(Uint128::new(10_000_000_000)),
+                duration_periods: 2,
+                refund_address: None,
+            },
+            25,
+        )
+        .unwrap();
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info.total_lp = Uint128::new(100);
+        pool_info
+            .incentivize(deps.as_mut().storage, &lp_asset, &schedule)
+            .unwrap();
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+
+        env.block.time = env.block.time.plus_seconds(1000);
+        let now = env.block.time.seconds();
+        let random = deps.api.addr_make("random");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&random, &[]),
+            ExecuteMsg::ExpireSchedule {
+                lp_token: lp_asset.to_string(),
+                reward: reward.to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::ExpireSchedule {
+                lp_token: lp_asset.to_string(),
+                reward: reward.to_string(),
+            },
+        )
+        .unwrap();
+
+        let expected: Uint128 = (schedule.rps * Decimal256::from_ratio(schedule.end_ts - now, 1u8))
+            .to_uint_floor()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            ORPHANED_REWARDS
+                .load(deps.as_ref().storage, &asset_info_key(&reward))
+                .unwrap(),
+            expected
+        );
+
+        // Accrued index is kept until the reward is finished on the next update
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        let ext_reward = pool_info
+            .rewards
+            .iter()
+            .find(|r| r.reward.asset_info() == &reward)
+            .unwrap();
+        assert_eq!(ext_reward.rps, Decimal256::zero());
+        assert!(ext_reward.index > Decimal256::zero());
+
+        env.block.time = env.block.time.plus_seconds(10);
+        pool_info
+            .update_rewards(deps.as_mut().storage, &env, &lp_asset)
+            .unwrap();
+        assert!(pool_info
+            .rewards
+            .iter()
+            .all(|r| r.reward.asset_info() != &reward));
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+        assert!(FINISHED_REWARD_INDEXES
+            .has(deps.as_ref().storage, (&lp_asset, env.block.time.seconds())));
+    }
+
+    #[test]
+    fn donation_is_split_among_current_stakers() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let lp_denom = lp_asset.to_string();
+        let (alice, bob, carol) = (
+            deps.api.addr_make("alice"),
+            deps.api.addr_make("bob"),
+            deps.api.addr_make("carol"),
+        );
+        let donor = message_info(&deps.api.addr_make("donor"), &coins(400, "ureward"));
+
+        // Nobody staked yet
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            donor.clone(),
+            ExecuteMsg::Donate {
+                lp_token: lp_denom.clone(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            ORPHANED_REWARDS
+                .load(
+                    deps.as_ref().storage,
+                    &asset_info_key(&AssetInfo::native("ureward"))
+                )
+                .unwrap(),
+            Uint128::new(400)
+        );
+
+        for (user, amount) in [(&alice, 100), (&bob, 300)] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(user, &coins(amount, &lp_denom)),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+            .unwrap();
+        }
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            donor,
+            ExecuteMsg::Donate {
+                lp_token: lp_denom.clone(),
+            },
+        )
+        .unwrap();
+        // Deposits after donation don't share it
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&carol, &coins(400, &lp_denom)),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        let claim = |deps: DepsMut, env: Env, user: &Addr| {
+            execute(
+                deps,
+                env,
+                message_info(user, &[]),
+                ExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_denom.clone()],
+                    user: None,
+                    bridge: None,
+                },
+            )
+            .unwrap()
+        };
+
+        let res = claim(deps.as_mut(), env.clone(), &alice);
+        assert_eq!(claimed_amount(&res, "ureward"), Uint128::new(100));
+
+        // Donated reward is claimable after it is moved to finished rewards
+        env.block.time = env.block.time.plus_seconds(10);
+        let res = claim(deps.as_mut(), env.clone(), &bob);
+        assert_eq!(claimed_amount(&res, "ureward"), Uint128::new(300));
+        let res = claim(deps.as_mut(), env.clone(), &alice);
+        assert_eq!(claimed_amount(&res, "ureward"), Uint128::zero());
+        let res = claim(deps.as_mut(), env, &carol);
+        assert_eq!(claimed_amount(&res, "ureward"), Uint128::zero());
+    }
+
+    #[test]
+    fn donation_is_validated_and_charged_like_schedules() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let lp_denom = lp_asset.to_string();
+        let fee_receiver = deps.api.addr_make("fee_receiver");
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.block_ibc_rewards = true;
+        config.incentivization_fee_info = Some(IncentivizationFeeInfo {
+            fee_receiver: fee_receiver.clone(),
+            fee: coin(1000, "upaloma"),
+        });
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, &lp_denom)),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        let donor = deps.api.addr_make("donor");
+        let donate = |deps: DepsMut, funds: &[Coin]| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&donor, funds),
+                ExecuteMsg::Donate {
+                    lp_token: lp_denom.clone(),
+                },
+            )
+        };
+
+        let err = donate(
+            deps.as_mut(),
+            &[coin(400, &lp_denom), coin(1000, "upaloma")],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::LpTokenAsReward {
+                reward: lp_denom.clone()
+            }
+        );
+        let err = donate(
+            deps.as_mut(),
+            &[coin(400, "ibc/ABCD"), coin(1000, "upaloma")],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::IbcRewardBlocked {
+                reward: "ibc/ABCD".to_string()
+            }
+        );
+        let err = donate(deps.as_mut(), &coins(400, "ureward")).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::IncentivizationFeeExpected { .. }
+        ));
+
+        // The fee is charged once as the donation adds a new reward to the pool
+        let res = donate(
+            deps.as_mut(),
+            &[coin(1000, "upaloma"), coin(400, "ureward")],
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: fee_receiver.to_string(),
+                amount: coins(1000, "upaloma"),
+            })
+        );
+        let res = donate(deps.as_mut(), &coins(400, "ureward")).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            REWARD_LIABILITIES
+                .load(
+                    deps.as_ref().storage,
+                    &asset_info_key(&AssetInfo::native("ureward"))
+                )
+                .unwrap(),
+            Uint128::new(800)
+        );
+    }
+
+    #[test]
+    fn withdraw_fails_on_pool_total_drift() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let lp_denom = lp_asset.to_string();
+        let alice = deps.api.addr_make("alice");
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, &lp_denom)),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        // Simulate corrupted state where pool total is less than the user's position
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info.total_lp = Uint128::new(50);
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::Withdraw {
+                lp_token: lp_denom.clone(),
+                amount: Uint128::new(100),
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PoolAccountingMismatch {
+                lp_token: lp_denom,
+                total_lp: Uint128::new(50),
+                withdraw_amount: Uint128::new(100),
+            }
+        );
+    }
+
+    #[test]
+    fn factory_minted_lp_token_resolved_via_stored_pair() {
+        let (mut deps, env, owner, _) = setup();
+        let factory = CONFIG.load(deps.as_ref().storage).unwrap().factory;
+        let pair = deps.api.addr_make("factory_lp_pair");
+        let lp_denom = format!("factory/{factory}/lp");
+        let alice = deps.api.addr_make("alice");
+
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("upaloma")],
+            contract_addr: pair.clone(),
+            liquidity_token: Addr::unchecked(&lp_denom),
+            pair_type: PairType::Xyk {},
+        };
+        let pair_addr = pair.clone();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == factory.as_str() => {
+                match from_json::<FactoryQueryMsg>(msg) {
+                    Ok(FactoryQueryMsg::Pair { .. }) => {
+                        SystemResult::Ok(ContractResult::Ok(to_json_binary(&pair_info).unwrap()))
+                    }
+                    _ => SystemResult::Ok(ContractResult::Err("unknown query".to_string())),
+                }
+            }
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == pair_addr.as_str() => {
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&pair_info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+
+        let deposit = |deps: DepsMut| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&alice, &coins(100, &lp_denom)),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+        };
+
+        // LP minter is the factory which isn't a pair
+        deposit(deps.as_mut()).unwrap_err();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetLpTokenPair {
+                lp_token: format!("factory/{pair}/lp"),
+                pair: Some(pair.to_string()),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::LpTokenPairMismatch {
+                lp_token: format!("factory/{pair}/lp"),
+                pair: pair.to_string(),
+            }
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetLpTokenPair {
+                lp_token: lp_denom.clone(),
+                pair: Some(pair.to_string()),
+            },
+        )
+        .unwrap();
+        deposit(deps.as_mut()).unwrap();
+        assert_eq!(
+            PoolInfo::load(deps.as_ref().storage, &AssetInfo::native(&lp_denom))
+                .unwrap()
+                .total_lp,
+            Uint128::new(100)
+        );
+    }
+
+    #[test]
+    fn fee_exempt_sender_skips_incentivization_fee() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let fee = coin(1000, "upaloma");
+        let fee_receiver = deps.api.addr_make("fee_receiver");
+        let partner = deps.api.addr_make("partner");
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.incentivization_fee_info = Some(IncentivizationFeeInfo {
+            fee_receiver: fee_receiver.clone(),
+            fee: fee.clone(),
+        });
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let incentivize = |deps: DepsMut, sender: &Addr, reward: &str| {
+            let reward = AssetInfo::native(reward).with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps,
+                env.clone(),
+                message_info(sender, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+        };
+
+        let err = incentivize(deps.as_mut(), &partner, "ureward").unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::IncentivizationFeeExpected { .. }
+        ));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateFeeExemptList {
+                add: vec![partner.to_string()],
+                remove: vec![],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            CONFIG.load(deps.as_ref().storage).unwrap().fee_exempt,
+            vec![partner.clone()]
+        );
+
+        let res = incentivize(deps.as_mut(), &partner, "ureward").unwrap();
+        assert!(res
+            .messages
+            .iter()
+            .all(|sub_msg| !matches!(&sub_msg.msg, CosmosMsg::Bank(BankMsg::Send { .. }))));
+    }
+
+    #[test]
+    fn min_reward_amount_matches_schedule_validation() {
+        let (_, env, _, _) = setup();
+        let reward = AssetInfo::native("ureward");
+
+        let min = IncentivesSchedule::min_reward_amount(&env, 2, 25).unwrap();
+        IncentivesSchedule::from_input(
+            &env,
+            &InputSchedule {
+                reward: reward.with_balance(min),
+                duration_periods: 2,
+                refund_address: None,
+            },
+            25,
+        )
+        .unwrap();
+        IncentivesSchedule::from_input(
+            &env,
+            &InputSchedule {
+                reward: reward.with_balance(min - Uint128::one()),
+                duration_periods: 2,
+                refund_address: None,
+            },
+            25,
+        )
+        .unwrap_err();
+
+        IncentivesSchedule::min_reward_amount(&env, 26, 25).unwrap_err();
+    }
+
+    #[test]
+    fn emergency_withdraw_available_while_paused() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let lp_denom = lp_asset.to_string();
+        let alice = deps.api.addr_make("alice");
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, &lp_denom)),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetPaused { paused: true },
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, &lp_denom)),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ContractPaused {});
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_denom.clone()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ContractPaused {});
+
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::EmergencyWithdraw {
+                lp_token: lp_denom.clone(),
+            },
+        )
+        .unwrap();
+        assert_eq!(claimed_amount(&res, &lp_denom), Uint128::new(100));
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            PoolInfo::load(deps.as_ref().storage, &lp_asset)
+                .unwrap()
+                .total_lp,
+            Uint128::zero()
+        );
+        assert!(
+            UserInfo::may_load_position(deps.as_ref().storage, &alice.to_string(), &lp_asset)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn lp_token_reward_requires_whitelisting() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let reward = lp_asset.with_balance(Uint128::new(10_000_000_000));
+        let sender = deps.api.addr_make("incentivizer");
+
+        let incentivize = |deps: DepsMut| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&sender, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward: reward.clone(),
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+        };
+
+        let err = incentivize(deps.as_mut()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::LpTokenAsReward {
+                reward: lp_asset.to_string(),
+            }
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateLpRewardWhitelist {
+                add: vec![lp_asset.clone()],
+                remove: vec![],
+            },
+        )
+        .unwrap();
+        incentivize(deps.as_mut()).unwrap();
+    }
+
+    #[test]
+    fn incentivize_unregistered_pool_fails_with_typed_error() {
+        let (mut deps, env, _, lp_asset) = setup();
+        // The factory doesn't know the pair anymore
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.factory = deps.api.addr_make("other_factory");
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+        let err = execute(
+            deps.as_mut(),
+            env,
+            message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward,
+                    duration_periods: 1,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PoolNotRegistered {
+                lp_token: lp_asset.to_string(),
+                assets: "uatom-uusdc".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn set_incentivization_fee_patches_provided_fields() {
+        let (mut deps, env, owner, _) = setup();
+        let fee_receiver = deps.api.addr_make("fee_receiver");
+        let set_fee = |deps: DepsMut, fee: Option<Coin>, fee_receiver: Option<String>, clear| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&owner, &[]),
+                ExecuteMsg::SetIncentivizationFee {
+                    fee,
+                    fee_receiver,
+                    clear,
+                },
+            )
+        };
+
+        // Both fields are required while the fee is not set
+        set_fee(deps.as_mut(), Some(coin(100, "uusdc")), None, false).unwrap_err();
+        set_fee(
+            deps.as_mut(),
+            Some(coin(100, "uusdc")),
+            Some(fee_receiver.to_string()),
+            false,
+        )
+        .unwrap();
+
+        set_fee(deps.as_mut(), Some(coin(250, "uusdc")), None, false).unwrap();
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(
+            config.incentivization_fee_info,
+            Some(IncentivizationFeeInfo {
+                fee_receiver,
+                fee: coin(250, "uusdc"),
+            })
+        );
+
+        set_fee(deps.as_mut(), Some(coin(1, "uusdc")), None, true).unwrap_err();
+        set_fee(deps.as_mut(), None, None, true).unwrap();
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(config.incentivization_fee_info, None);
+    }
+
+    #[test]
+    fn pool_reward_tokens_limit_override() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let incentivize = |deps: DepsMut, reward: &str| {
+            let reward = AssetInfo::native(reward).with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps,
+                env.clone(),
+                message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+        };
+        let set_limit = |deps: DepsMut, max_reward_tokens| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&owner, &[]),
+                ExecuteMsg::SetPoolMaxRewardTokens {
+                    lp_token: lp_asset.to_string(),
+                    max_reward_tokens,
+                },
+            )
+        };
+
+        let err = set_limit(deps.as_mut(), Some(MAX_POOL_REWARD_TOKENS + 1)).unwrap_err();
+        assert_eq!(err, ContractError::InvalidRewardTokensLimit {});
+        set_limit(deps.as_mut(), Some(1)).unwrap();
+
+        incentivize(deps.as_mut(), "ureward1").unwrap();
+        // Topping up existing reward is still allowed
+        incentivize(deps.as_mut(), "ureward1").unwrap();
+        let err = incentivize(deps.as_mut(), "ureward2").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TooManyRewardTokens {
+                lp_token: lp_asset.to_string(),
+                max_reward_tokens: 1,
+            }
+        );
+
+        set_limit(deps.as_mut(), None).unwrap();
+        let limit: u8 = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::MaxRewardTokens {
+                    lp_token: lp_asset.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(limit, MAX_REWARD_TOKENS);
+        incentivize(deps.as_mut(), "ureward2").unwrap();
+
+        // Unknown LP token doesn't get a pool
+        let unknown_lp = AssetInfo::native("factory/unknown/lp");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetPoolMaxRewardTokens {
+                lp_token: unknown_lp.to_string(),
+                max_reward_tokens: Some(1),
+            },
+        )
+        .unwrap_err();
+        assert!(!POOLS.has(deps.as_ref().storage, &unknown_lp));
+    }
+
+    #[test]
+    fn trader_deposits_cw20_lp_for_recipient() {
+        let (mut deps, env, _, _) = setup();
+        let pair = deps.api.addr_make("cw20_pair");
+        let lp_token = deps.api.addr_make("cw20_lp");
+        let factory = CONFIG.load(deps.as_ref().storage).unwrap().factory;
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uosmo")],
+            contract_addr: pair.clone(),
+            liquidity_token: lp_token.clone(),
+            pair_type: PairType::Xyk {},
+        };
+        let minter = cw20::MinterResponse {
+            minter: pair.to_string(),
+            cap: None,
+        };
+        let (lp_token_addr, pair_addr) = (lp_token.clone(), pair.clone());
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == lp_token_addr.as_str() => {
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&minter).unwrap()))
+            }
+            WasmQuery::Smart { contract_addr, .. }
+                if contract_addr == pair_addr.as_str() || contract_addr == factory.as_str() =>
+            {
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&pair_info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+
+        let trader = deps.api.addr_make("trader");
+        let alice = deps.api.addr_make("alice");
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.trader = Some(trader.clone());
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let cw20_deposit = |deps: DepsMut, sender: &Addr, recipient: &Addr| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&lp_token, &[]),
+                ExecuteMsg::Receive(cw20::Cw20ReceiveMsg {
+                    sender: sender.to_string(),
+                    amount: Uint128::new(100),
+                    msg: to_json_binary(&Cw20Msg::Deposit {
+                        recipient: Some(recipient.to_string()),
+                    })
+                    .unwrap(),
+                }),
+            )
+        };
+
+        let bob = deps.api.addr_make("bob");
+        let err = cw20_deposit(deps.as_mut(), &bob, &alice).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        // Depositing for oneself doesn't require trader role
+        cw20_deposit(deps.as_mut(), &bob, &bob).unwrap();
+
+        cw20_deposit(deps.as_mut(), &trader, &alice).unwrap();
+        let lp_asset = AssetInfo::cw20(lp_token);
+        let alice_pos =
+            UserInfo::load_position(deps.as_ref().storage, &alice.to_string(), &lp_asset).unwrap();
+        assert_eq!(alice_pos.amount, Uint128::new(100));
+        assert!(
+            UserInfo::may_load_position(deps.as_ref().storage, &trader.to_string(), &lp_asset)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn alloc_points_drift_returns_typed_error() {
+        let (mut deps, env, owner, _) = setup();
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.total_alloc_points = Uint128::zero();
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let err = block_token(deps.as_mut(), env, &owner, AssetInfo::native("uatom")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::AllocPointsMismatch {
+                total_alloc_points: Uint128::zero(),
+                alloc_points: Uint128::one(),
+            }
+        );
+    }
+
+    #[test]
+    fn max_user_pools_limits_new_positions() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateConfig {
+                generator_controller: None,
+                incentivization_fee_info: None,
+                max_periods: None,
+                claim_history_enabled: None,
+                vepadex: None,
+                max_user_pools: Some(1),
+                block_ibc_rewards: None,
+                min_schedule_duration: None,
+                max_orphaned_entries: None,
+                keeper_bounty: None,
+            },
+        )
+        .unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let alice_key = alice.to_string();
+        let deposit = |deps: DepsMut| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&alice, &coins(100, lp_asset.to_string())),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+        };
+
+        // Alice is staked in another pool
+        USER_POSITIONS_COUNT
+            .save(deps.as_mut().storage, &alice_key, &1)
+            .unwrap();
+        let err = deposit(deps.as_mut()).unwrap_err();
+        assert_eq!(err, ContractError::TooManyPositions { max_user_pools: 1 });
+
+        // The other position is closed
+        USER_POSITIONS_COUNT.remove(deps.as_mut().storage, &alice_key);
+        deposit(deps.as_mut()).unwrap();
+        // Topping up the existing position doesn't take a new slot
+        deposit(deps.as_mut()).unwrap();
+        assert_eq!(
+            USER_POSITIONS_COUNT
+                .load(deps.as_ref().storage, &alice_key)
+                .unwrap(),
+            1
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::Withdraw {
+                lp_token: lp_asset.to_string(),
+                amount: Uint128::new(200),
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        assert!(!USER_POSITIONS_COUNT.has(deps.as_ref().storage, &alice_key));
+    }
+
+    #[test]
+    fn claim_with_bridge_mints_padex_to_contract() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1000, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(100);
+
+        let target = BridgeTarget {
+            remote_chain_destination_address: "0x1234".to_string(),
+            chain_reference_id: "eth-main".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: None,
+                bridge: Some(target.clone()),
+            },
+        )
+        .unwrap();
+
+        let [sub_msg] = res.messages.as_slice() else {
+            panic!("Expected single PADEX mint message");
+        };
+        assert_eq!(sub_msg.id, PADEX_BRIDGE_REPLY_ID);
+        assert_eq!(sub_msg.reply_on, ReplyOn::Success);
+        assert_eq!(
+            sub_msg.msg,
+            CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
+                create_denom: None,
+                mint_tokens: Some(MintMsg {
+                    denom: "factory/incentives/padex".to_string(),
+                    amount: Uint128::new(1000),
+                    mint_to_address: env.contract.address.to_string(),
+                }),
+            })
+        );
+        let bridge_info: PadexBridgeInfo = from_json(&sub_msg.payload).unwrap();
+        assert_eq!(bridge_info.target, target);
+        assert_eq!(bridge_info.amount, Uint128::new(1000));
+
+        // Trader can't redirect PADEX of other users to a remote chain
+        let trader = deps.api.addr_make("trader");
+        CONFIG
+            .update::<_, StdError>(deps.as_mut().storage, |mut config| {
+                config.trader = Some(trader.clone());
+                Ok(config)
+            })
+            .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env,
+            message_info(&trader, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: Some(alice.to_string()),
+                bridge: Some(target),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::BridgeOnBehalfNotAllowed {});
+    }
+
+    #[test]
+    fn remap_reward_token_keeps_user_accruals() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1000, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        let old_reward = AssetInfo::cw20(deps.api.addr_make("old_reward"));
+        let new_reward = AssetInfo::cw20(deps.api.addr_make("new_reward"));
+        let incentivizer = deps.api.addr_make("incentivizer");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&incentivizer, &[]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward: old_reward.with_balance(Uint128::new(10_000_000_000)),
+                    duration_periods: 2,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(1000);
+        // Sync alice's position so she has a non-zero index of the old reward
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(1000);
+
+        let pending = |deps: Deps| -> Vec<Asset> {
+            from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::PendingRewards {
+                        lp_token: lp_asset.to_string(),
+                        user: alice.to_string(),
+                        with_metadata: false,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        let before = pending(deps.as_ref());
+        assert!(before
+            .iter()
+            .any(|asset| asset.info == old_reward && !asset.amount.is_zero()));
+        let liability = REWARD_LIABILITIES
+            .load(deps.as_ref().storage, &asset_info_key(&old_reward))
+            .unwrap();
+
+        let remap = |deps: DepsMut, sender: &Addr, old: &AssetInfo| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(sender, &[]),
+                ExecuteMsg::RemapRewardToken {
+                    old: old.to_string(),
+                    new: new_reward.to_string(),
+                },
+            )
+        };
+        let err = remap(deps.as_mut(), &alice, &old_reward).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let err = remap(deps.as_mut(), &owner, &AssetInfo::native("uatom")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RewardRemapNotCw20 {
+                reward: "uatom".to_string()
+            }
+        );
+        remap(deps.as_mut(), &owner, &old_reward).unwrap();
+
+        let after = pending(deps.as_ref());
+        assert_eq!(before.len(), after.len());
+        for (before, after) in before.iter().zip(&after) {
+            assert_eq!(before.amount, after.amount);
+            if before.info == old_reward {
+                assert_eq!(after.info, new_reward);
+            }
+        }
+        assert!(!REWARD_LIABILITIES.has(deps.as_ref().storage, &asset_info_key(&old_reward)));
+        assert_eq!(
+            REWARD_LIABILITIES
+                .load(deps.as_ref().storage, &asset_info_key(&new_reward))
+                .unwrap(),
+            liability
+        );
+    }
+
+    #[test]
+    fn prorated_first_epoch_is_reported() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward,
+                    duration_periods: 2,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+
+        // Setup places the block 1000 seconds after the epoch start
+        let now = env.block.time.seconds();
+        let next_epoch_start = now - 1000 + EPOCH_LENGTH;
+        let rps = Decimal256::from_ratio(
+            10_000_000_000u128,
+            next_epoch_start + 2 * EPOCH_LENGTH - now,
+        );
+        let expected: Uint128 = (rps * Decimal256::from_ratio(next_epoch_start - now, 1u8))
+            .to_uint_floor()
+            .try_into()
+            .unwrap();
+        let attr = res
+            .attributes
+            .iter()
+            .find(|attr| attr.key == "prorated_first_epoch_amount")
+            .unwrap();
+        assert_eq!(attr.value, expected.to_string());
+
+        let schedules: Vec<ScheduleResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::ExternalRewardSchedules {
+                    reward: "ureward".to_string(),
+                    lp_token: lp_asset.to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(schedules[0].start_ts, now);
+        assert_eq!(schedules[0].partial_epoch_amount, Some(expected));
+    }
+
+    #[test]
+    fn remove_rewards_from_pool_in_batch() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let incentivizer = deps.api.addr_make("incentivizer");
+        for reward in ["ureward1", "ureward2"] {
+            let reward = AssetInfo::native(reward).with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+            .unwrap();
+        }
+
+        let receiver = deps.api.addr_make("receiver");
+        let remove = |deps: DepsMut, rewards: Vec<String>| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&owner, &[]),
+                ExecuteMsg::RemoveRewardsFromPool {
+                    lp_token: lp_asset.to_string(),
+                    rewards,
+                    bypass_upcoming_schedules: false,
+                    receiver: receiver.to_string(),
+                },
+            )
+        };
+        let err = remove(
+            deps.as_mut(),
+            vec!["ureward1".to_string(); MAX_POOL_REWARD_TOKENS as usize + 1],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TooManyRewardsToRemove {
+                max: MAX_POOL_REWARD_TOKENS
+            }
+        );
+
+        let res = remove(
+            deps.as_mut(),
+            vec!["ureward1".to_string(), "ureward2".to_string()],
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        for (msg, denom) in res.messages.iter().zip(["ureward1", "ureward2"]) {
+            let CosmosMsg::Bank(BankMsg::Send { to_address, amount }) = &msg.msg else {
+                panic!("Expected bank transfer");
+            };
+            assert_eq!(to_address, receiver.as_str());
+            assert_eq!(amount[0].denom, denom);
+            assert!(res
+                .attributes
+                .contains(&attr("unclaimed", amount[0].amount.to_string())));
+        }
+
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        assert!(pool_info.rewards.iter().all(|r| !r.reward.is_external()));
+    }
+
+    #[test]
+    fn setup_pools_rejects_blocked_pair_asset_and_blocked_lp_token() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            contract_addr: deps.api.addr_make("pair"),
+            liquidity_token: Addr::unchecked(lp_asset.to_string()),
+            pair_type: PairType::Xyk {},
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let response = match from_json(msg) {
+                    Ok(FactoryQueryMsg::BlacklistedPairTypes {}) => {
+                        to_json_binary(&Vec::<PairType>::new())
+                    }
+                    _ => to_json_binary(&pair_info),
+                };
+                SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        let setup_pools = |deps: DepsMut| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&owner, &[]),
+                ExecuteMsg::SetupPools {
+                    pools: vec![(lp_asset.to_string(), Uint128::one())],
+                },
+            )
+        };
+        setup_pools(deps.as_mut()).unwrap();
+
+        // Blocking the pair asset deactivates the pool and prevents its setup
+        block_token(
+            deps.as_mut(),
+            env.clone(),
+            &owner,
+            AssetInfo::native("uatom"),
+        )
+        .unwrap();
+        let err = setup_pools(deps.as_mut()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BlockedToken {
+                token: "uatom".to_string()
+            }
+        );
+
+        // Blocked LP token is reported as such, not as one of the pair assets
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateBlockedTokenslist {
+                add: vec![lp_asset.clone()],
+                remove: vec![AssetInfo::native("uatom")],
+            },
+        )
+        .unwrap();
+        let err = setup_pools(deps.as_mut()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BlockedLpToken {
+                lp_token: lp_asset.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_reward_fractions_are_carried_over() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        for (user, amount) in [(&alice, 1), (&bob, 2)] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(user, &coins(amount, lp_asset.to_string())),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+            .unwrap();
+        }
+
+        // Minimal schedule: 1 unit per second, i.e. alice accrues 1/3 unit per second
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let reward = AssetInfo::native("ureward");
+        let min_amount = IncentivesSchedule::min_reward_amount(&env, 1, 25).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&incentivizer, &coins(min_amount.u128(), "ureward")),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward: reward.with_balance(min_amount),
+                    duration_periods: 1,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+
+        // Without carry-over every claim would pay floor(1/3) = 0 i.e. 100% loss
+        let mut claimed = Uint128::zero();
+        for _ in 0..31 {
+            env.block.time = env.block.time.plus_seconds(1);
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&alice, &[]),
+                ExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_asset.to_string()],
+                    user: None,
+                    bridge: None,
+                },
+            )
+            .unwrap();
+            claimed += claimed_amount(&res, "ureward");
+        }
+        // 31 / 3 = 10.33. Loss is bounded by a single unit over all claims
+        assert_eq!(claimed, Uint128::new(10));
+
+        let pos =
+            UserInfo::load_position(deps.as_ref().storage, &alice.to_string(), &lp_asset).unwrap();
+        let fraction = pos.pending_fraction(&RewardType::Ext {
+            info: reward,
+            next_update_ts: 0,
+        });
+        assert!(fraction > Decimal256::percent(33) && fraction < Decimal256::one());
+    }
+
+    #[test]
+    fn native_deposit_reports_missing_or_extra_coins() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        let mut deposit = |funds: &[Coin]| {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&alice, funds),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+        };
+
+        assert_eq!(deposit(&[]).unwrap_err(), ContractError::NoLpSent {});
+        assert_eq!(
+            deposit(&[coin(0, lp_asset.to_string())]).unwrap_err(),
+            ContractError::NoLpSent {}
+        );
+        assert_eq!(
+            deposit(&[coin(100, lp_asset.to_string()), coin(10, "upaloma")]).unwrap_err(),
+            ContractError::MultipleCoinsSent {
+                denoms: format!("{lp_asset}, upaloma"),
+            }
+        );
+        deposit(&[coin(100, lp_asset.to_string())]).unwrap();
+    }
+
+    #[test]
+    fn reward_schedule_start_and_end_events() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1000, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward,
+                    duration_periods: 1,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+        let [start] = res.events.as_slice() else {
+            panic!("Expected single reward_schedule_start event");
+        };
+        assert_eq!(start.ty, "reward_schedule_start");
+        assert!(start.attributes.contains(&attr("reward", "ureward")));
+        assert!(start.attributes.contains(&attr("new_reward", "true")));
+
+        let mut claim = |env: Env| {
+            execute(
+                deps.as_mut(),
+                env,
+                message_info(&alice, &[]),
+                ExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_asset.to_string()],
+                    user: None,
+                    bridge: None,
+                },
+            )
+            .unwrap()
+        };
+        assert!(claim(env.clone()).events.is_empty());
+
+        // Schedule is over. The end event is emitted once the pool is pruned
+        env.block.time = env.block.time.plus_seconds(2 * EPOCH_LENGTH);
+        let res = claim(env.clone());
+        let [end] = res.events.as_slice() else {
+            panic!("Expected single reward_schedule_end event");
+        };
+        assert_eq!(end.ty, "reward_schedule_end");
+        assert!(end.attributes.contains(&attr("reward", "ureward")));
+        assert!(end
+            .attributes
+            .contains(&attr("removed_at", env.block.time.seconds().to_string())));
+
+        env.block.time = env.block.time.plus_seconds(100);
+        assert!(claim(env).events.is_empty());
+    }
+
+    #[test]
+    fn claim_complexity_matches_claim_messages() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1000, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        let incentivizer = deps.api.addr_make("incentivizer");
+        for reward in ["ureward1", "ureward2"] {
+            let reward = AssetInfo::native(reward).with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+            .unwrap();
+        }
+        env.block.time = env.block.time.plus_seconds(1000);
+
+        let complexity = |deps: Deps, lp_tokens: Vec<String>| {
+            query(
+                deps,
+                env.clone(),
+                QueryMsg::ClaimComplexity {
+                    lp_tokens,
+                    user: alice.to_string(),
+                },
+            )
+        };
+        let err = complexity(
+            deps.as_ref(),
+            vec![lp_asset.to_string(), lp_asset.to_string()],
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::DuplicatedPoolFound {});
+
+        let resp: ClaimComplexityResponse =
+            from_json(complexity(deps.as_ref(), vec![lp_asset.to_string()]).unwrap()).unwrap();
+        assert_eq!(
+            resp,
+            ClaimComplexityResponse {
+                submessages: 3,
+                reward_tokens: 3,
+            }
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len() as u32, resp.submessages);
+
+        // Nothing left to claim in the same block
+        let resp: ClaimComplexityResponse =
+            from_json(complexity(deps.as_ref(), vec![lp_asset.to_string()]).unwrap()).unwrap();
+        assert_eq!(
+            resp,
+            ClaimComplexityResponse {
+                submessages: 0,
+                reward_tokens: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn per_epoch_schedule_pays_full_amount_every_epoch() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward");
+        // Setup places the block 1000 seconds after the epoch start
+        let now = env.block.time.seconds();
+        let next_epoch_start = now - 1000 + EPOCH_LENGTH;
+        let per_epoch = PerEpochSchedule {
+            reward_info: reward.clone(),
+            // 10_000 per second
+            amount_per_epoch: Uint128::new(6_048_000_000),
+            periods: 2,
+        };
+
+        // 603_800 seconds of the current epoch plus 2 full epochs at 10_000 per second
+        let converted = InputSchedule::per_epoch(&env, &per_epoch, 25).unwrap();
+        assert_eq!(
+            converted,
+            InputSchedule {
+                reward: reward.with_balance(Uint128::new(18_134_000_000)),
+                duration_periods: 2,
+                refund_address: None,
+            }
+        );
+        let schedule = IncentivesSchedule::from_input(&env, &converted, 25).unwrap();
+        assert_eq!(schedule.rps, Decimal256::from_ratio(10_000u128, 1u8));
+        assert_eq!(schedule.next_epoch_start_ts, next_epoch_start);
+        assert_eq!(schedule.end_ts, next_epoch_start + 2 * EPOCH_LENGTH);
+        assert_eq!(
+            schedule.prorated_amount(&env).unwrap(),
+            Uint128::new(6_038_000_000)
+        );
+
+        // Total is rounded up so full epochs never get less than amount_per_epoch:
+        // ceil(1_000_000_007 * 1_208_600 / 604_800) = 1_998_346_575
+        let converted = InputSchedule::per_epoch(
+            &env,
+            &PerEpochSchedule {
+                amount_per_epoch: Uint128::new(1_000_000_007),
+                periods: 1,
+                ..per_epoch.clone()
+            },
+            25,
+        )
+        .unwrap();
+        assert_eq!(converted.reward.amount, Uint128::new(1_998_346_575));
+        let schedule = IncentivesSchedule::from_input(&env, &converted, 25).unwrap();
+        assert!(
+            schedule.rps * Decimal256::from_ratio(EPOCH_LENGTH, 1u8)
+                >= Decimal256::from_ratio(1_000_000_007u128, 1u8)
+        );
+
+        // Min rps rule still applies
+        let err = IncentivesSchedule::from_input(
+            &env,
+            &InputSchedule::per_epoch(
+                &env,
+                &PerEpochSchedule {
+                    amount_per_epoch: Uint128::new(1000),
+                    ..per_epoch.clone()
+                },
+                25,
+            )
+            .unwrap(),
+            25,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Reward per second must be at least 1 unit"));
+
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let incentivize = |deps: DepsMut, amount: u128| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&incentivizer, &coins(amount, "ureward")),
+                ExecuteMsg::IncentivizePerEpoch {
+                    lp_token: lp_asset.to_string(),
+                    schedule: per_epoch.clone(),
+                },
+            )
+        };
+        incentivize(deps.as_mut(), 18_133_999_999).unwrap_err();
+
+        // Sending amount_per_epoch * (periods + 1) always covers the schedule, the rest is refunded
+        let res = incentivize(deps.as_mut(), 18_144_000_000).unwrap();
+        assert!(res.attributes.contains(&attr("reward", reward.to_string())));
+        assert_eq!(
+            res.messages
+                .iter()
+                .map(|sub_msg| sub_msg.msg.clone())
+                .collect::<Vec<_>>(),
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: incentivizer.to_string(),
+                amount: coins(10_000_000, "ureward"),
+            })]
+        );
+        assert_eq!(
+            REWARD_LIABILITIES
+                .load(deps.as_ref().storage, &asset_info_key(&reward))
+                .unwrap(),
+            Uint128::new(18_134_000_000)
+        );
+    }
+
+    #[test]
+    fn ownership_claim_respects_delay() {
+        let (mut deps, mut env, owner, _) = setup();
+        let new_owner = deps.api.addr_make("new_owner");
+        let propose = |deps: DepsMut, env: Env, delay: Option<u64>| {
+            execute(
+                deps,
+                env,
+                message_info(&owner, &[]),
+                ExecuteMsg::ProposeNewOwner {
+                    owner: new_owner.to_string(),
+                    expires_in: 1000,
+                    delay,
+                },
+            )
+        };
+        let claim = |deps: DepsMut, env: Env| {
+            execute(
+                deps,
+                env,
+                message_info(&new_owner, &[]),
+                ExecuteMsg::ClaimOwnership {},
+            )
+        };
+
+        let err = propose(deps.as_mut(), env.clone(), Some(1001)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Parameter delay cannot be higher than expires_in"));
+
+        propose(deps.as_mut(), env.clone(), Some(500)).unwrap();
+        let earliest_claim_ts = env.block.time.seconds() + 500;
+        env.block.time = env.block.time.plus_seconds(499);
+        let err = claim(deps.as_mut(), env.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::OwnershipClaimTooEarly { earliest_claim_ts }
+        );
+
+        env.block.time = env.block.time.plus_seconds(1);
+        claim(deps.as_mut(), env.clone()).unwrap();
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(config.owner, new_owner);
+
+        // Without delay ownership can be claimed right away
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&new_owner, &[]),
+            ExecuteMsg::ProposeNewOwner {
+                owner: owner.to_string(),
+                expires_in: 1000,
+                delay: None,
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env,
+            message_info(&owner, &[]),
+            ExecuteMsg::ClaimOwnership {},
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn ibc_rewards_can_be_blocked() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let incentivize = |deps: DepsMut, denom: &str| {
+            let reward = AssetInfo::native(denom).with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps,
+                env.clone(),
+                message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+        };
+
+        // Allowed by default
+        incentivize(deps.as_mut(), "ibc/ABCD").unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateConfig {
+                generator_controller: None,
+                incentivization_fee_info: None,
+                max_periods: None,
+                claim_history_enabled: None,
+                vepadex: None,
+                max_user_pools: None,
+                block_ibc_rewards: Some(true),
+                min_schedule_duration: None,
+                max_orphaned_entries: None,
+                keeper_bounty: None,
+            },
+        )
+        .unwrap();
+
+        let err = incentivize(deps.as_mut(), "IBC/ABCD").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::IbcRewardBlocked {
+                reward: "IBC/ABCD".to_string()
+            }
+        );
+        incentivize(deps.as_mut(), "ureward").unwrap();
+    }
+
+    #[test]
+    fn frozen_rewards_are_held_until_unfrozen() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1000, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        let reward = AssetInfo::native("ureward");
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let schedule = reward.with_balance(Uint128::new(10_000_000_000));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&incentivizer, &[schedule.as_coin().unwrap()]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward: schedule,
+                    duration_periods: 1,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::FreezeReward {
+                reward: reward.clone(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::FreezeReward {
+                reward: reward.clone(),
+            },
+        )
+        .unwrap();
+        let frozen: Vec<AssetInfo> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::FrozenRewards {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(frozen, vec![reward.clone()]);
+
+        let claim = |deps: DepsMut, env: Env| {
+            execute(
+                deps,
+                env,
+                message_info(&alice, &[]),
+                ExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_asset.to_string()],
+                    user: None,
+                    bridge: None,
+                },
+            )
+            .unwrap()
+        };
+        env.block.time = env.block.time.plus_seconds(1000);
+        let res = claim(deps.as_mut(), env.clone());
+        assert_eq!(claimed_amount(&res, "ureward"), Uint128::zero());
+        let held = HELD_REWARDS
+            .load(
+                deps.as_ref().storage,
+                (&alice.to_string(), &asset_info_key(&reward)),
+            )
+            .unwrap();
+        assert!(!held.is_zero());
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UnfreezeReward {
+                reward: reward.clone(),
+            },
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UnfreezeReward {
+                reward: reward.clone(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RewardNotFrozen {
+                reward: reward.to_string()
+            }
+        );
+
+        // Nothing new is accrued in the same block, only held rewards are paid out
+        let res = claim(deps.as_mut(), env);
+        assert_eq!(claimed_amount(&res, "ureward"), held);
+        assert!(!HELD_REWARDS.has(
+            deps.as_ref().storage,
+            (&alice.to_string(), &asset_info_key(&reward))
+        ));
+    }
+
+    #[test]
+    fn frozen_rewards_are_not_moved_on_any_path() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward");
+        // Failed calls aren't reverted in tests thus each removal path gets its own reward
+        let batch_reward = AssetInfo::native("ubatch");
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let incentivize = |deps: DepsMut, reward: &AssetInfo| {
+            let schedule = reward.with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps,
+                env.clone(),
+                message_info(&incentivizer, &[schedule.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward: schedule,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+        };
+        incentivize(deps.as_mut(), &reward).unwrap();
+        incentivize(deps.as_mut(), &batch_reward).unwrap();
+
+        let other = AssetInfo::native("uother");
+        let funder = deps.api.addr_make("funder");
+        let storage = deps.as_mut().storage;
+        add_orphaned_entry(
+            storage,
+            ORPHANED_REWARDS,
+            &asset_info_key(&reward),
+            Uint128::new(100),
+        )
+        .unwrap();
+        add_orphaned_entry(
+            storage,
+            REFUNDABLE_ORPHANED_REWARDS,
+            (&asset_info_key(&reward), &funder),
+            Uint128::new(200),
+        )
+        .unwrap();
+        add_orphaned_entry(
+            storage,
+            ORPHANED_REWARDS,
+            &asset_info_key(&other),
+            Uint128::new(300),
+        )
+        .unwrap();
+
+        for reward in [&reward, &batch_reward] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&owner, &[]),
+                ExecuteMsg::FreezeReward {
+                    reward: reward.clone(),
+                },
+            )
+            .unwrap();
+        }
+        let frozen_err = ContractError::RewardFrozen {
+            reward: reward.to_string(),
+        };
+
+        // Incoming transfers of the frozen token are rejected
+        assert_eq!(incentivize(deps.as_mut(), &reward).unwrap_err(), frozen_err);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&incentivizer, &coins(1000, "ureward")),
+            ExecuteMsg::Donate {
+                lp_token: lp_asset.to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, frozen_err);
+
+        // Removing the reward would send its unclaimed part out
+        let receiver = deps.api.addr_make("receiver");
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::RemoveRewardFromPool {
+                lp_token: lp_asset.to_string(),
+                reward: "ureward".to_string(),
+                bypass_upcoming_schedules: false,
+                receiver: receiver.to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, frozen_err);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::RemoveRewardsFromPool {
+                lp_token: lp_asset.to_string(),
+                rewards: vec!["ubatch".to_string()],
+                bypass_upcoming_schedules: false,
+                receiver: receiver.to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RewardFrozen {
+                reward: batch_reward.to_string()
+            }
+        );
+
+        // Frozen orphaned and refundable entries stay until the token is unfrozen
+        let claim_orphaned = |deps: DepsMut| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&owner, &[]),
+                ExecuteMsg::ClaimOrphanedRewards {
+                    limit: None,
+                    receiver: receiver.to_string(),
+                },
+            )
+        };
+        let res = claim_orphaned(deps.as_mut()).unwrap();
+        assert_eq!(
+            res.messages
+                .iter()
+                .map(|msg| msg.msg.clone())
+                .collect::<Vec<_>>(),
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: receiver.to_string(),
+                amount: coins(300, "uother"),
+            })]
+        );
+        assert_eq!(
+            claim_orphaned(deps.as_mut()).unwrap_err(),
+            ContractError::NoOrphanedRewards {}
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UnfreezeReward {
+                reward: reward.clone(),
+            },
+        )
+        .unwrap();
+        let res = claim_orphaned(deps.as_mut()).unwrap();
+        assert_eq!(
+            res.messages
+                .iter()
+                .map(|msg| msg.msg.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: receiver.to_string(),
+                    amount: coins(100, "ureward"),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: funder.to_string(),
+                    amount: coins(200, "ureward"),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn simulate_setup_pools_does_not_modify_state() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            contract_addr: deps.api.addr_make("pair"),
+            liquidity_token: Addr::unchecked(lp_asset.to_string()),
+            pair_type: PairType::Xyk {},
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let response = match from_json(msg) {
+                    Ok(FactoryQueryMsg::BlacklistedPairTypes {}) => {
+                        to_json_binary(&Vec::<PairType>::new())
+                    }
+                    _ => to_json_binary(&pair_info),
+                };
+                SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        let active_pools = ACTIVE_POOLS.load(deps.as_ref().storage).unwrap();
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+
+        let simulate = |deps: Deps, pools: Vec<(String, Uint128)>| {
+            query(deps, env.clone(), QueryMsg::SimulateSetupPools { pools })
+        };
+        let pools: Vec<(String, Uint128)> = from_json(
+            simulate(deps.as_ref(), vec![(lp_asset.to_string(), Uint128::new(5))]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(pools, vec![(lp_asset.to_string(), Uint128::new(5))]);
+
+        let err =
+            simulate(deps.as_ref(), vec![(lp_asset.to_string(), Uint128::zero())]).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ZeroAllocPoint {
+                lp_token: lp_asset.to_string()
+            }
+        );
+        let err = simulate(
+            deps.as_ref(),
+            vec![
+                (lp_asset.to_string(), Uint128::one()),
+                (lp_asset.to_string(), Uint128::one()),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::DuplicatedPoolFound {});
+
+        assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap(), config);
+        assert_eq!(
+            ACTIVE_POOLS.load(deps.as_ref().storage).unwrap(),
+            active_pools
+        );
+
+        block_token(
+            deps.as_mut(),
+            env.clone(),
+            &owner,
+            AssetInfo::native("uatom"),
+        )
+        .unwrap();
+        let err =
+            simulate(deps.as_ref(), vec![(lp_asset.to_string(), Uint128::one())]).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BlockedToken {
+                token: "uatom".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn overlapping_schedules_sum_rps() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward");
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let alice = deps.api.addr_make("alice");
+        // Setup places the block 1000 seconds after the epoch start
+        let next_epoch_start = env.block.time.seconds() - 1000 + EPOCH_LENGTH;
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        // 1_208_600 seconds at 10_000 per second and 1_813_400 seconds at 5_000 per second
+        for (amount, duration_periods) in [(12_086_000_000u128, 1), (9_067_000_000, 2)] {
+            let schedule = reward.with_balance(amount);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&incentivizer, &[schedule.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward: schedule,
+                        duration_periods,
+                        refund_address: None,
+                    },
+                },
+            )
+            .unwrap();
+        }
+
+        let reward_rps = |deps: Deps| {
+            PoolInfo::load(deps.storage, &lp_asset)
+                .unwrap()
+                .rewards
+                .into_iter()
+                .find(|reward_info| reward_info.reward.asset_info() == &reward)
+                .unwrap()
+                .rps
+        };
+        // Both schedules are distributed simultaneously within the overlap window
+        assert_eq!(
+            reward_rps(deps.as_ref()),
+            Decimal256::from_ratio(15_000u128, 1u8)
+        );
+        // Only the longer schedule remains after the overlap
+        assert_eq!(
+            EXTERNAL_REWARD_SCHEDULES
+                .prefix((&lp_asset, &reward))
+                .range(deps.as_ref().storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap(),
+            vec![(
+                next_epoch_start + 2 * EPOCH_LENGTH,
+                Decimal256::from_ratio(5_000u128, 1u8)
+            )]
+        );
+
+        env.block.time = Timestamp::from_seconds(next_epoch_start + EPOCH_LENGTH + 1);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        let claimed_overlap = claimed_amount(&res, &reward.to_string());
+        assert_eq!(claimed_overlap, Uint128::new(1_208_600 * 15_000 + 5_000));
+        assert_eq!(
+            reward_rps(deps.as_ref()),
+            Decimal256::from_ratio(5_000u128, 1u8)
+        );
+
+        // Total distributed equals the sum of both schedules
+        env.block.time = Timestamp::from_seconds(next_epoch_start + 2 * EPOCH_LENGTH);
+        let pending: Vec<PendingRewardResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::PendingRewards {
+                    lp_token: lp_asset.to_string(),
+                    user: alice.to_string(),
+                    with_metadata: false,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        let claimed_after = claimed_amount(&res, &reward.to_string());
+        assert_eq!(
+            claimed_overlap + claimed_after,
+            Uint128::new(21_153_000_000)
+        );
+        assert!(pending
+            .iter()
+            .any(|pending| pending.info == reward && pending.amount == claimed_after));
+    }
+
+    #[test]
+    fn orphaned_rewards_are_refunded_to_incentivizer() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+        let funder = deps.api.addr_make("funder");
+        let other = deps.api.addr_make("other");
+
+        let incentivize = |deps: DepsMut, env: Env, refund_address: Option<&Addr>| {
+            execute(
+                deps,
+                env,
+                message_info(&funder, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward: reward.clone(),
+                        duration_periods: 1,
+                        refund_address: refund_address.map(Addr::to_string),
+                    },
+                },
+            )
+        };
+
+        incentivize(deps.as_mut(), env.clone(), Some(&funder)).unwrap();
+        assert_eq!(
+            REWARD_REFUND_ADDRESSES
+                .load(deps.as_ref().storage, (&lp_asset, &reward.info))
+                .unwrap(),
+            funder
+        );
+
+        // Only the schedule adding the reward sets the refund address
+        let err = incentivize(deps.as_mut(), env.clone(), Some(&other)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RefundAddressNotAllowed {
+                reward: reward.info.to_string()
+            }
+        );
+        incentivize(deps.as_mut(), env.clone(), Some(&funder)).unwrap();
+
+        // Nobody staked thus the whole reward is orphaned once the schedule is over
+        env.block.time = env.block.time.plus_seconds(2 * EPOCH_LENGTH);
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info
+            .update_rewards(deps.as_ref().storage, &env, &lp_asset)
+            .unwrap();
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+
+        let reward_key = asset_info_key(&reward.info);
+        assert!(!ORPHANED_REWARDS.has(deps.as_ref().storage, &reward_key));
+        assert!(!REWARD_REFUND_ADDRESSES.has(deps.as_ref().storage, (&lp_asset, &reward.info)));
+        let refundable = REFUNDABLE_ORPHANED_REWARDS
+            .load(deps.as_ref().storage, (&reward_key, &funder))
+            .unwrap();
+        assert!(!refundable.is_zero());
+
+        let receiver = deps.api.addr_make("receiver");
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&owner, &[]),
+            ExecuteMsg::ClaimOrphanedRewards {
+                limit: None,
+                receiver: receiver.to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: funder.to_string(),
+                amount: vec![coin(refundable.u128(), "ureward")],
+            })
+        );
+        assert!(res.attributes.contains(&attr(
+            "refunded_orphaned_reward",
+            format!("{refundable}ureward:{funder}")
+        )));
+        assert!(!REFUNDABLE_ORPHANED_REWARDS.has(deps.as_ref().storage, (&reward_key, &funder)));
+    }
+
+    #[test]
+    fn orphans_of_funder_without_refund_address_are_not_refunded() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let funder = deps.api.addr_make("funder");
+        let other = deps.api.addr_make("other");
+        let incentivize = |deps: DepsMut, env: Env, sender: &Addr, amount: u128, refund: bool| {
+            let reward = AssetInfo::native("ureward").with_balance(amount);
+            execute(
+                deps,
+                env,
+                message_info(sender, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: refund.then(|| sender.to_string()),
+                    },
+                },
+            )
+            .unwrap()
+        };
+
+        // Schedule ends 2 epochs minus 1000 seconds from now. Both schedules pay 1000 per second
+        incentivize(deps.as_mut(), env.clone(), &funder, 1_208_600_000, true);
+        env.block.time = env.block.time.plus_seconds(1000);
+        let res = incentivize(deps.as_mut(), env.clone(), &other, 1_207_600_000, false);
+        assert!(res
+            .attributes
+            .contains(&attr("dropped_refund_address", funder.to_string())));
+        assert!(!REWARD_REFUND_ADDRESSES.has(
+            deps.as_ref().storage,
+            (&lp_asset, &AssetInfo::native("ureward"))
+        ));
+
+        // Nobody staked thus both schedules are orphaned once they are over
+        env.block.time = env.block.time.plus_seconds(2 * EPOCH_LENGTH);
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info
+            .update_rewards(deps.as_ref().storage, &env, &lp_asset)
+            .unwrap();
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+
+        // The funder gets back only what was orphaned before the other schedule was added
+        let reward_key = asset_info_key(&AssetInfo::native("ureward"));
+        assert_eq!(
+            REFUNDABLE_ORPHANED_REWARDS
+                .load(deps.as_ref().storage, (&reward_key, &funder))
+                .unwrap(),
+            Uint128::new(1_000_000)
+        );
+        assert!(!REFUNDABLE_ORPHANED_REWARDS.has(deps.as_ref().storage, (&reward_key, &other)));
+        assert_eq!(
+            ORPHANED_REWARDS
+                .load(deps.as_ref().storage, &reward_key)
+                .unwrap(),
+            Uint128::new(1_207_600_000 + 1_207_600_000)
+        );
+    }
+
+    #[test]
+    fn failed_cw20_lp_withdrawal_restores_position() {
+        let (mut deps, env, _, _) = setup();
+        let lp_asset = AssetInfo::cw20(deps.api.addr_make("cw20_lp"));
+        let alice = deps.api.addr_make("alice");
+        let user = alice.to_string();
+
+        let mut pool_info = PoolInfo {
+            last_update_ts: env.block.time.seconds(),
+            ..Default::default()
+        };
+        let mut user_info = UserInfo::new(&env);
+        user_info.update_and_sync_position(Op::Add(Uint128::new(100)), &mut pool_info);
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+        UserInfo::open_position(deps.as_mut().storage, &user, None).unwrap();
+        user_info
+            .save(deps.as_mut().storage, &user, &lp_asset)
+            .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::Withdraw {
+                lp_token: lp_asset.to_string(),
+                amount: Uint128::new(100),
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        let transfer = res.messages.last().unwrap();
+        assert_eq!(transfer.id, WITHDRAW_TRANSFER_REPLY_ID);
+        assert_eq!(transfer.reply_on, ReplyOn::Error);
+        assert!(
+            UserInfo::may_load_position(deps.as_ref().storage, &user, &lp_asset)
+                .unwrap()
+                .is_none()
+        );
+
+        crate::reply::reply(
+            deps.as_mut(),
+            env,
+            Reply {
+                id: WITHDRAW_TRANSFER_REPLY_ID,
+                result: SubMsgResult::Err("transfer reverted".to_string()),
+                payload: transfer.payload.clone(),
+                gas_used: 0,
+            },
+        )
+        .unwrap();
+
+        let user_info = UserInfo::load_position(deps.as_ref().storage, &user, &lp_asset).unwrap();
+        assert_eq!(user_info.amount, Uint128::new(100));
+        assert_eq!(
+            PoolInfo::load(deps.as_ref().storage, &lp_asset)
+                .unwrap()
+                .total_lp,
+            Uint128::new(100)
+        );
+        assert_eq!(
+            USER_POSITIONS_COUNT
+                .load(deps.as_ref().storage, &user)
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn schedule_shorter_than_minimum_is_rejected() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        assert_eq!(
+            CONFIG
+                .load(deps.as_ref().storage)
+                .unwrap()
+                .min_schedule_duration,
+            EPOCH_LENGTH
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateConfig {
+                generator_controller: None,
+                incentivization_fee_info: None,
+                max_periods: None,
+                claim_history_enabled: None,
+                vepadex: None,
+                max_user_pools: None,
+                block_ibc_rewards: None,
+                min_schedule_duration: Some(2 * EPOCH_LENGTH),
+                max_orphaned_entries: None,
+                keeper_bounty: None,
+            },
+        )
+        .unwrap();
+
+        let incentivize = |deps: DepsMut, duration_periods: u64| {
+            let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps,
+                env.clone(),
+                message_info(&owner, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods,
+                        refund_address: None,
+                    },
+                },
+            )
+        };
+
+        // The current epoch is partially covered thus one period lasts less than 2 epochs
+        let end_ts = IncentivesSchedule::next_epoch_start(env.block.time.seconds()) + EPOCH_LENGTH;
+        let err = incentivize(deps.as_mut(), 1).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ScheduleTooShort {
+                duration: end_ts - env.block.time.seconds(),
+                min_duration: 2 * EPOCH_LENGTH,
+            }
+        );
+
+        incentivize(deps.as_mut(), 2).unwrap();
+    }
+
+    #[test]
+    fn simulate_incentivize_splits_fee_and_reward() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let fee = coin(1000, "upaloma");
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.incentivization_fee_info = Some(IncentivizationFeeInfo {
+            fee_receiver: deps.api.addr_make("fee_receiver"),
+            fee: fee.clone(),
+        });
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let partner = deps.api.addr_make("partner");
+        let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+        let schedule = InputSchedule {
+            reward: reward.clone(),
+            duration_periods: 1,
+            refund_address: None,
+        };
+        let simulate = |deps: Deps, sent_funds: Vec<Coin>| {
+            query(
+                deps,
+                env.clone(),
+                QueryMsg::SimulateIncentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: schedule.clone(),
+                    sent_funds,
+                    sender: Some(partner.to_string()),
+                },
+            )
+            .map(|res| from_json::<SimulateIncentivizeResponse>(res).unwrap())
+        };
+
+        let funds = vec![reward.as_coin().unwrap(), fee.clone()];
+        assert_eq!(
+            simulate(deps.as_ref(), funds.clone()).unwrap(),
+            SimulateIncentivizeResponse {
+                fee: Some(fee.clone()),
+                reward: reward.clone(),
+                new_reward: true,
+            }
+        );
+        let err = simulate(deps.as_ref(), vec![reward.as_coin().unwrap()]).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::IncentivizationFeeExpected { .. }
+        ));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&partner, &funds),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: schedule.clone(),
+            },
+        )
+        .unwrap();
+
+        // Reward is already in the pool thus no fee is charged
+        assert_eq!(
+            simulate(deps.as_ref(), vec![reward.as_coin().unwrap()]).unwrap(),
+            SimulateIncentivizeResponse {
+                fee: None,
+                reward: reward.clone(),
+                new_reward: false,
+            }
+        );
+
+        // Incentivize is rejected while the contract is paused
+        config.paused = true;
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        let err = simulate(deps.as_ref(), vec![reward.as_coin().unwrap()]).unwrap_err();
+        assert_eq!(err, ContractError::ContractPaused {});
+    }
+
+    #[test]
+    fn orphaned_entries_are_capped_until_claimed() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.max_orphaned_entries = Some(1);
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let donor = deps.api.addr_make("donor");
+        let donate = |deps: DepsMut, denom: &str| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&donor, &coins(1000, denom)),
+                ExecuteMsg::Donate {
+                    lp_token: lp_asset.to_string(),
+                },
+            )
+        };
+        let entries_count = |deps: Deps| -> u32 {
+            from_json(query(deps, env.clone(), QueryMsg::OrphanedEntriesCount {}).unwrap()).unwrap()
+        };
+
+        // Nobody staked thus donations are orphaned
+        donate(deps.as_mut(), "ufoo").unwrap();
+        assert_eq!(entries_count(deps.as_ref()), 1);
+
+        let limit_err = ContractError::OrphanedEntriesLimitReached {
+            max_orphaned_entries: 1,
+        };
+        assert_eq!(donate(deps.as_mut(), "ubar").unwrap_err(), limit_err);
+        let err = accrue_orphaned_reward(
+            deps.as_mut().storage,
+            &lp_asset,
+            &AssetInfo::native("ubar"),
+            Uint128::new(1000),
+        )
+        .unwrap_err();
+        assert_eq!(err, limit_err);
+
+        // Tokens which already have an entry aren't blocked by the cap
+        donate(deps.as_mut(), "ufoo").unwrap();
+        let incentivize = |deps: DepsMut, denom: &str| {
+            let reward = AssetInfo::native(denom).with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps,
+                env.clone(),
+                message_info(&donor, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+        };
+        incentivize(deps.as_mut(), "ufoo").unwrap();
+        assert_eq!(incentivize(deps.as_mut(), "ubar").unwrap_err(), limit_err);
+        assert_eq!(entries_count(deps.as_ref()), 1);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::ClaimOrphanedRewards {
+                limit: None,
+                receiver: owner.to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(entries_count(deps.as_ref()), 0);
+
+        donate(deps.as_mut(), "ubar").unwrap();
+        assert_eq!(entries_count(deps.as_ref()), 1);
+    }
+
+    #[test]
+    fn withdraw_bridges_registered_native_lp() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let lp_denom = lp_asset.to_string();
+        let alice = deps.api.addr_make("alice");
+        let target = BridgeTarget {
+            remote_chain_destination_address: "0xalice".to_string(),
+            chain_reference_id: "eth-main".to_string(),
+        };
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, &lp_denom)),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        let withdraw = |deps: DepsMut| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&alice, &[]),
+                ExecuteMsg::Withdraw {
+                    lp_token: lp_denom.clone(),
+                    amount: Uint128::new(40),
+                    user: None,
+                    bridge: Some(target.clone()),
+                },
+            )
+        };
+
+        let err = withdraw(deps.as_mut()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::LpTokenNotBridgeable {
+                lp_token: lp_denom.clone(),
+                chain_reference_id: "eth-main".to_string(),
+            }
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetLpTokenBridge {
+                lp_token: lp_denom.clone(),
+                erc20_address: "0xlp".to_string(),
+                chain_reference_id: "eth-main".to_string(),
+            },
+        )
+        .unwrap();
+
+        let res = withdraw(deps.as_mut()).unwrap();
+        assert_eq!(
+            res.messages.last().unwrap().msg,
+            CosmosMsg::Custom(PalomaMsg::SkywayMsg {
+                send_tx: Some(SendTx {
+                    remote_chain_destination_address: "0xalice".to_string(),
+                    amount: format!("40{lp_denom}"),
+                    chain_reference_id: "eth-main".to_string(),
+                }),
+                set_erc20_to_denom: None,
+            })
+        );
+        assert_eq!(
+            UserInfo::load_position(deps.as_ref().storage, &alice.to_string(), &lp_asset)
+                .unwrap()
+                .amount,
+            Uint128::new(60)
+        );
+    }
+
+    #[test]
+    fn skyway_msg_omits_unset_fields() {
+        let msg = PalomaMsg::SkywayMsg {
+            send_tx: None,
+            set_erc20_to_denom: Some(SetErc20ToDenom {
+                erc20_address: "0xpadex".to_string(),
+                token_denom: "factory/padex".to_string(),
+                chain_reference_id: "eth-main".to_string(),
+            }),
+        };
+        assert_eq!(
+            to_json_string(&msg).unwrap(),
+            r#"{"skyway_msg":{"set_erc20_to_denom":{"erc20_address":"0xpadex","token_denom":"factory/padex","chain_reference_id":"eth-main"}}}"#
+        );
+
+        let msg = PalomaMsg::SkywayMsg {
+            send_tx: Some(SendTx {
+                remote_chain_destination_address: "0xalice".to_string(),
+                amount: "40factory/padex".to_string(),
+                chain_reference_id: "eth-main".to_string(),
+            }),
+            set_erc20_to_denom: None,
+        };
+        assert_eq!(
+            to_json_string(&msg).unwrap(),
+            r#"{"skyway_msg":{"send_tx":{"remote_chain_destination_address":"0xalice","amount":"40factory/padex","chain_reference_id":"eth-main"}}}"#
+        );
+    }
+
+    #[test]
+    fn padex_distribution_sums_to_padex_per_second() {
+        let (deps, _, _, _) = setup();
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.padex_per_second = Uint128::new(1_000_000);
+        let alloc_points = [1u128, 2, 3, 5, 7, 11, 13];
+        config.total_alloc_points = Uint128::new(alloc_points.iter().sum());
+        let pools = alloc_points
+            .iter()
+            .enumerate()
+            .map(|(i, points)| (AssetInfo::native(format!("lp{i}")), Uint128::new(*points)))
+            .collect_vec();
+        let expected = Decimal256::from_ratio(config.padex_per_second, 1u8);
+
+        // Truncating each pool's share separately loses atomic units of PADEX every second
+        let naive_sum = pools
+            .iter()
+            .map(|(_, points)| {
+                Decimal256::from_ratio(config.padex_per_second * *points, config.total_alloc_points)
+            })
+            .fold(Decimal256::zero(), |acc, rps| acc + rps);
+        let shortfall = (expected - naive_sum).atomics();
+        assert!(!shortfall.is_zero());
+        assert!(shortfall < Uint256::from(pools.len() as u128));
+
+        let distribution = padex_rps_distribution(&config, &pools);
+        let sum = distribution
+            .iter()
+            .fold(Decimal256::zero(), |acc, rps| acc + *rps);
+        assert_eq!(sum, expected);
+        // Each pool is off its exact share by less than one atomic unit
+        for ((_, points), rps) in pools.iter().zip(&distribution) {
+            let naive = Decimal256::from_ratio(
+                config.padex_per_second * *points,
+                config.total_alloc_points,
+            );
+            assert!(*rps - naive <= Decimal256::new(Uint256::one()));
+        }
+
+        config.total_alloc_points = Uint128::zero();
+        assert_eq!(
+            padex_rps_distribution(&config, &pools),
+            vec![Decimal256::zero(); pools.len()]
+        );
+    }
+
+    #[test]
+    fn blacklisted_pair_types_are_forwarded_from_factory() {
+        let (mut deps, env, _, _) = setup();
+        let factory = CONFIG.load(deps.as_ref().storage).unwrap().factory;
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == factory.as_str() => {
+                match from_json(msg) {
+                    Ok(FactoryQueryMsg::BlacklistedPairTypes {}) => SystemResult::Ok(
+                        ContractResult::Ok(to_json_binary(&vec![PairType::Stable {}]).unwrap()),
+                    ),
+                    _ => SystemResult::Err(SystemError::Unknown {}),
+                }
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+
+        let pair_types: Vec<PairType> =
+            from_json(query(deps.as_ref(), env, QueryMsg::BlacklistedPairTypes {}).unwrap())
+                .unwrap();
+        assert_eq!(pair_types, vec![PairType::Stable {}]);
+    }
+
+    #[test]
+    fn update_rewards_on_default_pool_is_noop() {
+        let (mut deps, env, _, _) = setup();
+        let lp_asset = AssetInfo::native("factory/new_pair/lp");
+
+        let mut pool_info = PoolInfo::may_load(deps.as_ref().storage, &lp_asset)
+            .unwrap()
+            .unwrap_or_default();
+        pool_info
+            .update_rewards(deps.as_ref().storage, &env, &lp_asset)
+            .unwrap();
+        assert_eq!(pool_info.last_update_ts, env.block.time.seconds());
+        assert!(pool_info.rewards.is_empty());
+        assert!(pool_info.rewards_to_remove.is_empty());
+        assert!(!pool_info.is_active_pool());
+
+        // Saving the first-touched pool doesn't record orphaned rewards
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        assert!(pool_info.rewards.is_empty());
+        assert!(ORPHANED_REWARDS.is_empty(deps.as_ref().storage));
+    }
+
+    #[test]
+    fn claim_receiver_must_be_allowed() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let trader = deps.api.addr_make("trader");
+        let alice = deps.api.addr_make("alice");
+        let custody = deps.api.addr_make("custody");
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.trader = Some(trader.clone());
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::UpdateAllowedReceivers {
+                add: vec![custody.to_string()],
+                remove: vec![],
+            },
+        )
+        .unwrap();
+        let allowed: Vec<Addr> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AllowedReceivers {
+                    user: alice.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(allowed, vec![custody.clone()]);
+
+        env.block.time = env.block.time.plus_seconds(100);
+        let trader_claim = |deps: DepsMut, env: Env| {
+            execute(
+                deps,
+                env,
+                message_info(&trader, &[]),
+                ExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_asset.to_string()],
+                    user: Some(alice.to_string()),
+                    bridge: None,
+                },
+            )
+        };
+        // Without custody account rewards would go to the trader
+        let err = trader_claim(deps.as_mut(), env.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ReceiverNotAllowed {
+                receiver: trader.to_string()
+            }
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::SetCustodyReceiver {
+                receiver: Some(custody.to_string()),
+            },
+        )
+        .unwrap();
+        trader_claim(deps.as_mut(), env.clone()).unwrap();
+
+        // The user can always claim for themselves
+        execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn claim_history_is_recorded_when_enabled() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let padex = CONFIG.load(deps.as_ref().storage).unwrap().padex_token;
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        let claim = |deps: DepsMut, env: Env| {
+            execute(
+                deps,
+                env,
+                message_info(&alice, &[]),
+                ExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_asset.to_string()],
+                    user: None,
+                    bridge: None,
+                },
+            )
+            .unwrap();
+        };
+        let history = |deps: Deps, start_after: Option<u64>| -> Vec<(u64, ClaimRecord)> {
+            from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::ClaimHistory {
+                        user: alice.to_string(),
+                        start_after,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        // Disabled by default
+        env.block.time = env.block.time.plus_seconds(100);
+        claim(deps.as_mut(), env.clone());
+        assert_eq!(history(deps.as_ref(), None), vec![]);
+
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.claim_history_enabled = true;
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        let mut expected = vec![];
+        for (id, seconds) in [(0, 100), (1, 50)] {
+            env.block.time = env.block.time.plus_seconds(seconds);
+            claim(deps.as_mut(), env.clone());
+            expected.push((
+                id,
+                ClaimRecord {
+                    timestamp: env.block.time.seconds(),
+                    lp_token: lp_asset.to_string(),
+                    rewards: vec![padex.with_balance(seconds as u128 * 10)],
+                },
+            ));
+        }
+        assert_eq!(history(deps.as_ref(), None), expected);
+        assert_eq!(history(deps.as_ref(), Some(0)), expected[1..]);
+
+        // Nothing is recorded if there was nothing to claim
+        claim(deps.as_mut(), env);
+        assert_eq!(history(deps.as_ref(), None).len(), 2);
+
+        // Only the latest records are kept
+        let user = alice.to_string();
+        for _ in 0..MAX_CLAIM_HISTORY_LEN {
+            push_claim_record(deps.as_mut().storage, &user, &expected[0].1).unwrap();
+        }
+        let ids: Vec<u64> = CLAIM_HISTORY
+            .prefix(&user)
+            .keys(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(ids, (2..MAX_CLAIM_HISTORY_LEN + 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stale_lp_token_blocks_deposits_but_not_withdrawals() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let lp_denom = lp_asset.to_string();
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        for user in [&alice, &bob] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(user, &coins(100, &lp_denom)),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+            .unwrap();
+        }
+
+        let is_stale = |deps: Deps, lp_token: &str| -> bool {
+            from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::IsLpTokenStale {
+                        lp_token: lp_token.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        assert!(!is_stale(deps.as_ref(), &lp_denom));
+
+        // The factory re-registers the pair with a new LP token
+        let pair = deps.api.addr_make("pair");
+        let factory = deps.api.addr_make("factory");
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            contract_addr: pair.clone(),
+            liquidity_token: Addr::unchecked(lp_denom.clone()),
+            pair_type: PairType::Xyk {},
+        };
+        let registered = PairInfo {
+            liquidity_token: Addr::unchecked(format!("factory/{pair}/lp2")),
+            ..pair_info.clone()
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == pair.as_str() => {
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&pair_info).unwrap()))
+            }
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == factory.as_str() => {
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&registered).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        assert!(is_stale(deps.as_ref(), &lp_denom));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, &lp_denom)),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::StaleLpToken {
+                lp_token: lp_denom.clone()
+            }
+        );
+
+        // Existing stakers can still leave the pool
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::Withdraw {
+                lp_token: lp_denom.clone(),
+                amount: Uint128::new(100),
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(claimed_amount(&res, &lp_denom), Uint128::new(100));
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&bob, &[]),
+            ExecuteMsg::EmergencyWithdraw {
+                lp_token: lp_denom.clone(),
+            },
+        )
+        .unwrap();
+        assert_eq!(claimed_amount(&res, &lp_denom), Uint128::new(100));
+        assert_eq!(
+            PoolInfo::load(deps.as_ref().storage, &lp_asset)
+                .unwrap()
+                .total_lp,
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn claim_on_behalf_requires_trader_to_be_set() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        let trader = deps.api.addr_make("trader");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        // Setup leaves the trader unset
+        let err = execute(
+            deps.as_mut(),
+            env,
+            message_info(&trader, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: Some(alice.to_string()),
+                bridge: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn withdraw_on_behalf_requires_trader_sender() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        let trader = deps.api.addr_make("trader");
+        let bob = deps.api.addr_make("bob");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        let withdraw_for_alice = ExecuteMsg::Withdraw {
+            lp_token: lp_asset.to_string(),
+            amount: Uint128::new(100),
+            user: Some(alice.to_string()),
+            bridge: None,
+        };
+
+        // Setup leaves the trader unset
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&trader, &[]),
+            withdraw_for_alice.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.trader = Some(trader.clone());
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&bob, &[]),
+            withdraw_for_alice.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        execute(
+            deps.as_mut(),
+            env,
+            message_info(&trader, &[]),
+            withdraw_for_alice,
+        )
+        .unwrap();
+        assert_eq!(
+            PoolInfo::load(deps.as_ref().storage, &lp_asset)
+                .unwrap()
+                .total_lp,
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn trader_claim_settles_to_custody_receiver() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let trader = deps.api.addr_make("trader");
+        let alice = deps.api.addr_make("alice");
+        let custody = deps.api.addr_make("custody");
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.trader = Some(trader.clone());
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+
+        let set_custody = |deps: DepsMut, sender: &Addr, receiver: Option<&Addr>| {
+            execute(
+                deps,
+                mock_env(),
+                message_info(sender, &[]),
+                ExecuteMsg::SetCustodyReceiver {
+                    receiver: receiver.map(Addr::to_string),
+                },
+            )
+            .unwrap()
+        };
+        let custody_receiver = |deps: Deps| -> Option<Addr> {
+            from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::CustodyReceiver {
+                        user: alice.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        // Returns (receiver, amount) of minted PADEX
+        let trader_claim = |deps: DepsMut, env: Env| {
+            let res = execute(
+                deps,
+                env,
+                message_info(&trader, &[]),
+                ExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_asset.to_string()],
+                    user: Some(alice.to_string()),
+                    bridge: None,
+                },
+            )
+            .unwrap();
+            res.messages
+                .into_iter()
+                .find_map(|sub_msg| match sub_msg.msg {
+                    CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
+                        mint_tokens:
+                            Some(MintMsg {
+                                amount,
+                                mint_to_address,
+                                ..
+                            }),
+                        ..
+                    }) => Some((mint_to_address, amount)),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(
+            trader_claim(deps.as_mut(), env.clone()),
+            (trader.to_string(), Uint128::new(1000))
+        );
+
+        // The trader can't opt in on behalf of the user
+        set_custody(deps.as_mut(), &trader, Some(&trader));
+        assert_eq!(custody_receiver(deps.as_ref()), None);
+
+        let res = set_custody(deps.as_mut(), &alice, Some(&custody));
+        assert!(res
+            .attributes
+            .contains(&attr("receiver", custody.to_string())));
+        assert_eq!(custody_receiver(deps.as_ref()), Some(custody.clone()));
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(
+            trader_claim(deps.as_mut(), env.clone()),
+            (custody.to_string(), Uint128::new(1000))
+        );
+
+        set_custody(deps.as_mut(), &alice, None);
+        assert_eq!(custody_receiver(deps.as_ref()), None);
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(
+            trader_claim(deps.as_mut(), env),
+            (trader.to_string(), Uint128::new(1000))
+        );
+    }
+
+    #[test]
+    fn first_staker_does_not_capture_pre_stake_rewards() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+        let funder = deps.api.addr_make("funder");
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&funder, &[reward.as_coin().unwrap()]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward: reward.clone(),
+                    duration_periods: 1,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+
+        // Nobody is staked for the first 1000 seconds
+        env.block.time = env.block.time.plus_seconds(1000);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(1000);
+
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info
+            .update_rewards(deps.as_ref().storage, &env, &lp_asset)
+            .unwrap();
+        let reward_info = pool_info
+            .rewards
+            .iter()
+            .find(|r| r.reward.is_external())
+            .unwrap()
+            .clone();
+        let accrued_in_1000_secs = reward_info.rps * Decimal256::from_ratio(1000u16, 1u8);
+        assert_eq!(reward_info.orphaned, accrued_in_1000_secs);
+
+        let mut alice_pos =
+            UserInfo::load_position(deps.as_ref().storage, &alice.to_string(), &lp_asset).unwrap();
+        let rewards = pool_info.calculate_rewards(&mut alice_pos).unwrap();
+        let (_, ext_reward) = rewards.iter().find(|(is_ext, _)| *is_ext).unwrap();
+        assert_eq!(
+            ext_reward.amount,
+            Uint128::try_from(accrued_in_1000_secs.to_uint_floor()).unwrap()
+        );
+    }
+
+    #[test]
+    fn first_deposit_below_minimum_is_rejected() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::SetPoolMinFirstDeposit {
+                lp_token: lp_asset.to_string(),
+                min_amount: Some(MINIMUM_LIQUIDITY_AMOUNT),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        // Unknown LP token doesn't get a pool
+        let unknown_lp = AssetInfo::native("factory/unknown/lp");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetPoolMinFirstDeposit {
+                lp_token: unknown_lp.to_string(),
+                min_amount: Some(MINIMUM_LIQUIDITY_AMOUNT),
+            },
+        )
+        .unwrap_err();
+        assert!(!POOLS.has(deps.as_ref().storage, &unknown_lp));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetPoolMinFirstDeposit {
+                lp_token: lp_asset.to_string(),
+                min_amount: Some(MINIMUM_LIQUIDITY_AMOUNT),
+            },
+        )
+        .unwrap();
+
+        let deposit = |deps: DepsMut, user: &Addr, amount: u128| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(user, &coins(amount, lp_asset.to_string())),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+        };
+        let err = deposit(deps.as_mut(), &alice, 999).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::FirstDepositTooSmall {
+                lp_token: lp_asset.to_string(),
+                min_amount: MINIMUM_LIQUIDITY_AMOUNT,
+            }
+        );
+        deposit(deps.as_mut(), &alice, 1000).unwrap();
+        // Subsequent deposits of any size are fine
+        deposit(deps.as_mut(), &bob, 1).unwrap();
+
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        assert_eq!(pool_info.total_lp, Uint128::new(1001));
+    }
+
+    #[test]
+    fn deactivate_pool_recovers_from_alloc_points_drift() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        // Stored total is less than the pool's alloc points
+        config.total_alloc_points = Uint128::zero();
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&config.factory, &[]),
+            ExecuteMsg::DeactivatePool {
+                lp_token: lp_asset.to_string(),
+            },
+        )
+        .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "reconciled_total_alloc_points" && attr.value == "0"));
+        assert!(ACTIVE_POOLS.load(deps.as_ref().storage).unwrap().is_empty());
+        assert!(!PoolInfo::load(deps.as_ref().storage, &lp_asset)
+            .unwrap()
+            .is_active_pool());
+
+        // Owner can reconcile drifted total without deactivating pools
+        let other_lp = AssetInfo::native("factory/other_pair/lp");
+        PoolInfo {
+            last_update_ts: env.block.time.seconds(),
+            ..Default::default()
+        }
+        .save(deps.as_mut().storage, &other_lp)
+        .unwrap();
+        ACTIVE_POOLS
+            .save(
+                deps.as_mut().storage,
+                &vec![(other_lp.clone(), Uint128::new(3))],
+            )
+            .unwrap();
+        let reconcile = |deps: DepsMut, sender: &Addr| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(sender, &[]),
+                ExecuteMsg::ReconcileAllocPoints {},
+            )
+        };
+        let err = reconcile(deps.as_mut(), &config.factory).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        reconcile(deps.as_mut(), &owner).unwrap();
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(config.total_alloc_points, Uint128::new(3));
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &other_lp).unwrap();
+        assert_eq!(
+            pool_info.rewards[0].rps,
+            Decimal256::from_ratio(config.padex_per_second, 1u8)
+        );
+    }
+
+    #[test]
+    fn deactivate_blocked_pools_pays_keeper_bounty() {
+        let (mut deps, env, owner, lp_asset) = setup();
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            contract_addr: deps.api.addr_make("pair"),
+            liquidity_token: Addr::unchecked(lp_asset.to_string()),
+            pair_type: PairType::Xyk {},
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let response = match from_json(msg) {
+                    Ok(FactoryQueryMsg::BlacklistedPairTypes {}) => {
+                        to_json_binary(&vec![PairType::Xyk {}])
+                    }
+                    _ => to_json_binary(&pair_info),
+                };
+                SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        let keeper = deps.api.addr_make("keeper");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &coins(7, "ubounty")),
+            ExecuteMsg::FundKeeperBounty {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::KeeperBountyNotSet {});
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateConfig {
+                generator_controller: None,
+                incentivization_fee_info: None,
+                max_periods: None,
+                claim_history_enabled: None,
+                vepadex: None,
+                max_user_pools: None,
+                block_ibc_rewards: None,
+                min_schedule_duration: None,
+                max_orphaned_entries: None,
+                keeper_bounty: Some(coin(5, "ubounty")),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &coins(7, "uother")),
+            ExecuteMsg::FundKeeperBounty {},
+        )
+        .unwrap_err();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &coins(7, "ubounty")),
+            ExecuteMsg::FundKeeperBounty {},
+        )
+        .unwrap();
+
+        let bounty_msgs = |res: &Response<PalomaMsg>| {
+            res.messages
+                .iter()
+                .filter_map(|sub_msg| match &sub_msg.msg {
+                    CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                        Some((to_address.clone(), amount.clone()))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &[]),
+            ExecuteMsg::DeactivateBlockedPools {},
+        )
+        .unwrap();
+        assert!(ACTIVE_POOLS.load(deps.as_ref().storage).unwrap().is_empty());
+        assert_eq!(
+            bounty_msgs(&res),
+            vec![(keeper.to_string(), coins(5, "ubounty"))]
+        );
+
+        // Nothing to deactivate, no bounty
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &[]),
+            ExecuteMsg::DeactivateBlockedPools {},
+        )
+        .unwrap();
+        assert!(bounty_msgs(&res).is_empty());
+
+        let fund: Option<Coin> =
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::KeeperBountyFund {}).unwrap())
+                .unwrap();
+        assert_eq!(fund, Some(coin(2, "ubounty")));
+
+        // Switching the bounty denom leaves the old fund withdrawable by the owner only
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateConfig {
+                generator_controller: None,
+                incentivization_fee_info: None,
+                max_periods: None,
+                claim_history_enabled: None,
+                vepadex: None,
+                max_user_pools: None,
+                block_ibc_rewards: None,
+                min_schedule_duration: None,
+                max_orphaned_entries: None,
+                keeper_bounty: Some(coin(5, "unew")),
+            },
+        )
+        .unwrap();
+        let withdraw =
+            |amount: Option<u128>, receiver: Option<&Addr>| ExecuteMsg::WithdrawKeeperBountyFund {
+                denom: "ubounty".to_string(),
+                amount: amount.map(Uint128::new),
+                receiver: receiver.map(Addr::to_string),
+            };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &[]),
+            withdraw(None, None),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            withdraw(Some(3), None),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::KeeperBountyFundTooLow {
+                fund: Uint128::new(2),
+                amount: Uint128::new(3)
+            }
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            withdraw(Some(1), Some(&keeper)),
+        )
+        .unwrap();
+        assert_eq!(
+            bounty_msgs(&res),
+            vec![(keeper.to_string(), coins(1, "ubounty"))]
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            withdraw(None, None),
+        )
+        .unwrap();
+        assert_eq!(
+            bounty_msgs(&res),
+            vec![(owner.to_string(), coins(1, "ubounty"))]
+        );
+        assert!(!KEEPER_BOUNTY_FUNDS.has(deps.as_ref().storage, "ubounty"));
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            message_info(&owner, &[]),
+            withdraw(None, None),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::KeeperBountyFundTooLow {
+                fund: Uint128::zero(),
+                amount: Uint128::zero()
+            }
+        );
+    }
+
+    #[test]
+    fn external_padex_schedule_coexists_with_emissions() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let padex = CONFIG.load(deps.as_ref().storage).unwrap().padex_token;
+        let funder = deps.api.addr_make("funder");
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        let ext_padex = padex.with_balance(Uint128::new(7_000_000));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&funder, &[ext_padex.as_coin().unwrap()]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward: ext_padex.clone(),
+                    duration_periods: 1,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        assert_eq!(pool_info.rewards.len(), 2);
+        assert!(pool_info
+            .rewards
+            .iter()
+            .all(|reward_info| reward_info.reward.asset_info() == &padex));
+
+        env.block.time = env.block.time.plus_seconds(1000);
+        let pending: Vec<PendingRewardResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::PendingRewards {
+                    lp_token: lp_asset.to_string(),
+                    user: alice.to_string(),
+                    with_metadata: false,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let pending_total: Uint128 = pending.iter().map(|reward| reward.amount).sum();
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![lp_asset.to_string()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+
+        // Protocol PADEX is minted while external PADEX is transferred from the contract balance
+        let minted: Uint128 = res
+            .messages
+            .iter()
+            .filter_map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
+                    mint_tokens: Some(MintMsg { amount, .. }),
+                    ..
+                }) => Some(*amount),
+                _ => None,
+            })
+            .sum();
+        let transferred = claimed_amount(&res, &padex.to_string());
+        assert_eq!(minted, Uint128::new(10_000));
+        assert!(!transferred.is_zero());
+        assert_eq!(minted + transferred, pending_total);
+        assert_eq!(
+            REWARD_LIABILITIES
+                .load(deps.as_ref().storage, &asset_info_key(&padex))
+                .unwrap(),
+            ext_padex.amount - transferred
+        );
+    }
+
+    #[test]
+    fn scheduled_padex_rate_applies_at_effective_time() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        let start = env.block.time.seconds();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetTokensPerSecond {
+                amount: Uint128::new(4),
+                effective_ts: Some(start),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PastEffectiveTime {
+                effective_ts: start
+            }
+        );
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetTokensPerSecond {
+                amount: Uint128::new(4),
+                effective_ts: Some(start + 1000),
+            },
+        )
+        .unwrap();
+
+        let padex_rate = |deps: Deps, env: &Env| -> PadexRateResponse {
+            from_json(query(deps, env.clone(), QueryMsg::PadexRate {}).unwrap()).unwrap()
+        };
+        let pending = |deps: Deps, env: &Env| -> Uint128 {
+            let rewards: Vec<PendingRewardResponse> = from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::PendingRewards {
+                        lp_token: lp_asset.to_string(),
+                        user: alice.to_string(),
+                        with_metadata: false,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            rewards.iter().map(|reward| reward.amount).sum()
+        };
+        assert_eq!(
+            padex_rate(deps.as_ref(), &env),
+            PadexRateResponse {
+                padex_per_second: Uint128::new(10),
+                scheduled: Some(ScheduledPadexRate {
+                    padex_per_second: Uint128::new(4),
+                    effective_ts: start + 1000,
+                }),
+            }
+        );
+
+        // Old rate is paid till the effective time without any keeper action
+        env.block.time = Timestamp::from_seconds(start + 3000);
+        assert_eq!(
+            pending(deps.as_ref(), &env),
+            Uint128::new(10 * 1000 + 4 * 2000)
+        );
+        assert_eq!(
+            padex_rate(deps.as_ref(), &env),
+            PadexRateResponse {
+                padex_per_second: Uint128::new(4),
+                scheduled: None,
+            }
+        );
+
+        // Redistribution moves the scheduled rate into config
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::ReconcileAllocPoints {},
+        )
+        .unwrap();
+        assert_eq!(
+            CONFIG.load(deps.as_ref().storage).unwrap().padex_per_second,
+            Uint128::new(4)
+        );
+        assert!(!SCHEDULED_PADEX_RATE.exists(deps.as_ref().storage));
+        env.block.time = Timestamp::from_seconds(start + 4000);
+        assert_eq!(
+            pending(deps.as_ref(), &env),
+            Uint128::new(10 * 1000 + 4 * 3000)
+        );
+    }
+
+    #[test]
+    fn blocking_token_after_effective_time_keeps_scheduled_rate() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let pair = deps.api.addr_make("pair");
+        let other_pair = deps.api.addr_make("other_pair");
+        let other_lp = AssetInfo::native(format!("factory/{other_pair}/lp"));
+        let pair_info = |pair: &Addr, asset_infos: Vec<AssetInfo>| PairInfo {
+            asset_infos,
+            contract_addr: pair.clone(),
+            liquidity_token: Addr::unchecked(format!("factory/{pair}/lp")),
+            pair_type: PairType::Xyk {},
+        };
+        let pair_infos = [
+            pair_info(
+                &pair,
+                vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            ),
+            pair_info(
+                &other_pair,
+                vec![AssetInfo::native("uosmo"), AssetInfo::native("uusdt")],
+            ),
+        ];
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, msg } => {
+                let response = match from_json(msg) {
+                    Ok(FactoryQueryMsg::BlacklistedPairTypes {}) => {
+                        to_json_binary(&Vec::<PairType>::new())
+                    }
+                    Ok(FactoryQueryMsg::Pair { asset_infos }) => to_json_binary(
+                        pair_infos
+                            .iter()
+                            .find(|pair_info| pair_info.asset_infos == asset_infos)
+                            .unwrap(),
+                    ),
+                    _ => to_json_binary(
+                        pair_infos
+                            .iter()
+                            .find(|pair_info| pair_info.contract_addr.as_str() == contract_addr)
+                            .unwrap(),
+                    ),
+                };
+                SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetupPools {
+                pools: vec![
+                    (lp_asset.to_string(), Uint128::one()),
+                    (other_lp.to_string(), Uint128::one()),
+                ],
+            },
+        )
+        .unwrap();
+        let start = env.block.time.seconds();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetTokensPerSecond {
+                amount: Uint128::new(4),
+                effective_ts: Some(start + 1000),
+            },
+        )
+        .unwrap();
+
+        let padex_rps = |deps: Deps, lp_asset: &AssetInfo| {
+            PoolInfo::load(deps.storage, lp_asset)
+                .unwrap()
+                .rewards
+                .iter()
+                .find(|reward_info| !reward_info.reward.is_external())
+                .map(|reward_info| reward_info.rps)
+        };
+        assert_eq!(
+            padex_rps(deps.as_ref(), &other_lp),
+            Some(Decimal256::from_ratio(5u8, 1u8))
+        );
+
+        // Blocking a token after the effective time deactivates the first pool.
+        // The remaining pool must get the whole scheduled rate, not the stale one from config
+        env.block.time = Timestamp::from_seconds(start + 3000);
+        block_token(
+            deps.as_mut(),
+            env.clone(),
+            &owner,
+            AssetInfo::native("uatom"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            CONFIG.load(deps.as_ref().storage).unwrap().padex_per_second,
+            Uint128::new(4)
+        );
+        assert!(!SCHEDULED_PADEX_RATE.exists(deps.as_ref().storage));
+        assert_eq!(
+            padex_rps(deps.as_ref(), &lp_asset),
+            Some(Decimal256::zero())
+        );
+        assert_eq!(
+            padex_rps(deps.as_ref(), &other_lp),
+            Some(Decimal256::from_ratio(4u8, 1u8))
+        );
+        let top_pools: Vec<TopPoolResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::TopPools { limit: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            top_pools[0].padex_per_second,
+            Decimal256::from_ratio(4u8, 1u8)
+        );
+    }
+
+    #[test]
+    fn unknown_lp_token_fails_with_pool_not_found() {
+        let (mut deps, env, _, _) = setup();
+        let alice = deps.api.addr_make("alice");
+        let typo = format!("factory/{}/lp", deps.api.addr_make("typo"));
+        let expected = ContractError::PoolNotFound {
+            lp_token: typo.clone(),
+        };
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimRewards {
+                lp_tokens: vec![typo.clone()],
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, expected);
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::Withdraw {
+                lp_token: typo.clone(),
+                amount: Uint128::one(),
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, expected);
+
+        let err = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::RewardInfo {
+                lp_token: typo.clone(),
+                with_metadata: false,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, expected);
+    }
+
+    #[test]
+    fn config_stored_before_upgrade_loads_with_defaults() {
+        let mut deps = mock_dependencies();
+        // Config layout of the initially deployed contract
+        let stored = r#"{
+            "owner": "owner",
+            "trader": "trader",
+            "factory": "factory",
+            "generator_controller": null,
+            "padex_token": {"native_token": {"denom": "factory/incentives/padex"}},
+            "padex_per_second": "10",
+            "total_alloc_points": "1",
+            "incentivization_fee_info": null
+        }"#;
+        deps.storage.set(b"config", stored.as_bytes());
+
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(config.trader, Some(Addr::unchecked("trader")));
+        assert_eq!(config.max_periods, crate::constants::MAX_PERIODS);
+        assert!(!config.claim_history_enabled);
+        assert_eq!(config.fee_exempt, Vec::<Addr>::new());
+        assert!(!config.paused);
+        assert_eq!(config.vepadex, None);
+        assert_eq!(config.max_user_pools, None);
+        assert!(!config.block_ibc_rewards);
+        assert_eq!(config.min_schedule_duration, EPOCH_LENGTH);
+        assert_eq!(config.max_orphaned_entries, None);
+        assert_eq!(config.keeper_bounty, None);
+    }
+
+    #[test]
+    fn set_weight_uses_checked_math() {
+        let env = mock_env();
+        let mut user_info = UserInfo {
+            amount: Uint128::new(1),
+            weight: Uint128::new(4),
+            ..UserInfo::new(&env)
+        };
+        let mut pool_info = PoolInfo {
+            total_weight: Uint128::new(10),
+            ..Default::default()
+        };
+        user_info
+            .set_weight(Uint128::new(6), &mut pool_info)
+            .unwrap();
+        assert_eq!(user_info.weight, Uint128::new(6));
+        assert_eq!(pool_info.total_weight, Uint128::new(12));
+
+        // Total weight below the user weight means broken accounting rather than a panic
+        let mut pool_info = PoolInfo {
+            total_weight: Uint128::new(5),
+            ..Default::default()
+        };
+        let err = user_info
+            .set_weight(Uint128::new(6), &mut pool_info)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::OverflowError(_)));
+        assert_eq!(user_info.weight, Uint128::new(6));
+        assert_eq!(pool_info.total_weight, Uint128::new(5));
+
+        let mut pool_info = PoolInfo {
+            total_weight: Uint128::MAX,
+            ..Default::default()
+        };
+        let err = user_info
+            .set_weight(Uint128::new(7), &mut pool_info)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::OverflowError(_)));
+
+        // Positions without LP tokens have no weight
+        let mut pool_info = PoolInfo {
+            total_weight: Uint128::new(10),
+            ..Default::default()
+        };
+        user_info.amount = Uint128::zero();
+        user_info
+            .set_weight(Uint128::new(100), &mut pool_info)
+            .unwrap();
+        assert_eq!(user_info.weight, Uint128::zero());
+        assert_eq!(pool_info.total_weight, Uint128::new(4));
+    }
+
+    #[test]
+    fn voting_power_weighted_pool_splits_padex_by_weight() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let vepadex = deps.api.addr_make("vepadex");
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            contract_addr: deps.api.addr_make("pair"),
+            liquidity_token: Addr::unchecked(lp_asset.to_string()),
+            pair_type: PairType::Xyk {},
+        };
+        let weights = HashMap::from([(alice.to_string(), 100u128), (bob.to_string(), 300)]);
+        let vepadex_addr = vepadex.to_string();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == &vepadex_addr => {
+                let VepadexQueryMsg::Locker { address, .. } = from_json(msg).unwrap();
+                let response = LockerResponse {
+                    deposited_amount: Uint128::zero(),
+                    locked_amount: Uint128::zero(),
+                    balance: Uint128::new(weights[&address]),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&response).unwrap()))
+            }
+            WasmQuery::Smart { .. } => {
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&pair_info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+
+        let set_distribution = |deps: DepsMut| {
+            execute(
+                deps,
+                env.clone(),
+                message_info(&owner, &[]),
+                ExecuteMsg::SetPoolDistribution {
+                    lp_token: lp_asset.to_string(),
+                    distribution: RewardDistribution::VotingPowerWeighted,
+                },
+            )
+        };
+        let err = set_distribution(deps.as_mut()).unwrap_err();
+        assert_eq!(err, ContractError::VepadexNotSet {});
+        CONFIG
+            .update::<_, StdError>(deps.as_mut().storage, |mut config| {
+                config.vepadex = Some(vepadex);
+                Ok(config)
+            })
+            .unwrap();
+        set_distribution(deps.as_mut()).unwrap();
+        // Unknown LP token doesn't get a pool
+        let unknown_lp = AssetInfo::native("factory/unknown/lp");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::SetPoolDistribution {
+                lp_token: unknown_lp.to_string(),
+                distribution: RewardDistribution::VotingPowerWeighted,
+            },
+        )
+        .unwrap_err();
+        assert!(!POOLS.has(deps.as_ref().storage, &unknown_lp));
+
+        // LP amounts are the inverse of the weights
+        for (user, amount) in [(&alice, 300), (&bob, 100)] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(user, &coins(amount, lp_asset.to_string())),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+            .unwrap();
+        }
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        assert_eq!(pool_info.total_lp, Uint128::new(400));
+        assert_eq!(pool_info.total_weight, Uint128::new(400));
+
+        // Pool gets 10 PADEX per second
+        env.block.time = env.block.time.plus_seconds(1000);
+        let pending_padex = |deps: Deps, user: &Addr| {
+            let pending: Vec<PendingRewardResponse> = from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::PendingRewards {
+                        lp_token: lp_asset.to_string(),
+                        user: user.to_string(),
+                        with_metadata: false,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            pending.iter().map(|reward| reward.amount).sum::<Uint128>()
+        };
+        assert_eq!(pending_padex(deps.as_ref(), &alice), Uint128::new(2_500));
+        assert_eq!(pending_padex(deps.as_ref(), &bob), Uint128::new(7_500));
+
+        // Full withdrawal takes the weight out of the pool
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&bob, &[]),
+            ExecuteMsg::Withdraw {
+                lp_token: lp_asset.to_string(),
+                amount: Uint128::new(100),
+                user: None,
+                bridge: None,
+            },
+        )
+        .unwrap();
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        assert_eq!(pool_info.total_weight, Uint128::new(100));
+    }
+}
@@ -1,10 +1,14 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{CosmosMsg, DepsMut, Env, MessageInfo, Response, Uint128};
+use cosmwasm_std::{
+    attr, to_json_binary, CosmosMsg, DepsMut, Env, MessageInfo, Response, SubMsg, Uint128,
+};
 
-use crate::asset::{validate_native_denom, AssetInfo};
+use crate::asset::{addr_opt_validate, validate_native_denom, AssetInfo};
+use crate::constants::{EPOCH_LENGTH, MAX_PERIODS};
 use crate::error::ContractError;
 use crate::msg::InstantiateMsg;
+use crate::reply::PADEX_DENOM_REPLY_ID;
 use crate::state::{ACTIVE_POOLS, CONFIG};
 use crate::types::{Config, CreateDenomMsg, DenomUnit, Metadata, PalomaMsg};
 
@@ -22,6 +26,11 @@ pub fn instantiate(
 ) -> Result<Response<PalomaMsg>, ContractError> {
     cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    let max_periods = msg.max_periods.unwrap_or(MAX_PERIODS);
+    if max_periods == 0 {
+        return Err(ContractError::ZeroMaxPeriods {});
+    }
+
     if let Some(fee_info) = &msg.incentivization_fee_info {
         deps.api.addr_validate(fee_info.fee_receiver.as_str())?;
         validate_native_denom(&fee_info.fee.denom)?;
@@ -34,17 +43,57 @@ pub fn instantiate(
         denom: denom.clone(),
     };
 
+    let owner = deps.api.addr_validate(&msg.owner)?;
+    let factory = deps.api.addr_validate(&msg.factory)?;
+    let trader = addr_opt_validate(deps.api, &msg.trader)?;
+    let vepadex = addr_opt_validate(deps.api, &msg.vepadex)?;
+
+    let mut attrs = vec![];
+
+    if factory == env.contract.address {
+        return Err(ContractError::SelfReferencingAddress {
+            role: "factory".to_string(),
+        });
+    }
+
+    if let Some(trader) = &trader {
+        if trader == env.contract.address {
+            return Err(ContractError::SelfReferencingAddress {
+                role: "trader".to_string(),
+            });
+        }
+
+        // Owner acting as trader is allowed but most likely a misconfiguration
+        if trader == owner {
+            attrs.push(attr("warning", "trader_is_owner"));
+        }
+    }
+
+    if factory == owner {
+        attrs.push(attr("warning", "factory_is_owner"));
+    }
+
     CONFIG.save(
         deps.storage,
         &Config {
-            owner: deps.api.addr_validate(&msg.owner)?,
-            trader: deps.api.addr_validate(&msg.trader)?,
-            factory: deps.api.addr_validate(&msg.factory)?,
+            owner,
+            trader,
+            factory,
             generator_controller: None,
             padex_token: padex_token.clone(),
             padex_per_second: Uint128::zero(),
             total_alloc_points: Uint128::zero(),
             incentivization_fee_info: msg.incentivization_fee_info,
+            max_periods,
+            claim_history_enabled: msg.claim_history_enabled,
+            vepadex,
+            fee_exempt: vec![],
+            paused: false,
+            max_user_pools: None,
+            block_ibc_rewards: false,
+            min_schedule_duration: EPOCH_LENGTH,
+            max_orphaned_entries: None,
+            keeper_bounty: None,
         },
     )?;
     ACTIVE_POOLS.save(deps.storage, &vec![])?;
@@ -69,12 +118,114 @@ pub fn instantiate(
         display: msg.padex_symbol,
     };
 
-    let messages = vec![CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
-        create_denom: Some(CreateDenomMsg {
-            subdenom: subdenom.to_string(),
-            metadata,
+    // Failed denom creation reverts instantiation as a whole,
+    // so a deployed contract is always able to mint PADEX.
+    let create_denom_msg = SubMsg::reply_on_error(
+        CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
+            create_denom: Some(CreateDenomMsg {
+                subdenom: subdenom.to_string(),
+                metadata,
+            }),
+            mint_tokens: None,
         }),
-        mint_tokens: None,
-    })];
-    Ok(Response::new().add_messages(messages))
+        PADEX_DENOM_REPLY_ID,
+    )
+    .with_payload(to_json_binary(&denom)?);
+    Ok(Response::new()
+        .add_submessage(create_denom_msg)
+        .add_attributes(attrs))
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
+    use cosmwasm_std::Addr;
+
+    use super::*;
+
+    fn instantiate_msg(owner: &Addr, factory: &Addr, trader: Option<&Addr>) -> InstantiateMsg {
+        InstantiateMsg {
+            owner: owner.to_string(),
+            trader: trader.map(Addr::to_string),
+            factory: factory.to_string(),
+            incentivization_fee_info: None,
+            padex_name: "Palomadex".to_string(),
+            padex_symbol: "PADEX".to_string(),
+            padex_description: None,
+            max_periods: None,
+            claim_history_enabled: false,
+            vepadex: None,
+        }
+    }
+
+    #[test]
+    fn addresses_must_not_reference_the_contract() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let owner = deps.api.addr_make("owner");
+        let factory = deps.api.addr_make("factory");
+        let info = message_info(&owner, &[]);
+
+        let err = instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            instantiate_msg(&owner, &env.contract.address, None),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::SelfReferencingAddress {
+                role: "factory".to_string()
+            }
+        );
+
+        let err = instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            instantiate_msg(&owner, &factory, Some(&env.contract.address)),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::SelfReferencingAddress {
+                role: "trader".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn suspicious_roles_are_reported() {
+        let mut deps = mock_dependencies();
+        let owner = deps.api.addr_make("owner");
+        let info = message_info(&owner, &[]);
+
+        let res = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            instantiate_msg(&owner, &owner, Some(&owner)),
+        )
+        .unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("warning", "trader_is_owner"),
+                attr("warning", "factory_is_owner"),
+            ]
+        );
+
+        // Trader is optional
+        let factory = deps.api.addr_make("factory");
+        let res = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            instantiate_msg(&owner, &factory, None),
+        )
+        .unwrap();
+        assert!(res.attributes.is_empty());
+        assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().trader, None);
+    }
 }
@@ -13,3 +13,6 @@ pub mod state;
 pub mod traits;
 pub mod types;
 pub mod utils;
+
+#[cfg(test)]
+mod testing;
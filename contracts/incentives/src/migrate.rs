@@ -2,10 +2,11 @@
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{DepsMut, Empty, Env, Response};
+use cosmwasm_std::{ensure, DepsMut, Empty, Env, Response};
 
 use crate::error::ContractError;
 use crate::instantiate::{CONTRACT_NAME, CONTRACT_VERSION};
+use crate::state::{rebuild_reward_tokens_index, CONFIG};
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: Empty) -> Result<Response, ContractError> {
@@ -13,15 +14,51 @@ pub fn migrate(deps: DepsMut, _env: Env, _msg: Empty) -> Result<Response, Contra
 
     match contract_version.contract.as_ref() {
         "palomadex-incentives" => match contract_version.version.as_ref() {
-            "1.0.0" | "1.0.1" => {}
+            "1.0.0" | "1.0.1" | "1.1.0" => {}
             _ => return Err(ContractError::MigrationError {}),
         },
         _ => return Err(ContractError::MigrationError {}),
     };
 
+    // PADEX is always created as a token factory denom on instantiation and can't be changed later.
+    // Reject stored configs with cw20 PADEX here rather than failing on the first claim
+    ensure!(
+        CONFIG.load(deps.storage)?.padex_token.is_native_token(),
+        ContractError::PADEXNotNativeCoin {}
+    );
+
+    rebuild_reward_tokens_index(deps.storage, deps.api)?;
+
+    // Keep the stored version in sync so that the Version query reflects deployed code
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
     Ok(Response::new()
         .add_attribute("previous_contract_name", &contract_version.contract)
         .add_attribute("previous_contract_version", &contract_version.version)
         .add_attribute("new_contract_name", CONTRACT_NAME)
         .add_attribute("new_contract_version", CONTRACT_VERSION))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::setup;
+
+    #[test]
+    fn migrate_bumps_stored_version() {
+        let (mut deps, env, _, _) = setup();
+        cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "1.0.1").unwrap();
+
+        migrate(deps.as_mut(), env.clone(), Empty {}).unwrap();
+        let version = cw2::get_contract_version(deps.as_ref().storage).unwrap();
+        assert_eq!(version.version, CONTRACT_VERSION);
+
+        // Migrating to the same code again is allowed
+        migrate(deps.as_mut(), env.clone(), Empty {}).unwrap();
+
+        // Never downgrade a newer deployment
+        cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "2.0.0").unwrap();
+        let err = migrate(deps.as_mut(), env, Empty {}).unwrap_err();
+        assert_eq!(err, ContractError::MigrationError {});
+    }
+}
@@ -1,13 +1,19 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Addr, Coin, Uint128};
 use cw20::Cw20ReceiveMsg;
 
 #[allow(unused_imports)]
 use crate::{
     asset::{Asset, AssetInfo, PairInfo},
     types::{
-        Config, FeeInfoResponse, IncentivizationFeeInfo, InputSchedule, PairType, PairsResponse,
-        PoolInfoResponse, RewardInfo, ScheduleResponse,
+        BalanceReconciliationResponse, BridgeTarget, ClaimComplexityResponse, ClaimRecord, Config,
+        ConfigExtendedResponse, FeeInfoResponse, IncentivizationFeeInfo, InconsistentPoolsResponse,
+        InputSchedule, IsPoolActiveResponse, PadexEmissionWindowResponse, PadexRateResponse,
+        PairType, PairsResponse, PendingRewardDetailedResponse, PendingRewardResponse,
+        PerEpochSchedule, PoolInfoResponse, PoolLifecycleResponse, RewardBoundsResponse,
+        RewardDistribution, RewardInfo, RewardInfoResponse, RewardTimelineItem, ScheduleResponse,
+        SimulateIncentivizeResponse, StalePoolsResponse, TopPoolResponse, UserRewardRate,
+        UserRewardStateResponse, ZeroStakerPoolsResponse,
     },
 };
 
@@ -24,12 +30,21 @@ pub enum ExecuteMsg {
         /// The LP token cw20 address or token factory denom
         lp_tokens: Vec<String>,
         user: Option<String>,
+        /// Bridge claimed PADEX to the remote chain via Skyway. External rewards are sent as usual.
+        /// Not available when the trader claims on behalf of a user
+        bridge: Option<BridgeTarget>,
     },
     /// Receives a message of type [`Cw20ReceiveMsg`]. Handles cw20 LP token deposits.
     Receive(Cw20ReceiveMsg),
     /// Stake LP tokens in the Generator. LP tokens staked on behalf of recipient if recipient is set.
     /// Otherwise LP tokens are staked on behalf of message sender.
     Deposit { recipient: Option<String> },
+    /// Withdraw all LP tokens of the sender from the Generator without claiming rewards.
+    /// Unclaimed rewards are forfeited. Available even if the contract is paused.
+    EmergencyWithdraw {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+    },
     /// Withdraw LP tokens from the Generator
     Withdraw {
         /// The LP token cw20 address or token factory denom
@@ -37,13 +52,24 @@ pub enum ExecuteMsg {
         /// The amount to withdraw. Must not exceed total staked amount.
         amount: Uint128,
         user: Option<String>,
+        /// Bridge withdrawn LP tokens to the remote chain via Skyway. Claimed rewards are sent as usual.
+        /// Only native LP tokens registered with SetLpTokenBridge are eligible.
+        /// Not available when the trader withdraws on behalf of a user
+        bridge: Option<BridgeTarget>,
     },
     /// Set a new amount of PADEX to distribute per seconds.
     /// Only the owner can execute this.
     SetTokensPerSecond {
         /// The new amount of PADEX to distribute per second
         amount: Uint128,
+        /// Queue the change to take effect at this future time instead of immediately.
+        /// Replaces previously queued change. Pools switch to the new rate exactly at this time
+        effective_ts: Option<u64>,
     },
+    /// Recover from drifted state by setting total allocation points to the sum of
+    /// active pools' allocation points. PADEX emissions of active pools are redistributed.
+    /// Only the owner can execute this.
+    ReconcileAllocPoints {},
     /// Incentivize a pool with external rewards. Rewards can be in either native or cw20 form.
     /// Incentivizor must send incentivization fee along with rewards (if this reward token is new in this pool).
     /// 3rd parties are encouraged to keep endless schedules without breaks even with the small rewards.
@@ -56,6 +82,24 @@ pub enum ExecuteMsg {
         /// Incentives schedule
         schedule: InputSchedule,
     },
+    /// Same as Incentivize but the schedule amount is specified per epoch.
+    /// Each full epoch distributes amount_per_epoch and the rest of the current epoch is paid at the same rate.
+    /// The total is ceil(amount_per_epoch * (end_ts - now) / EPOCH_LENGTH), at most amount_per_epoch * (periods + 1).
+    /// Native reward sent above the total is refunded; cw20 reward is transferred in the exact total.
+    IncentivizePerEpoch {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        /// Incentives schedule
+        schedule: PerEpochSchedule,
+    },
+    /// Distribute sent native coin among current pool stakers right away.
+    /// Unlike Incentivize, rewards aren't streamed over periods.
+    /// Incentivization fee is charged if the token is a new reward for the pool.
+    /// If pool has no stakers, the donation goes to orphaned rewards.
+    Donate {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+    },
     /// Remove specific reward token from the pool.
     /// Only the owner can execute this.
     RemoveRewardFromPool {
@@ -72,6 +116,44 @@ pub enum ExecuteMsg {
         /// Receiver of unclaimed rewards
         receiver: String,
     },
+    /// Remove multiple reward tokens from the pool at once.
+    /// Same as RemoveRewardFromPool but unclaimed rewards are sent in a single transfer per token.
+    /// Only the owner can execute this.
+    RemoveRewardsFromPool {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        /// The reward tokens cw20 addresses or token factory denoms.
+        /// At most MAX_POOL_REWARD_TOKENS rewards per call
+        rewards: Vec<String>,
+        /// Bypass upcoming schedules. See RemoveRewardFromPool for details.
+        /// Default: false
+        #[serde(default)]
+        bypass_upcoming_schedules: bool,
+        /// Receiver of unclaimed rewards
+        receiver: String,
+    },
+    /// End abandoned external reward schedule at the current point.
+    /// Unlike RemoveRewardFromPool, undistributed rewards aren't sent out but moved to orphaned rewards
+    /// (claimable with ClaimOrphanedRewards) and the reward is finished through the regular pool update,
+    /// so stakers keep all rewards accrued up to now.
+    /// Only the owner can execute this.
+    ExpireSchedule {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        /// The reward token cw20 address or token factory denom
+        reward: String,
+    },
+    /// Replace external cw20 reward token whose contract migrated to a new address.
+    /// Rewrites the reward in all pools, their schedules, finished reward indexes,
+    /// orphaned rewards and outstanding liabilities. User positions are updated lazily.
+    /// Contract balance of the new token must be migrated by the cw20 itself.
+    /// Only the owner can execute this.
+    RemapRewardToken {
+        /// Old reward cw20 address
+        old: String,
+        /// New reward cw20 address
+        new: String,
+    },
     /// Claim all or up to the limit accumulated orphaned rewards.
     /// Only the owner can execute this.
     ClaimOrphanedRewards {
@@ -87,6 +169,22 @@ pub enum ExecuteMsg {
         generator_controller: Option<String>,
         /// New incentivization fee info
         incentivization_fee_info: Option<IncentivizationFeeInfo>,
+        /// New maximum number of periods per external reward schedule
+        max_periods: Option<u64>,
+        /// Enable or disable recording of users' claim history
+        claim_history_enabled: Option<bool>,
+        /// The new vePADEX contract address
+        vepadex: Option<String>,
+        /// New maximum number of pools a user can be staked in. 0 removes the limit
+        max_user_pools: Option<u32>,
+        /// Reject or allow IBC denoms as external rewards
+        block_ibc_rewards: Option<bool>,
+        /// New minimum duration of external reward schedules in seconds
+        min_schedule_duration: Option<u64>,
+        /// New maximum number of orphaned rewards entries. 0 removes the limit
+        max_orphaned_entries: Option<u32>,
+        /// New bounty paid for deactivating blocked pools. Zero amount disables the bounty
+        keeper_bounty: Option<Coin>,
     },
     /// Add or remove token to the block list.
     /// Only owner or guardian can execute this.
@@ -101,11 +199,67 @@ pub enum ExecuteMsg {
         #[serde(default)]
         remove: Vec<AssetInfo>,
     },
+    /// Stop transfers of the reward token in all pools, e.g. if its cw20 contract is compromised.
+    /// Claimed rewards in this token are held for users and paid out after the token is unfrozen.
+    /// Only the owner can execute this.
+    FreezeReward { reward: AssetInfo },
+    /// Resume transfers of the frozen reward token.
+    /// Only the owner can execute this.
+    UnfreezeReward { reward: AssetInfo },
+    /// Patch incentivization fee settings without touching the rest of the config.
+    /// Only provided fields are changed. If the fee is not set yet, both fields must be provided.
+    /// Only the owner can execute this.
+    SetIncentivizationFee {
+        /// New fee amount and denom
+        fee: Option<Coin>,
+        /// New fee receiver
+        fee_receiver: Option<String>,
+        /// Disable the incentivization fee. Can't be combined with other fields.
+        #[serde(default)]
+        clear: bool,
+    },
+    /// Suspend or resume deposits, withdrawals, claims and adding new rewards.
+    /// Emergency withdrawals remain available while paused.
+    /// Only the owner can execute this.
+    SetPaused { paused: bool },
+    /// Add or remove LP tokens which are allowed to be used as external rewards.
+    /// Only the owner can execute this.
+    UpdateLpRewardWhitelist {
+        /// Tokens to add
+        #[serde(default)]
+        add: Vec<AssetInfo>,
+        /// Tokens to remove
+        #[serde(default)]
+        remove: Vec<AssetInfo>,
+    },
+    /// Add or remove addresses which don't pay incentivization fee when adding new reward to a pool.
+    /// Only the owner can execute this.
+    UpdateFeeExemptList {
+        /// Addresses to add
+        #[serde(default)]
+        add: Vec<String>,
+        /// Addresses to remove
+        #[serde(default)]
+        remove: Vec<String>,
+    },
     /// Only factory can set the allocation points to zero for the specified pool.
     /// Initiated from deregistration context in factory.
     DeactivatePool { lp_token: String },
-    /// Go through active pools and deactivate the ones which pair type is blocked
+    /// Go through active pools and deactivate the ones which pair type or any of tokens is blocked.
+    /// The caller receives the keeper bounty if at least one pool is deactivated
+    /// and the bounty fund covers it.
     DeactivateBlockedPools {},
+    /// Top up the keeper bounty fund. Accepts only the configured bounty denom. Anyone can execute this.
+    FundKeeperBounty {},
+    /// Withdraw the keeper bounty fund of the denom, including funds left from a previous bounty denom.
+    /// Only the owner can execute this.
+    WithdrawKeeperBountyFund {
+        denom: String,
+        /// Defaults to the whole fund
+        amount: Option<Uint128>,
+        /// Defaults to the owner
+        receiver: Option<String>,
+    },
     /// Creates a request to change contract ownership
     /// Only the current owner can execute this.
     ProposeNewOwner {
@@ -113,6 +267,9 @@ pub enum ExecuteMsg {
         owner: String,
         /// The validity period of the proposal to change the contract owner
         expires_in: u64,
+        /// Minimum time before the proposed owner can claim ownership.
+        /// Gives a window to drop the proposal if the current owner is compromised
+        delay: Option<u64>,
     },
     /// Removes a request to change contract ownership
     /// Only the current owner can execute this
@@ -124,17 +281,85 @@ pub enum ExecuteMsg {
         erc20_address: String,
         chain_reference_id: String,
     },
+    /// Register native LP token in Skyway so that withdrawn LP tokens can be bridged to the remote chain.
+    /// Only the owner can execute this.
+    SetLpTokenBridge {
+        /// The LP token factory denom
+        lp_token: String,
+        erc20_address: String,
+        chain_reference_id: String,
+    },
+    /// Set or unset custody account receiving rewards claimed by the trader on behalf of the sender.
+    /// Only the user themselves can set it.
+    SetCustodyReceiver {
+        /// Custody account address. None removes custody receiver
+        receiver: Option<String>,
+    },
+    /// Add or remove addresses allowed to receive rewards claimed on behalf of the sender.
+    /// If the list is empty, rewards can be sent to any receiver.
+    /// Only the user themselves can update it.
+    UpdateAllowedReceivers {
+        /// Addresses to add
+        #[serde(default)]
+        add: Vec<String>,
+        /// Addresses to remove
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    /// Set or unset the pair of the token factory LP token minted by a contract other than the pair itself
+    /// e.g. by the factory. The pair must be registered in the factory with this LP token.
+    /// Only the owner can execute this.
+    SetLpTokenPair {
+        /// The LP token token factory denom
+        lp_token: String,
+        /// Pair contract address. None removes the mapping
+        pair: Option<String>,
+    },
+    /// Set how PADEX emissions are split between pool stakers.
+    /// Can be changed only while the pool has no stakers.
+    /// Only the owner can execute this.
+    SetPoolDistribution {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        distribution: RewardDistribution,
+    },
+    /// Override the maximum number of external reward tokens for a specific pool.
+    /// Existing rewards are kept if the new limit is lower.
+    /// Only the owner can execute this.
+    SetPoolMaxRewardTokens {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        /// New limit. None resets to the default MAX_REWARD_TOKENS
+        max_reward_tokens: Option<u8>,
+    },
+    /// Set or unset the minimum amount of LP tokens deposited into the pool without stakers.
+    /// Subsequent deposits of any size are allowed.
+    /// Only the owner can execute this.
+    SetPoolMinFirstDeposit {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        /// New minimum. None removes it
+        min_amount: Option<Uint128>,
+    },
 }
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub owner: String,
-    pub trader: String,
+    /// Trader contract allowed to act on behalf of users. Optional
+    pub trader: Option<String>,
     pub factory: String,
     pub incentivization_fee_info: Option<IncentivizationFeeInfo>,
     pub padex_name: String,
     pub padex_symbol: String,
     pub padex_description: Option<String>,
+    /// Maximum number of periods per external reward schedule. Default: 25
+    pub max_periods: Option<u64>,
+    /// Whether to record users' claim history. Default: false
+    #[serde(default)]
+    pub claim_history_enabled: bool,
+    /// vePADEX contract used by voting power weighted pools
+    pub vepadex: Option<String>,
 }
 
 #[cw_serde]
@@ -143,23 +368,75 @@ pub enum QueryMsg {
     /// Config returns the main contract parameters
     #[returns(Config)]
     Config {},
+    /// Returns the main contract parameters along with derived fields
+    #[returns(ConfigExtendedResponse)]
+    ConfigExtended {},
     /// Deposit returns the LP token amount deposited in a specific generator
     #[returns(Uint128)]
     Deposit { lp_token: String, user: String },
+    /// Returns the maximum amount of LP tokens the user can withdraw. Zero if there is no position
+    #[returns(Uint128)]
+    MaxWithdrawable { lp_token: String, user: String },
     /// PendingToken returns the amount of rewards that can be claimed by an account that deposited a specific LP token in a generator
-    #[returns(Vec<Asset>)]
-    PendingRewards { lp_token: String, user: String },
+    #[returns(Vec<PendingRewardResponse>)]
+    PendingRewards {
+        lp_token: String,
+        user: String,
+        /// Include reward token symbol and decimals. Costs extra queries. Default: false
+        #[serde(default)]
+        with_metadata: bool,
+    },
+    /// Estimates the size of ClaimRewards for the specified pools.
+    /// Helps to decide whether a claim should be split into several transactions
+    #[returns(ClaimComplexityResponse)]
+    ClaimComplexity {
+        lp_tokens: Vec<String>,
+        user: String,
+    },
+    /// Same as PendingRewards but each reward is split into base amount and boost contribution.
+    /// Total pending amount is base + boosted_delta
+    #[returns(Vec<PendingRewardDetailedResponse>)]
+    PendingRewardsDetailed { lp_token: String, user: String },
+    /// Returns raw reward indexes of the user position and the pool.
+    /// Active reward pending amount equals (pool_index - user_index) * amount.
+    /// Outstanding rewards from finished schedules are not reflected here.
+    #[returns(UserRewardStateResponse)]
+    UserRewardState { lp_token: String, user: String },
+    /// Returns reward tokens per second the user is currently earning in the pool per reward.
+    /// PADEX in voting power weighted pools is shared by vePADEX weight, all other rewards by LP amount.
+    #[returns(Vec<UserRewardRate>)]
+    UserRewardRate { lp_token: String, user: String },
     /// RewardInfo returns reward information for a specified LP token
-    #[returns(Vec<RewardInfo>)]
-    RewardInfo { lp_token: String },
+    #[returns(Vec<RewardInfoResponse>)]
+    RewardInfo {
+        lp_token: String,
+        /// Include reward token symbol and decimals. Costs extra queries. Default: false
+        #[serde(default)]
+        with_metadata: bool,
+    },
+    /// Returns reward information for each specified LP token in the same order.
+    /// Number of LP tokens is limited by 50
+    #[returns(Vec<(String, Vec<RewardInfoResponse>)>)]
+    RewardInfoBatch {
+        lp_tokens: Vec<String>,
+        /// Include reward token symbol and decimals. Costs extra queries. Default: false
+        #[serde(default)]
+        with_metadata: bool,
+    },
+    /// Returns the maximum number of external reward tokens allowed in the pool
+    #[returns(u8)]
+    MaxRewardTokens { lp_token: String },
     /// PoolInfo returns information about a pool associated with the specified LP token
     #[returns(PoolInfoResponse)]
     PoolInfo { lp_token: String },
-    /// Returns a list of tuples with addresses and their staked amount
+    /// Returns a list of tuples with addresses and their staked amount.
+    /// Stakers are sorted by address bytes in ascending order.
     #[returns(Vec<(String, Uint128)>)]
     PoolStakers {
         lp_token: String,
+        /// Start after specified address (exclusive)
         start_after: Option<String>,
+        /// Limit number of returned stakers. Capped at 50
         limit: Option<u8>,
     },
     /// Returns paginated list of blocked tokens
@@ -168,6 +445,23 @@ pub enum QueryMsg {
         start_after: Option<AssetInfo>,
         limit: Option<u8>,
     },
+    /// Returns paginated list of frozen reward tokens
+    #[returns(Vec<AssetInfo>)]
+    FrozenRewards {
+        start_after: Option<AssetInfo>,
+        limit: Option<u8>,
+    },
+    /// Returns the minimum and maximum reward amount for a new schedule starting now
+    /// and lasting for the specified number of periods
+    #[returns(RewardBoundsResponse)]
+    RewardBounds {
+        /// Reward cw20 addr/denom
+        reward: String,
+        duration_periods: u64,
+    },
+    /// Returns the list of addresses exempted from incentivization fee
+    #[returns(Vec<Addr>)]
+    FeeExemptList {},
     /// Checks whether fee expected for the specified pool if user wants to add new reward schedule
     #[returns(bool)]
     IsFeeExpected { lp_token: String, reward: String },
@@ -182,17 +476,185 @@ pub enum QueryMsg {
         /// Limit number of returned schedules.
         limit: Option<u8>,
     },
+    /// Returns queued schedules of the external reward which haven't started yet.
+    /// Unlike ExternalRewardSchedules the currently active schedule is not included
+    #[returns(Vec<ScheduleResponse>)]
+    PendingSchedules {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        /// Reward cw20 addr/denom
+        reward: String,
+    },
+    /// Returns recorded periods of the external reward in the pool including finished ones.
+    /// Periods are sorted by end time. Use the last end_ts as start_after to fetch the next page
+    #[returns(Vec<RewardTimelineItem>)]
+    RewardTimeline {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        /// Reward cw20 addr/denom
+        reward: String,
+        /// Start after specified end timestamp
+        start_after: Option<u64>,
+        /// Limit number of returned periods. Capped at 50
+        limit: Option<u8>,
+    },
     #[returns(Vec<String>)]
-    /// Returns the list of all ever incentivized pools
+    /// Returns the list of all ever incentivized pools including deactivated ones
     ListPools {
         /// Start after specified LP token
         start_after: Option<String>,
-        /// Limit number of returned pools.
+        /// Limit number of returned pools. Capped at 50
+        limit: Option<u8>,
+    },
+    #[returns(ZeroStakerPoolsResponse)]
+    /// Returns pools without stakers which accrue rewards as orphaned.
+    /// Iterates over the same list as ListPools, at most `limit` pools per call,
+    /// so a page may contain fewer items than the limit.
+    ZeroStakerPools {
+        /// Start after specified LP token
+        start_after: Option<String>,
+        /// Limit number of scanned pools.
+        limit: Option<u8>,
+    },
+    #[returns(StalePoolsResponse)]
+    /// Returns pools which weren't updated for more than `older_than_secs` seconds.
+    /// Keepers can checkpoint them to keep accounting fresh.
+    /// Pages are scanned the same way as in ZeroStakerPools.
+    StalePools {
+        older_than_secs: u64,
+        /// Start after specified LP token
+        start_after: Option<String>,
+        /// Limit number of scanned pools.
+        limit: Option<u8>,
+    },
+    #[returns(InconsistentPoolsResponse)]
+    /// Returns active pools whose LP token or any of pair assets is blocked.
+    /// Such pools are deactivated by DeactivateBlockedPools.
+    /// Active pools are scanned in their stored order, at most `limit` per page.
+    InconsistentPools {
+        /// Start after specified LP token
+        start_after: Option<String>,
+        /// Limit number of scanned pools.
+        limit: Option<u8>,
+    },
+    #[returns(Vec<AssetInfo>)]
+    /// Returns deduplicated list of reward tokens used across all pools, including PADEX.
+    /// Tokens are sorted the same way as in BlockedTokensList.
+    AllRewardTokens {
+        /// Start after specified reward token
+        start_after: Option<AssetInfo>,
+        /// Limit number of returned tokens.
         limit: Option<u8>,
     },
     #[returns(Vec<(String, Uint128)>)]
     /// Returns the list of all pools receiving padex emissions
     ActivePools {},
+    #[returns(Vec<(String, Uint128)>)]
+    /// Runs all SetupPools checks against the current state without executing it.
+    /// Returns the first error found or pools as they would be stored in ActivePools.
+    /// Sender permissions are not checked
+    SimulateSetupPools { pools: Vec<(String, Uint128)> },
+    #[returns(u32)]
+    /// Returns the number of orphaned rewards entries limited by max_orphaned_entries in config
+    OrphanedEntriesCount {},
+    #[returns(SimulateIncentivizeResponse)]
+    /// Runs Incentivize checks and splits sent funds between incentivization fee and reward
+    /// without executing it. Returns the first error found.
+    /// Fee exemption is considered only if sender is specified
+    SimulateIncentivize {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+        schedule: InputSchedule,
+        /// Native coins which would be sent along with Incentivize
+        sent_funds: Vec<Coin>,
+        sender: Option<String>,
+    },
+    #[returns(AssetInfo)]
+    /// Returns the PADEX token asset info
+    PadexToken {},
+    #[returns(Vec<PairType>)]
+    /// Returns pair types blacklisted in the factory. Pools of these types are rejected by SetupPools
+    /// and removed from active pools by DeactivateBlockedPools
+    BlacklistedPairTypes {},
+    #[returns(Option<Coin>)]
+    /// Returns the remaining keeper bounty fund in the configured bounty denom. None if the bounty is not set
+    KeeperBountyFund {},
+    #[returns(Option<Addr>)]
+    /// Returns custody account receiving rewards claimed by the trader on behalf of the user
+    CustodyReceiver { user: String },
+    #[returns(Vec<Addr>)]
+    /// Returns addresses allowed to receive rewards claimed on behalf of the user.
+    /// Empty list means any receiver is allowed
+    AllowedReceivers { user: String },
+    #[returns(Option<Addr>)]
+    /// Returns the pair stored for the token factory LP token which isn't minted by its pair
+    LpTokenPair {
+        /// The LP token token factory denom
+        lp_token: String,
+    },
+    #[returns(bool)]
+    /// Checks whether LP token doesn't match the one registered in the factory anymore.
+    /// Stale pools don't accept new deposits while existing stakers can still withdraw.
+    IsLpTokenStale {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+    },
+    #[returns(Vec<(u64, ClaimRecord)>)]
+    /// Returns user's claim history records with their sequence numbers, oldest first.
+    /// Only the latest records are kept and only while claim history is enabled in config.
+    ClaimHistory {
+        user: String,
+        /// Start after specified sequence number
+        start_after: Option<u64>,
+        /// Limit number of returned records.
+        limit: Option<u8>,
+    },
+    #[returns(cw2::ContractVersion)]
+    /// Returns the contract name and version stored by cw2 on instantiate and migrate
+    Version {},
+    #[returns(PoolLifecycleResponse)]
+    /// Returns when the pool was first incentivized, last updated and whether it is active
+    PoolLifecycle {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+    },
+    #[returns(PadexRateResponse)]
+    /// Returns PADEX rewards per second in effect and the queued rate change if any
+    PadexRate {},
+    #[returns(PadexEmissionWindowResponse)]
+    /// Returns the time window during which the specified pool receives padex emissions
+    PadexEmissionWindow {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+    },
+    #[returns(Uint128)]
+    /// Returns the total amount of the external reward token the contract still owes
+    /// to stakers and incentivizers. Compare it with the contract balance to verify solvency.
+    /// PADEX is minted on claim and is never accounted here.
+    RewardLiability {
+        /// The reward token cw20 address or token factory denom
+        reward: String,
+    },
+    #[returns(BalanceReconciliationResponse)]
+    /// Returns the actual contract balance of the external reward token alongside the accounted liability.
+    /// Balance below liability indicates an accounting bug, balance above it indicates stuck tokens.
+    /// If the token is also staked as an LP token, the balance includes staked amount as well.
+    BalanceReconciliation {
+        /// The reward token cw20 address or token factory denom
+        reward: String,
+    },
+    #[returns(Vec<TopPoolResponse>)]
+    /// Returns active pools sorted by PADEX emission rate in descending order.
+    /// All active pools are loaded and sorted on every call thus
+    /// gas cost grows with the number of active pools rather than with the limit.
+    /// Limit is capped at 10
+    TopPools { limit: Option<u8> },
+    #[returns(IsPoolActiveResponse)]
+    /// Checks whether the specified pool is receiving padex emissions
+    IsPoolActive {
+        /// The LP token cw20 address or token factory denom
+        lp_token: String,
+    },
 }
 
 #[cw_serde]
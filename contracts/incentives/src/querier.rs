@@ -3,7 +3,7 @@ use crate::asset::{Asset, AssetInfo, PairInfo};
 use crate::msg::FactoryQueryMsg;
 use crate::types::{
     FactoryConfig, FeeInfoResponse, PairQueryMsg, PairType, PairsResponse,
-    ReverseSimulationResponse, SimulationResponse,
+    ReverseSimulationResponse, SimulationResponse, TokenMetadata,
 };
 
 use cosmwasm_std::{
@@ -122,6 +122,32 @@ where
     })
 }
 
+/// Returns a token's symbol and number of decimals. Native tokens report their denom as symbol.
+/// cw20 token info is queried once.
+pub fn query_token_metadata<C>(
+    querier: &QuerierWrapper<C>,
+    asset_info: &AssetInfo,
+) -> StdResult<TokenMetadata>
+where
+    C: CustomQuery,
+{
+    Ok(match asset_info {
+        AssetInfo::NativeToken { denom } => TokenMetadata {
+            symbol: denom.clone(),
+            decimals: query_token_precision(querier, asset_info)?,
+        },
+        AssetInfo::Token { contract_addr } => {
+            let res: TokenInfoResponse =
+                querier.query_wasm_smart(contract_addr, &Cw20QueryMsg::TokenInfo {})?;
+
+            TokenMetadata {
+                symbol: res.symbol,
+                decimals: res.decimals,
+            }
+        }
+    })
+}
+
 /// Returns the configuration for the factory contract.
 pub fn query_factory_config<C>(
     querier: &QuerierWrapper<C>,
@@ -234,3 +260,59 @@ pub fn reverse_simulate(
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use cosmwasm_std::testing::mock_dependencies;
+    use cosmwasm_std::{
+        to_json_binary, ContractResult, Empty, SystemError, SystemResult, WasmQuery,
+    };
+
+    use super::*;
+
+    #[test]
+    fn token_metadata_queries_cw20_token_info_once() {
+        let mut deps = mock_dependencies();
+        let token = deps.api.addr_make("token");
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { .. } => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let token_info = TokenInfoResponse {
+                    name: "Reward".to_string(),
+                    symbol: "RWD".to_string(),
+                    decimals: 8,
+                    total_supply: Uint128::zero(),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&token_info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        let querier = QuerierWrapper::<Empty>::new(&deps.querier);
+
+        let metadata = query_token_metadata(&querier, &AssetInfo::cw20(token)).unwrap();
+        assert_eq!(
+            metadata,
+            TokenMetadata {
+                symbol: "RWD".to_string(),
+                decimals: 8,
+            }
+        );
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        // Native tokens don't need any queries
+        let metadata = query_token_metadata(&querier, &AssetInfo::native("ureward")).unwrap();
+        assert_eq!(
+            metadata,
+            TokenMetadata {
+                symbol: "ureward".to_string(),
+                decimals: 6,
+            }
+        );
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,45 +1,301 @@
+use std::collections::{BTreeMap, HashSet};
+
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    ensure, to_json_binary, Binary, Deps, Env, Order, StdError, StdResult, Uint128,
+    ensure, to_json_binary, Binary, Coin, Decimal256, Deps, Env, Order, StdError, StdResult,
+    Uint128,
 };
-use cw_storage_plus::Bound;
+use cw_storage_plus::{Bound, Map};
 use itertools::Itertools;
 
-use crate::asset::{determine_asset_info, Asset, AssetInfo, AssetInfoExt};
-use crate::constants::MAX_PAGE_LIMIT;
+use crate::asset::{determine_asset_info, Asset, AssetInfo, AssetInfoExt, CoinsExt, LpToken};
+use crate::constants::{MAX_PAGE_LIMIT, MAX_TOP_POOLS_LIMIT};
 use crate::error::ContractError;
 use crate::msg::QueryMsg;
+use crate::querier::query_token_metadata;
 use crate::state::{
-    list_pool_stakers, PoolInfo, UserInfo, ACTIVE_POOLS, BLOCKED_TOKENS, CONFIG,
-    EXTERNAL_REWARD_SCHEDULES, POOLS,
+    ensure_orphaned_capacity, is_new_orphaned_entry, list_claim_history, list_pool_stakers,
+    load_effective_config, padex_rps_distribution, PoolInfo, UserInfo, ACTIVE_POOLS,
+    ALLOWED_RECEIVERS, BLOCKED_TOKENS, CONFIG, CUSTODY_RECEIVERS, EXTERNAL_REWARD_SCHEDULES,
+    FROZEN_REWARDS, KEEPER_BOUNTY_FUNDS, LP_TOKEN_PAIRS, ORPHANED_ENTRIES_COUNT, ORPHANED_REWARDS,
+    POOLS, REWARD_ACTIVE_PERIODS, REWARD_LIABILITIES, REWARD_TOKEN_POOLS_COUNT,
+    SCHEDULED_PADEX_RATE,
+};
+use crate::types::{
+    BalanceReconciliationResponse, ClaimComplexityResponse, ConfigExtendedResponse,
+    IncentivesSchedule, InconsistentPool, InconsistentPoolsResponse, InputSchedule,
+    IsPoolActiveResponse, PadexEmissionWindowResponse, PadexRateResponse,
+    PendingRewardDetailedResponse, PendingRewardResponse, PoolLifecycleResponse,
+    RewardBoundsResponse, RewardDistribution, RewardInfoResponse, RewardTimelineItem, RewardType,
+    ScheduleResponse, ScheduleStatus, SimulateIncentivizeResponse, StalePool, StalePoolsResponse,
+    TopPoolResponse, UserRewardIndex, UserRewardRate, UserRewardStateResponse, ZeroStakerPool,
+    ZeroStakerPoolsResponse,
+};
+use crate::utils::{
+    asset_info_key, deduct_incentivization_fee, find_blocked_tokens, from_key_to_asset_info,
+    is_lp_token_stale, query_blacklisted_pair_types, query_pair_info, validate_schedule,
+    validate_setup_pools,
 };
-use crate::types::{RewardType, ScheduleResponse};
-use crate::utils::{asset_info_key, from_key_to_asset_info};
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
     match msg {
-        QueryMsg::Config {} => Ok(to_json_binary(&CONFIG.load(deps.storage)?)?),
-        QueryMsg::Deposit { lp_token, user } => {
+        QueryMsg::Config {} => Ok(to_json_binary(&load_effective_config(deps.storage, &env)?)?),
+        QueryMsg::ConfigExtended {} => {
+            let config = load_effective_config(deps.storage, &env)?;
+            Ok(to_json_binary(&ConfigExtendedResponse {
+                padex_denom: config.padex_token.to_string(),
+                active_pools: ACTIVE_POOLS.load(deps.storage)?.len() as u32,
+                incentivization_fee_required: config.incentivization_fee_info.is_some(),
+                config,
+            })?)
+        }
+        QueryMsg::CustodyReceiver { user } => Ok(to_json_binary(
+            &CUSTODY_RECEIVERS.may_load(deps.storage, &user)?,
+        )?),
+        QueryMsg::AllowedReceivers { user } => Ok(to_json_binary(
+            &ALLOWED_RECEIVERS
+                .prefix(&user)
+                .keys(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?,
+        )?),
+        QueryMsg::LpTokenPair { lp_token } => Ok(to_json_binary(
+            &LP_TOKEN_PAIRS.may_load(deps.storage, &lp_token)?,
+        )?),
+        QueryMsg::IsLpTokenStale { lp_token } => {
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            let config = CONFIG.load(deps.storage)?;
+            Ok(to_json_binary(&is_lp_token_stale(
+                deps, &config, &lp_asset,
+            ))?)
+        }
+        QueryMsg::ClaimHistory {
+            user,
+            start_after,
+            limit,
+        } => Ok(to_json_binary(&list_claim_history(
+            deps.storage,
+            &user,
+            start_after,
+            limit,
+        )?)?),
+        QueryMsg::Version {} => Ok(to_json_binary(&cw2::get_contract_version(deps.storage)?)?),
+        QueryMsg::PoolLifecycle { lp_token } => {
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            let pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
+            Ok(to_json_binary(&PoolLifecycleResponse {
+                first_incentivized_ts: pool_info.first_incentivized_ts,
+                last_update_ts: pool_info.last_update_ts,
+                is_active: pool_info.is_active_pool(),
+            })?)
+        }
+        QueryMsg::PadexEmissionWindow { lp_token } => {
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            let start = PoolInfo::may_load(deps.storage, &lp_asset)?
+                .filter(|pool_info| pool_info.is_active_pool())
+                .and_then(|pool_info| pool_info.activated_at);
+            Ok(to_json_binary(&PadexEmissionWindowResponse {
+                start,
+                end: None,
+            })?)
+        }
+        QueryMsg::RewardLiability { reward } => {
+            let reward_asset = determine_asset_info(&reward, deps.api)?;
+            Ok(to_json_binary(
+                &REWARD_LIABILITIES
+                    .may_load(deps.storage, &asset_info_key(&reward_asset))?
+                    .unwrap_or_default(),
+            )?)
+        }
+        QueryMsg::BalanceReconciliation { reward } => {
+            let reward_asset = determine_asset_info(&reward, deps.api)?;
+            let key = asset_info_key(&reward_asset);
+            Ok(to_json_binary(&BalanceReconciliationResponse {
+                balance: reward_asset.query_pool(&deps.querier, &env.contract.address)?,
+                liability: REWARD_LIABILITIES
+                    .may_load(deps.storage, &key)?
+                    .unwrap_or_default(),
+                orphaned: ORPHANED_REWARDS
+                    .may_load(deps.storage, &key)?
+                    .unwrap_or_default(),
+            })?)
+        }
+        QueryMsg::BlacklistedPairTypes {} => {
+            let config = CONFIG.load(deps.storage)?;
+            Ok(to_json_binary(&query_blacklisted_pair_types(
+                deps.querier,
+                &config,
+            )?)?)
+        }
+        QueryMsg::KeeperBountyFund {} => {
+            let fund = CONFIG
+                .load(deps.storage)?
+                .keeper_bounty
+                .map(|bounty| -> StdResult<_> {
+                    let amount = KEEPER_BOUNTY_FUNDS
+                        .may_load(deps.storage, &bounty.denom)?
+                        .unwrap_or_default();
+                    Ok(Coin::new(amount, bounty.denom))
+                })
+                .transpose()?;
+            Ok(to_json_binary(&fund)?)
+        }
+        QueryMsg::PadexRate {} => Ok(to_json_binary(&PadexRateResponse {
+            padex_per_second: load_effective_config(deps.storage, &env)?.padex_per_second,
+            scheduled: SCHEDULED_PADEX_RATE
+                .may_load(deps.storage)?
+                .filter(|rate| rate.effective_ts > env.block.time.seconds()),
+        })?),
+        QueryMsg::PadexToken {} => Ok(to_json_binary(&CONFIG.load(deps.storage)?.padex_token)?),
+        // Whole staked amount can be withdrawn at any time
+        QueryMsg::Deposit { lp_token, user } | QueryMsg::MaxWithdrawable { lp_token, user } => {
             let lp_asset = determine_asset_info(&lp_token, deps.api)?;
             let amount = UserInfo::may_load_position(deps.storage, &user, &lp_asset)?
                 .map(|maybe_pos| maybe_pos.amount)
                 .unwrap_or_default();
             Ok(to_json_binary(&amount)?)
         }
-        QueryMsg::PendingRewards { lp_token, user } => Ok(to_json_binary(&query_pending_rewards(
-            deps, env, user, lp_token,
-        )?)?),
-        QueryMsg::RewardInfo { lp_token } => {
+        QueryMsg::PendingRewards {
+            lp_token,
+            user,
+            with_metadata,
+        } => {
+            let rewards = query_pending_rewards(deps, env, user, lp_token)?
+                .into_iter()
+                .map(|asset| {
+                    let metadata = with_metadata
+                        .then(|| query_token_metadata(&deps.querier, &asset.info))
+                        .transpose()?;
+                    Ok(PendingRewardResponse {
+                        info: asset.info,
+                        amount: asset.amount,
+                        metadata,
+                    })
+                })
+                .collect::<StdResult<Vec<_>>>()?;
+            Ok(to_json_binary(&rewards)?)
+        }
+        QueryMsg::UserRewardState { lp_token, user } => {
             let lp_asset = determine_asset_info(&lp_token, deps.api)?;
             let mut pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
             pool_info.update_rewards(deps.storage, &env, &lp_asset)?;
-            Ok(to_json_binary(&pool_info.rewards)?)
+            let pos = UserInfo::load_position(deps.storage, &user, &lp_asset)?;
+
+            let rewards = pool_info
+                .rewards
+                .into_iter()
+                .map(|reward_info| UserRewardIndex {
+                    user_index: pos.last_rewards_index.iter().find_map(|(reward, index)| {
+                        reward.matches(&reward_info.reward).then_some(*index)
+                    }),
+                    reward: reward_info.reward,
+                    pool_index: reward_info.index,
+                    orphaned: reward_info.orphaned,
+                })
+                .collect();
+
+            Ok(to_json_binary(&UserRewardStateResponse {
+                amount: pos.amount,
+                weight: pos.weight,
+                rewards,
+            })?)
+        }
+        QueryMsg::UserRewardRate { lp_token, user } => {
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            let mut pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
+            pool_info.update_rewards(deps.storage, &env, &lp_asset)?;
+            let pos = UserInfo::load_position(deps.storage, &user, &lp_asset)?;
+
+            let rates = pool_info
+                .rewards
+                .iter()
+                .map(|reward_info| {
+                    let total_shares = if !reward_info.reward.is_external()
+                        && pool_info.distribution == RewardDistribution::VotingPowerWeighted
+                    {
+                        pool_info.total_weight
+                    } else {
+                        pool_info.total_lp
+                    };
+                    let rps = if total_shares.is_zero() {
+                        Decimal256::zero()
+                    } else {
+                        reward_info.rps
+                            * Decimal256::from_ratio(
+                                pos.shares(&reward_info.reward, pool_info.distribution),
+                                total_shares,
+                            )
+                    };
+                    UserRewardRate {
+                        reward: reward_info.reward.clone(),
+                        rps,
+                    }
+                })
+                .collect_vec();
+
+            Ok(to_json_binary(&rates)?)
+        }
+        QueryMsg::ClaimComplexity { lp_tokens, user } => Ok(to_json_binary(
+            &query_claim_complexity(deps, env, lp_tokens, user)?,
+        )?),
+        QueryMsg::PendingRewardsDetailed { lp_token, user } => {
+            // There is no boost yet thus the whole pending amount is base reward
+            let rewards = query_pending_rewards(deps, env, user, lp_token)?
+                .into_iter()
+                .map(|asset| PendingRewardDetailedResponse {
+                    info: asset.info,
+                    base: asset.amount,
+                    boosted_delta: Uint128::zero(),
+                })
+                .collect_vec();
+            Ok(to_json_binary(&rewards)?)
+        }
+        QueryMsg::RewardInfo {
+            lp_token,
+            with_metadata,
+        } => Ok(to_json_binary(&query_reward_info(
+            deps,
+            &env,
+            lp_token,
+            with_metadata,
+        )?)?),
+        QueryMsg::RewardInfoBatch {
+            lp_tokens,
+            with_metadata,
+        } => {
+            ensure!(
+                lp_tokens.len() <= MAX_PAGE_LIMIT as usize,
+                StdError::generic_err(format!(
+                    "Number of LP tokens must not exceed {MAX_PAGE_LIMIT}"
+                ))
+            );
+            let rewards = lp_tokens
+                .into_iter()
+                .map(|lp_token| {
+                    let rewards = query_reward_info(deps, &env, lp_token.clone(), with_metadata)?;
+                    Ok((lp_token, rewards))
+                })
+                .collect::<Result<Vec<_>, ContractError>>()?;
+            Ok(to_json_binary(&rewards)?)
         }
         QueryMsg::BlockedTokensList { start_after, limit } => Ok(to_json_binary(
             &query_blocked_tokens(deps, start_after, limit)?,
         )?),
+        QueryMsg::FrozenRewards { start_after, limit } => Ok(to_json_binary(
+            &query_frozen_rewards(deps, start_after, limit)?,
+        )?),
+        QueryMsg::AllRewardTokens { start_after, limit } => Ok(to_json_binary(
+            &query_all_reward_tokens(deps, start_after, limit)?,
+        )?),
+        QueryMsg::MaxRewardTokens { lp_token } => {
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            Ok(to_json_binary(
+                &PoolInfo::may_load(deps.storage, &lp_asset)?
+                    .unwrap_or_default()
+                    .max_reward_tokens(),
+            )?)
+        }
         QueryMsg::PoolInfo { lp_token } => {
             let lp_asset = determine_asset_info(&lp_token, deps.api)?;
             Ok(to_json_binary(
@@ -55,6 +311,19 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractErro
             let stakers = list_pool_stakers(deps.storage, &lp_asset, start_after, limit)?;
             Ok(to_json_binary(&stakers)?)
         }
+        QueryMsg::RewardBounds {
+            reward,
+            duration_periods,
+        } => {
+            // Reward token doesn't affect bounds but must be a valid asset
+            determine_asset_info(&reward, deps.api)?;
+            let max_periods = CONFIG.load(deps.storage)?.max_periods;
+            Ok(to_json_binary(&RewardBoundsResponse {
+                min: IncentivesSchedule::min_reward_amount(&env, duration_periods, max_periods)?,
+                max: None,
+            })?)
+        }
+        QueryMsg::FeeExemptList {} => Ok(to_json_binary(&CONFIG.load(deps.storage)?.fee_exempt)?),
         QueryMsg::IsFeeExpected { lp_token, reward } => {
             let lp_asset = determine_asset_info(&lp_token, deps.api)?;
             let pool_info = PoolInfo::may_load(deps.storage, &lp_asset)?;
@@ -91,9 +360,42 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractErro
             start_after,
             limit,
         )?)?),
+        QueryMsg::PendingSchedules { lp_token, reward } => Ok(to_json_binary(
+            &query_pending_schedules(deps, env, reward, lp_token)?,
+        )?),
+        QueryMsg::RewardTimeline {
+            lp_token,
+            reward,
+            start_after,
+            limit,
+        } => Ok(to_json_binary(&query_reward_timeline(
+            deps,
+            env,
+            lp_token,
+            reward,
+            start_after,
+            limit,
+        )?)?),
         QueryMsg::ListPools { start_after, limit } => {
             Ok(to_json_binary(&list_pools(deps, start_after, limit)?)?)
         }
+        QueryMsg::ZeroStakerPools { start_after, limit } => Ok(to_json_binary(
+            &query_zero_staker_pools(deps, env, start_after, limit)?,
+        )?),
+        QueryMsg::InconsistentPools { start_after, limit } => Ok(to_json_binary(
+            &query_inconsistent_pools(deps, start_after, limit)?,
+        )?),
+        QueryMsg::StalePools {
+            older_than_secs,
+            start_after,
+            limit,
+        } => Ok(to_json_binary(&query_stale_pools(
+            deps,
+            env,
+            older_than_secs,
+            start_after,
+            limit,
+        )?)?),
         QueryMsg::ActivePools {} => {
             let pools = ACTIVE_POOLS
                 .load(deps.storage)?
@@ -102,6 +404,66 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractErro
                 .collect_vec();
             Ok(to_json_binary(&pools)?)
         }
+        QueryMsg::TopPools { limit } => {
+            let limit = limit
+                .unwrap_or(MAX_TOP_POOLS_LIMIT)
+                .min(MAX_TOP_POOLS_LIMIT);
+            let config = load_effective_config(deps.storage, &env)?;
+            let active_pools = ACTIVE_POOLS.load(deps.storage)?;
+            // Same distribution as in PoolInfo::set_padex_rewards
+            let distribution = padex_rps_distribution(&config, &active_pools);
+            let pools = active_pools
+                .into_iter()
+                .zip(distribution)
+                .sorted_by(|((a_info, a_points), _), ((b_info, b_points), _)| {
+                    // Ties are ordered by LP token to keep the result deterministic
+                    b_points
+                        .cmp(a_points)
+                        .then_with(|| a_info.to_string().cmp(&b_info.to_string()))
+                })
+                .take(limit as usize)
+                .map(
+                    |((lp_token, alloc_points), padex_per_second)| TopPoolResponse {
+                        lp_token: lp_token.to_string(),
+                        alloc_points,
+                        padex_per_second,
+                    },
+                )
+                .collect_vec();
+            Ok(to_json_binary(&pools)?)
+        }
+        QueryMsg::SimulateSetupPools { pools } => {
+            let config = CONFIG.load(deps.storage)?;
+            let (pools, _) = validate_setup_pools(deps, &config, pools)?;
+            let pools = pools
+                .into_iter()
+                .map(|(asset_info, alloc_points)| (asset_info.to_string(), alloc_points))
+                .collect_vec();
+            Ok(to_json_binary(&pools)?)
+        }
+        QueryMsg::OrphanedEntriesCount {} => Ok(to_json_binary(
+            &ORPHANED_ENTRIES_COUNT
+                .may_load(deps.storage)?
+                .unwrap_or_default(),
+        )?),
+        QueryMsg::SimulateIncentivize {
+            lp_token,
+            schedule,
+            sent_funds,
+            sender,
+        } => Ok(to_json_binary(&simulate_incentivize(
+            deps, env, lp_token, schedule, sent_funds, sender,
+        )?)?),
+        QueryMsg::IsPoolActive { lp_token } => {
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            let alloc_points = ACTIVE_POOLS.load(deps.storage)?.into_iter().find_map(
+                |(asset_info, alloc_points)| (asset_info == lp_asset).then_some(alloc_points),
+            );
+            Ok(to_json_binary(&IsPoolActiveResponse {
+                is_active: alloc_points.is_some(),
+                alloc_points,
+            })?)
+        }
     }
 }
 
@@ -110,7 +472,8 @@ fn list_pools(
     start_after: Option<String>,
     limit: Option<u8>,
 ) -> StdResult<Vec<String>> {
-    let limit = limit.unwrap_or(MAX_PAGE_LIMIT) as usize;
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    // Only keys within the page are read
     POOLS
         .keys_raw(
             deps.storage,
@@ -127,6 +490,175 @@ fn list_pools(
         .collect()
 }
 
+fn query_inconsistent_pools(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u8>,
+) -> StdResult<InconsistentPoolsResponse> {
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    let active_pools = ACTIVE_POOLS.load(deps.storage)?;
+    let start = match start_after {
+        Some(lp_token) => {
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            active_pools
+                .iter()
+                .position(|(lp_asset_, _)| lp_asset_ == &lp_asset)
+                .map_or(active_pools.len(), |ind| ind + 1)
+        }
+        None => 0,
+    };
+    let scanned = active_pools
+        .into_iter()
+        .skip(start)
+        .take(limit)
+        .collect_vec();
+
+    let last_scanned = scanned.last().map(|(lp_asset, _)| lp_asset.to_string());
+
+    let pools = scanned
+        .into_iter()
+        .map(|(lp_asset, _)| {
+            let pair_info = query_pair_info(deps, &lp_asset)?;
+            Ok(InconsistentPool {
+                lp_token: lp_asset.to_string(),
+                blocked_tokens: find_blocked_tokens(deps.storage, &lp_asset, &pair_info),
+            })
+        })
+        .filter(|pool| {
+            pool.as_ref()
+                .map(|pool| !pool.blocked_tokens.is_empty())
+                .unwrap_or(true)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(InconsistentPoolsResponse {
+        pools,
+        last_scanned,
+    })
+}
+
+fn query_stale_pools(
+    deps: Deps,
+    env: Env,
+    older_than_secs: u64,
+    start_after: Option<String>,
+    limit: Option<u8>,
+) -> StdResult<StalePoolsResponse> {
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    let block_ts = env.block.time.seconds();
+    let scanned = POOLS
+        .range_raw(
+            deps.storage,
+            start_after
+                .map(|lp_token| determine_asset_info(&lp_token, deps.api))
+                .transpose()?
+                .as_ref()
+                .map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let last_scanned = scanned
+        .last()
+        .map(|(key, _)| String::from_utf8(key.clone()).map_err(StdError::invalid_utf8))
+        .transpose()?;
+
+    let pools = scanned
+        .into_iter()
+        .filter(|(_, pool_info)| {
+            block_ts.saturating_sub(pool_info.last_update_ts) > older_than_secs
+        })
+        .map(|(key, pool_info)| {
+            Ok(StalePool {
+                lp_token: String::from_utf8(key).map_err(StdError::invalid_utf8)?,
+                last_update_ts: pool_info.last_update_ts,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(StalePoolsResponse {
+        pools,
+        last_scanned,
+    })
+}
+
+fn query_zero_staker_pools(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u8>,
+) -> StdResult<ZeroStakerPoolsResponse> {
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    let scanned = POOLS
+        .range_raw(
+            deps.storage,
+            start_after
+                .map(|lp_token| determine_asset_info(&lp_token, deps.api))
+                .transpose()?
+                .as_ref()
+                .map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let last_scanned = scanned
+        .last()
+        .map(|(key, _)| String::from_utf8(key.clone()).map_err(StdError::invalid_utf8))
+        .transpose()?;
+
+    let pools = scanned
+        .into_iter()
+        .filter(|(_, pool_info)| pool_info.total_lp.is_zero())
+        .map(|(key, mut pool_info)| {
+            let lp_token = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+            // Move to the current schedules to skip finished rewards
+            pool_info.update_rewards(deps.storage, &env, &lp_asset)?;
+            let rewards = pool_info
+                .rewards
+                .iter()
+                .filter(|reward_info| !reward_info.rps.is_zero())
+                .map(|reward_info| reward_info.reward.asset_info().clone())
+                .collect_vec();
+
+            Ok(ZeroStakerPool { lp_token, rewards })
+        })
+        .filter(|pool| {
+            pool.as_ref()
+                .map(|pool| !pool.rewards.is_empty())
+                .unwrap_or(true)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(ZeroStakerPoolsResponse {
+        pools,
+        last_scanned,
+    })
+}
+
+fn query_all_reward_tokens(
+    deps: Deps,
+    start_after: Option<AssetInfo>,
+    limit: Option<u8>,
+) -> StdResult<Vec<AssetInfo>> {
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    let start_key = start_after.as_ref().map(asset_info_key);
+    REWARD_TOKEN_POOLS_COUNT
+        .keys(
+            deps.storage,
+            start_key.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|key| from_key_to_asset_info(key?))
+        .collect()
+}
+
 fn query_blocked_tokens(
     deps: Deps,
     start_after: Option<AssetInfo>,
@@ -149,6 +681,25 @@ fn query_blocked_tokens(
     .collect()
 }
 
+fn query_frozen_rewards(
+    deps: Deps,
+    start_after: Option<AssetInfo>,
+    limit: Option<u8>,
+) -> StdResult<Vec<AssetInfo>> {
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    let start_key = start_after.as_ref().map(asset_info_key);
+    FROZEN_REWARDS
+        .keys(
+            deps.storage,
+            start_key.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|key| from_key_to_asset_info(key?))
+        .collect()
+}
+
 pub fn query_pending_rewards(
     deps: Deps,
     env: Env,
@@ -188,6 +739,194 @@ pub fn query_pending_rewards(
     Ok(aggregated)
 }
 
+/// Replays reward calculation of claim_rewards without persisting anything
+/// and counts messages the claim would produce.
+pub fn query_claim_complexity(
+    deps: Deps,
+    env: Env,
+    lp_tokens: Vec<String>,
+    user: String,
+) -> Result<ClaimComplexityResponse, ContractError> {
+    ensure!(
+        lp_tokens.iter().all_unique(),
+        ContractError::DuplicatedPoolFound {}
+    );
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut external_rewards = vec![];
+    let mut protocol_reward_amount = Uint128::zero();
+    for lp_token in lp_tokens {
+        let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+        let mut pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
+        pool_info.update_rewards(deps.storage, &env, &lp_asset)?;
+        let mut pos = UserInfo::load_position(deps.storage, &user, &lp_asset)?;
+
+        external_rewards.extend(
+            pos.claim_finished_rewards(deps.storage, &lp_asset, &pool_info)?
+                .into_iter()
+                .filter(|asset| !asset.amount.is_zero()),
+        );
+        pos.reset_user_index(deps.storage, &lp_asset, &pool_info)?;
+
+        for (is_external, reward_asset) in pool_info.calculate_rewards(&mut pos)? {
+            if reward_asset.amount.is_zero() {
+                continue;
+            }
+            if is_external {
+                external_rewards.push(reward_asset);
+            } else {
+                protocol_reward_amount += reward_asset.amount;
+            }
+        }
+    }
+
+    // claim_rewards aggregates only adjacent rewards with the same asset info
+    // Frozen rewards are held instead of being transferred
+    let mut submessages = external_rewards
+        .iter()
+        .filter(|asset| !FROZEN_REWARDS.has(deps.storage, &asset_info_key(&asset.info)))
+        .chunk_by(|asset| &asset.info)
+        .into_iter()
+        .count() as u32;
+    let mut reward_tokens = external_rewards
+        .iter()
+        .map(|asset| &asset.info)
+        .collect::<HashSet<_>>();
+    if !protocol_reward_amount.is_zero() {
+        // PADEX is minted with a single message
+        submessages += 1;
+        reward_tokens.insert(&config.padex_token);
+    }
+
+    Ok(ClaimComplexityResponse {
+        submessages,
+        reward_tokens: reward_tokens.len() as u32,
+    })
+}
+
+/// Returns queued schedules which start after the current one ends.
+/// Their number is bounded by max_periods.
+pub fn query_pending_schedules(
+    deps: Deps,
+    env: Env,
+    reward: String,
+    lp_token: String,
+) -> Result<Vec<ScheduleResponse>, ContractError> {
+    let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+    let reward_asset = determine_asset_info(&reward, deps.api)?;
+    let mut pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
+    pool_info.update_rewards(deps.storage, &env, &lp_asset)?;
+
+    let mut start_ts = pool_info
+        .rewards
+        .iter()
+        .find_map(|active| match &active.reward {
+            RewardType::Ext {
+                info,
+                next_update_ts,
+            } if info == &reward_asset => Some(*next_update_ts),
+            _ => None,
+        })
+        .ok_or(ContractError::RewardNotFound {
+            pool: lp_token,
+            reward,
+        })?;
+
+    EXTERNAL_REWARD_SCHEDULES
+        .prefix((&lp_asset, &reward_asset))
+        .range(
+            deps.storage,
+            Some(Bound::exclusive(start_ts)),
+            None,
+            Order::Ascending,
+        )
+        .map(|item| {
+            let (end_ts, rps) = item?;
+            let resp = ScheduleResponse::new(rps, start_ts, end_ts)?;
+            start_ts = end_ts;
+            Ok(resp)
+        })
+        .collect()
+}
+
+/// Schedule breakpoints stay in state after they pass, thus they form the reward history.
+/// Periods which were active are taken from [`REWARD_ACTIVE_PERIODS`] and pool info.
+pub fn query_reward_timeline(
+    deps: Deps,
+    env: Env,
+    lp_token: String,
+    reward: String,
+    start_after: Option<u64>,
+    limit: Option<u8>,
+) -> Result<Vec<RewardTimelineItem>, ContractError> {
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+    let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+    let reward_asset = determine_asset_info(&reward, deps.api)?;
+    let block_ts = env.block.time.seconds();
+    let mut pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
+    pool_info.update_rewards(deps.storage, &env, &lp_asset)?;
+
+    let active = pool_info
+        .rewards
+        .iter()
+        .find_map(|reward_info| match &reward_info.reward {
+            RewardType::Ext {
+                info,
+                next_update_ts,
+            } if info == &reward_asset => Some((*next_update_ts, reward_info.rps)),
+            _ => None,
+        })
+        .filter(|(end_ts, _)| match start_after {
+            Some(start_after) => *end_ts > start_after,
+            None => true,
+        });
+
+    let load_page = |map: Map<(&AssetInfo, &AssetInfo, u64), Decimal256>| {
+        map.prefix((&lp_asset, &reward_asset))
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()
+    };
+    // Passed schedule breakpoints stay in state. Periods which were active override them
+    // as the rps of the active period is changed only in pool info
+    let mut periods: BTreeMap<_, _> = load_page(EXTERNAL_REWARD_SCHEDULES)?
+        .into_iter()
+        .chain(load_page(REWARD_ACTIVE_PERIODS)?)
+        .collect();
+    if let Some((active_end_ts, active_rps)) = active {
+        periods.insert(active_end_ts, active_rps);
+    }
+    // Both pages cover all periods up to the limit-th one thus the merged page is complete
+    let periods = periods.into_iter().take(limit).collect_vec();
+
+    let mut start_ts = start_after;
+    Ok(periods
+        .into_iter()
+        .map(|(end_ts, rps)| {
+            let status = if end_ts <= block_ts {
+                ScheduleStatus::Finished
+            } else if active.is_some_and(|(active_end_ts, _)| active_end_ts == end_ts) {
+                ScheduleStatus::Active
+            } else {
+                ScheduleStatus::Pending
+            };
+            let item = RewardTimelineItem {
+                start_ts,
+                end_ts,
+                rps,
+                status,
+            };
+            start_ts = Some(end_ts);
+            item
+        })
+        .collect())
+}
+
 pub fn query_external_reward_schedules(
     deps: Deps,
     env: Env,
@@ -223,11 +962,11 @@ pub fn query_external_reward_schedules(
     let mut results = vec![];
 
     if start_after < end_ts {
-        results.push(ScheduleResponse {
+        results.push(ScheduleResponse::new(
             rps,
-            start_ts: env.block.time.seconds(),
+            env.block.time.seconds(),
             end_ts,
-        });
+        )?);
         limit -= 1;
         start_after = end_ts
     }
@@ -243,17 +982,999 @@ pub fn query_external_reward_schedules(
         .collect::<StdResult<Vec<_>>>()?
         .into_iter()
         .map(|(next_update_ts, rps)| {
-            let resp = ScheduleResponse {
-                rps,
-                start_ts: start_after,
-                end_ts: next_update_ts,
-            };
+            let resp = ScheduleResponse::new(rps, start_after, next_update_ts);
             start_after = next_update_ts;
 
             resp
-        });
+        })
+        .collect::<StdResult<Vec<_>>>()?;
 
     results.extend(from_state);
 
     Ok(results)
 }
+
+/// Returns rewards of the pool updated to the current block.
+fn query_reward_info(
+    deps: Deps,
+    env: &Env,
+    lp_token: String,
+    with_metadata: bool,
+) -> Result<Vec<RewardInfoResponse>, ContractError> {
+    let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+    let mut pool_info = PoolInfo::load_existing(deps.storage, &lp_asset)?;
+    pool_info.update_rewards(deps.storage, env, &lp_asset)?;
+    pool_info
+        .rewards
+        .into_iter()
+        .map(|reward_info| {
+            let metadata = with_metadata
+                .then(|| query_token_metadata(&deps.querier, reward_info.reward.asset_info()))
+                .transpose()?;
+            Ok(RewardInfoResponse {
+                reward: reward_info.reward,
+                rps: reward_info.rps,
+                index: reward_info.index,
+                orphaned: reward_info.orphaned,
+                metadata,
+            })
+        })
+        .collect()
+}
+
+/// Replays [`crate::utils::incentivize`] checks and fee deduction against the current state.
+pub fn simulate_incentivize(
+    deps: Deps,
+    env: Env,
+    lp_token: String,
+    input: InputSchedule,
+    sent_funds: Vec<Coin>,
+    sender: Option<String>,
+) -> Result<SimulateIncentivizeResponse, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure!(!config.paused, ContractError::ContractPaused {});
+    let sender = sender
+        .map(|sender| deps.api.addr_validate(&sender))
+        .transpose()?;
+    let lp_token = LpToken::new(&lp_token, deps.api)?;
+    let schedule = validate_schedule(deps, &env, &config, &lp_token, &input)?;
+    let lp_asset = lp_token.asset_info();
+
+    let mut pool_info = PoolInfo::may_load(deps.storage, lp_asset)?.unwrap_or_default();
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+    if pool_info.total_lp.is_zero() {
+        let refund_address = input
+            .refund_address
+            .as_deref()
+            .map(|addr| deps.api.addr_validate(addr))
+            .transpose()?;
+        ensure_orphaned_capacity(
+            deps.storage,
+            is_new_orphaned_entry(deps.storage, &schedule.reward_info, refund_address.as_ref()),
+        )?;
+    }
+    let ext_rewards = pool_info
+        .rewards
+        .iter()
+        .filter(|reward_info| reward_info.reward.is_external())
+        .collect_vec();
+    let is_new_reward = !ext_rewards
+        .iter()
+        .any(|reward_info| reward_info.reward.asset_info() == &schedule.reward_info);
+    let max_reward_tokens = pool_info.max_reward_tokens();
+    ensure!(
+        !is_new_reward || ext_rewards.len() < max_reward_tokens as usize,
+        ContractError::TooManyRewardTokens {
+            lp_token: lp_asset.to_string(),
+            max_reward_tokens,
+        }
+    );
+
+    let mut funds = sent_funds;
+    let fee = deduct_incentivization_fee(
+        &config,
+        sender.as_ref(),
+        &mut funds,
+        &lp_token,
+        &schedule.reward_info,
+        is_new_reward,
+    )?
+    .map(|fee_info| fee_info.fee.clone());
+    if schedule.reward_info.is_native_token() {
+        funds.assert_coins_properly_sent(
+            std::slice::from_ref(&input.reward),
+            std::slice::from_ref(&schedule.reward_info),
+        )?;
+    }
+
+    Ok(SimulateIncentivizeResponse {
+        fee,
+        reward: input.reward,
+        new_reward: is_new_reward,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{message_info, mock_env};
+    use cosmwasm_std::{
+        coin, coins, from_json, to_json_binary, Addr, ContractResult, Decimal256, Deps, DepsMut,
+        Env, Order, SystemError, SystemResult, Timestamp, WasmQuery,
+    };
+
+    use super::*;
+    use crate::asset::PairInfo;
+    use crate::constants::EPOCH_LENGTH;
+    use crate::execute::execute;
+    use crate::msg::{ExecuteMsg, FactoryQueryMsg};
+    use crate::state::rebuild_reward_tokens_index;
+    use crate::testing::{block_token, setup};
+    use crate::types::{IncentivizationFeeInfo, PairType};
+
+    #[test]
+    fn zero_staker_pools_lists_pools_accruing_orphaned_rewards() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let zero_staker_pools = |deps: Deps| -> ZeroStakerPoolsResponse {
+            from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::ZeroStakerPools {
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        let resp = zero_staker_pools(deps.as_ref());
+        assert_eq!(
+            resp.pools,
+            vec![ZeroStakerPool {
+                lp_token: lp_asset.to_string(),
+                rewards: vec![AssetInfo::native("factory/incentives/padex")],
+            }]
+        );
+        assert_eq!(resp.last_scanned, Some(lp_asset.to_string()));
+
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(100, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        assert!(zero_staker_pools(deps.as_ref()).pools.is_empty());
+    }
+
+    #[test]
+    fn pool_stakers_pagination_is_stable() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let mut users = (0..7)
+            .map(|i| deps.api.addr_make(&format!("user{i}")).to_string())
+            .collect_vec();
+        for user in &users {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&Addr::unchecked(user), &coins(100, lp_asset.to_string())),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+            .unwrap();
+        }
+        users.sort();
+
+        let mut paginated = vec![];
+        let mut start_after = None;
+        loop {
+            let page: Vec<(String, Uint128)> = from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::PoolStakers {
+                        lp_token: lp_asset.to_string(),
+                        start_after: start_after.clone(),
+                        limit: Some(3),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            assert!(page.len() <= 3);
+            let Some((last, _)) = page.last() else {
+                break;
+            };
+            start_after = Some(last.clone());
+            paginated.extend(page.into_iter().map(|(user, _)| user));
+        }
+
+        assert_eq!(paginated, users);
+    }
+
+    #[test]
+    fn user_reward_state_matches_pending_rewards() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1000, lp_asset.to_string())),
+            ExecuteMsg::Deposit { recipient: None },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(1000);
+
+        let state: UserRewardStateResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::UserRewardState {
+                    lp_token: lp_asset.to_string(),
+                    user: alice.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let pending: Vec<PendingRewardResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::PendingRewards {
+                    lp_token: lp_asset.to_string(),
+                    user: alice.to_string(),
+                    with_metadata: false,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(state.amount, Uint128::new(1000));
+        let [padex] = state.rewards.as_slice() else {
+            panic!("Expected single PADEX reward");
+        };
+        let user_index = padex.user_index.unwrap();
+        let expected = ((padex.pool_index - user_index)
+            * Decimal256::from_ratio(state.amount, 1u8))
+        .to_uint_floor();
+        assert_eq!(pending[0].amount, Uint128::try_from(expected).unwrap());
+    }
+
+    #[test]
+    fn all_reward_tokens_are_deduplicated_across_pools() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let incentivizer = deps.api.addr_make("incentivizer");
+        for reward in ["ureward2", "ureward1"] {
+            let reward = AssetInfo::native(reward).with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods: 1,
+                        refund_address: None,
+                    },
+                },
+            )
+            .unwrap();
+        }
+        // Second pool with the same set of rewards
+        let pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info
+            .save(
+                deps.as_mut().storage,
+                &AssetInfo::native("factory/pair2/lp"),
+            )
+            .unwrap();
+
+        let query_env = env.clone();
+        let all_reward_tokens = |deps: Deps| {
+            let mut paginated = vec![];
+            let mut start_after = None;
+            loop {
+                let page: Vec<AssetInfo> = from_json(
+                    query(
+                        deps,
+                        query_env.clone(),
+                        QueryMsg::AllRewardTokens {
+                            start_after: start_after.clone(),
+                            limit: Some(2),
+                        },
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+                let Some(last) = page.last() else {
+                    break;
+                };
+                start_after = Some(last.clone());
+                paginated.extend(page);
+            }
+            paginated
+        };
+
+        assert_eq!(
+            all_reward_tokens(deps.as_ref()),
+            vec![
+                AssetInfo::native("factory/incentives/padex"),
+                AssetInfo::native("ureward1"),
+                AssetInfo::native("ureward2"),
+            ]
+        );
+        let padex_key = asset_info_key(&AssetInfo::native("factory/incentives/padex"));
+        let reward_key = asset_info_key(&AssetInfo::native("ureward1"));
+        let counts = |deps: Deps| {
+            REWARD_TOKEN_POOLS_COUNT
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap()
+        };
+        let before_rebuild = counts(deps.as_ref());
+        assert_eq!(before_rebuild[0], (padex_key.clone(), 2));
+        assert_eq!(before_rebuild[1], (reward_key, 2));
+        rebuild_reward_tokens_index(&mut deps.storage, &deps.api).unwrap();
+        assert_eq!(counts(deps.as_ref()), before_rebuild);
+
+        // Finished rewards leave the index once no pool has them
+        env.block.time = env.block.time.plus_seconds(2 * EPOCH_LENGTH);
+        for (lp, remaining) in [
+            (lp_asset.clone(), 3),
+            (AssetInfo::native("factory/pair2/lp"), 1),
+        ] {
+            let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp).unwrap();
+            pool_info
+                .update_rewards(deps.as_mut().storage, &env, &lp)
+                .unwrap();
+            pool_info.save(deps.as_mut().storage, &lp).unwrap();
+            assert_eq!(all_reward_tokens(deps.as_ref()).len(), remaining);
+        }
+        assert_eq!(counts(deps.as_ref()), vec![(padex_key, 2)]);
+    }
+
+    #[test]
+    fn pool_lifecycle_keeps_first_incentivized_ts() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let activated_at = env.block.time.seconds();
+        let lifecycle = |deps: Deps, env: Env| -> PoolLifecycleResponse {
+            from_json(
+                query(
+                    deps,
+                    env,
+                    QueryMsg::PoolLifecycle {
+                        lp_token: lp_asset.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            lifecycle(deps.as_ref(), env.clone()),
+            PoolLifecycleResponse {
+                first_incentivized_ts: Some(activated_at),
+                last_update_ts: activated_at,
+                is_active: true,
+            }
+        );
+
+        env.block.time = env.block.time.plus_seconds(1000);
+        let incentivizer = deps.api.addr_make("incentivizer");
+        let reward = AssetInfo::native("ureward").with_balance(Uint128::new(10_000_000_000));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&incentivizer, &[reward.as_coin().unwrap()]),
+            ExecuteMsg::Incentivize {
+                lp_token: lp_asset.to_string(),
+                schedule: InputSchedule {
+                    reward,
+                    duration_periods: 1,
+                    refund_address: None,
+                },
+            },
+        )
+        .unwrap();
+
+        let response = lifecycle(deps.as_ref(), env.clone());
+        assert_eq!(response.first_incentivized_ts, Some(activated_at));
+        assert_eq!(response.last_update_ts, env.block.time.seconds());
+    }
+
+    #[test]
+    fn padex_emission_window_starts_on_activation() {
+        let (mut deps, mut env, owner, lp_asset) = setup();
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            contract_addr: deps.api.addr_make("pair"),
+            liquidity_token: Addr::unchecked(lp_asset.to_string()),
+            pair_type: PairType::Xyk {},
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let response = match from_json(msg) {
+                    Ok(FactoryQueryMsg::BlacklistedPairTypes {}) => {
+                        to_json_binary(&Vec::<PairType>::new())
+                    }
+                    _ => to_json_binary(&pair_info),
+                };
+                SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        let window = |deps: Deps, lp_token: &str| -> PadexEmissionWindowResponse {
+            from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::PadexEmissionWindow {
+                        lp_token: lp_token.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        let setup_pools = |deps: DepsMut, env: Env, alloc_points: u128| {
+            execute(
+                deps,
+                env,
+                message_info(&owner, &[]),
+                ExecuteMsg::SetupPools {
+                    pools: vec![(lp_asset.to_string(), Uint128::new(alloc_points))],
+                },
+            )
+            .unwrap();
+        };
+        let activated_at = env.block.time.seconds();
+        assert_eq!(
+            window(deps.as_ref(), &lp_asset.to_string()),
+            PadexEmissionWindowResponse {
+                start: Some(activated_at),
+                end: None,
+            }
+        );
+
+        // Changing allocation of an active pool keeps the activation time
+        env.block.time = env.block.time.plus_seconds(1000);
+        setup_pools(deps.as_mut(), env.clone(), 2);
+        assert_eq!(
+            window(deps.as_ref(), &lp_asset.to_string()).start,
+            Some(activated_at)
+        );
+
+        env.block.time = env.block.time.plus_seconds(1000);
+        block_token(
+            deps.as_mut(),
+            env.clone(),
+            &owner,
+            AssetInfo::native("uatom"),
+        )
+        .unwrap();
+        assert_eq!(window(deps.as_ref(), &lp_asset.to_string()).start, None);
+
+        // Reactivated pool reports the new activation time
+        env.block.time = env.block.time.plus_seconds(1000);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::UpdateBlockedTokenslist {
+                add: vec![],
+                remove: vec![AssetInfo::native("uatom")],
+            },
+        )
+        .unwrap();
+        setup_pools(deps.as_mut(), env.clone(), 1);
+        assert_eq!(
+            window(deps.as_ref(), &lp_asset.to_string()).start,
+            Some(env.block.time.seconds())
+        );
+
+        assert_eq!(window(deps.as_ref(), "factory/unknown/lp").start, None);
+    }
+
+    #[test]
+    fn version_query_returns_stored_cw2_version() {
+        let (mut deps, env, _, _) = setup();
+        cw2::set_contract_version(deps.as_mut().storage, "palomadex-incentives", "1.0.1").unwrap();
+
+        let version: cw2::ContractVersion =
+            from_json(query(deps.as_ref(), env, QueryMsg::Version {}).unwrap()).unwrap();
+        assert_eq!(
+            version,
+            cw2::ContractVersion {
+                contract: "palomadex-incentives".to_string(),
+                version: "1.0.1".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn top_pools_are_sorted_by_emissions() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let pools = vec![
+            (AssetInfo::native("factory/pair2/lp"), Uint128::new(3)),
+            (lp_asset.clone(), Uint128::new(1)),
+            (AssetInfo::native("factory/pair3/lp"), Uint128::new(6)),
+        ];
+        ACTIVE_POOLS.save(deps.as_mut().storage, &pools).unwrap();
+        CONFIG
+            .update::<_, StdError>(deps.as_mut().storage, |mut config| {
+                config.total_alloc_points = Uint128::new(10);
+                Ok(config)
+            })
+            .unwrap();
+
+        let top_pools = |limit: Option<u8>| -> Vec<TopPoolResponse> {
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::TopPools { limit }).unwrap())
+                .unwrap()
+        };
+        let top = top_pools(Some(2));
+        assert_eq!(
+            top,
+            vec![
+                TopPoolResponse {
+                    lp_token: "factory/pair3/lp".to_string(),
+                    alloc_points: Uint128::new(6),
+                    padex_per_second: Decimal256::from_ratio(6u8, 1u8),
+                },
+                TopPoolResponse {
+                    lp_token: "factory/pair2/lp".to_string(),
+                    alloc_points: Uint128::new(3),
+                    padex_per_second: Decimal256::from_ratio(3u8, 1u8),
+                },
+            ]
+        );
+        assert_eq!(top_pools(None).len(), 3);
+        assert_eq!(top_pools(None)[2].lp_token, lp_asset.to_string());
+    }
+
+    #[test]
+    fn pending_schedules_exclude_active_one() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward");
+        let incentivizer = deps.api.addr_make("incentivizer");
+        for duration_periods in [1, 3] {
+            let schedule = reward.with_balance(Uint128::new(10_000_000_000));
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&incentivizer, &[schedule.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward: schedule,
+                        duration_periods,
+                        refund_address: None,
+                    },
+                },
+            )
+            .unwrap();
+        }
+
+        let all: Vec<ScheduleResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::ExternalRewardSchedules {
+                    reward: reward.to_string(),
+                    lp_token: lp_asset.to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let pending: Vec<ScheduleResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::PendingSchedules {
+                    lp_token: lp_asset.to_string(),
+                    reward: reward.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        // The longer schedule is queued after the overlapping part ends
+        assert!(!pending.is_empty());
+        assert_eq!(pending, all[1..]);
+        assert!(pending
+            .iter()
+            .all(|schedule| schedule.start_ts > env.block.time.seconds()));
+
+        let err = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::PendingSchedules {
+                lp_token: lp_asset.to_string(),
+                reward: "uother".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RewardNotFound {
+                pool: lp_asset.to_string(),
+                reward: "uother".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn config_extended_includes_derived_fields() {
+        let (mut deps, env, _, _) = setup();
+        let query_extended = |deps: Deps| -> ConfigExtendedResponse {
+            from_json(query(deps, env.clone(), QueryMsg::ConfigExtended {}).unwrap()).unwrap()
+        };
+
+        let res = query_extended(deps.as_ref());
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(res.padex_denom, config.padex_token.to_string());
+        assert_eq!(res.active_pools, 1);
+        assert!(!res.incentivization_fee_required);
+        assert_eq!(res.config, config);
+
+        let mut config = config;
+        config.incentivization_fee_info = Some(IncentivizationFeeInfo {
+            fee_receiver: deps.api.addr_make("fee_receiver"),
+            fee: coin(1000, "upaloma"),
+        });
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        ACTIVE_POOLS.save(deps.as_mut().storage, &vec![]).unwrap();
+
+        let res = query_extended(deps.as_ref());
+        assert_eq!(res.active_pools, 0);
+        assert!(res.incentivization_fee_required);
+    }
+
+    #[test]
+    fn reward_info_batch_matches_single_queries() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let reward = AssetInfo::native("ureward");
+        let schedule = IncentivesSchedule::from_input(
+            &env,
+            &InputSchedule {
+                reward: reward.with_balance(Uint128::new(10_000_000_000)),
+                duration_periods: 1,
+                refund_address: None,
+            },
+            25,
+        )
+        .unwrap();
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info
+            .incentivize(deps.as_mut().storage, &lp_asset, &schedule)
+            .unwrap();
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+
+        let single: Vec<RewardInfoResponse> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::RewardInfo {
+                    lp_token: lp_asset.to_string(),
+                    with_metadata: false,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let batch: Vec<(String, Vec<RewardInfoResponse>)> = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::RewardInfoBatch {
+                    lp_tokens: vec![lp_asset.to_string()],
+                    with_metadata: false,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(batch, vec![(lp_asset.to_string(), single)]);
+
+        let err = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::RewardInfoBatch {
+                lp_tokens: vec![lp_asset.to_string(); MAX_PAGE_LIMIT as usize + 1],
+                with_metadata: false,
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("must not exceed"));
+    }
+
+    #[test]
+    fn list_pools_pages_are_bounded() {
+        let (mut deps, env, _, lp_asset) = setup();
+        for i in 0..300 {
+            PoolInfo::default()
+                .save(
+                    deps.as_mut().storage,
+                    &AssetInfo::native(format!("factory/pair{i:03}/lp")),
+                )
+                .unwrap();
+        }
+
+        let list_pools = |start_after: Option<String>, limit: Option<u8>| -> Vec<String> {
+            from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::ListPools { start_after, limit },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            list_pools(None, Some(u8::MAX)).len(),
+            MAX_PAGE_LIMIT as usize
+        );
+
+        let mut all_pools = vec![];
+        let mut start_after = None;
+        loop {
+            let page = list_pools(start_after, Some(u8::MAX));
+            assert!(page.len() <= MAX_PAGE_LIMIT as usize);
+            if page.is_empty() {
+                break;
+            }
+            start_after = page.last().cloned();
+            all_pools.extend(page);
+        }
+        assert_eq!(all_pools.len(), 301);
+        assert!(all_pools.iter().all_unique());
+        assert!(all_pools.contains(&lp_asset.to_string()));
+    }
+
+    #[test]
+    fn stale_pools_are_reported() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let fresh_lp = AssetInfo::native("factory/fresh_pair/lp");
+        let setup_ts = env.block.time.seconds();
+        env.block.time = env.block.time.plus_seconds(1000);
+        PoolInfo {
+            last_update_ts: env.block.time.seconds(),
+            ..Default::default()
+        }
+        .save(deps.as_mut().storage, &fresh_lp)
+        .unwrap();
+
+        let stale_pools = |env: Env, older_than_secs: u64| -> StalePoolsResponse {
+            from_json(
+                query(
+                    deps.as_ref(),
+                    env,
+                    QueryMsg::StalePools {
+                        older_than_secs,
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        let res = stale_pools(env.clone(), 500);
+        assert_eq!(
+            res.pools,
+            vec![StalePool {
+                lp_token: lp_asset.to_string(),
+                last_update_ts: setup_ts,
+            }]
+        );
+        assert!(res.last_scanned.is_some());
+        assert!(stale_pools(env.clone(), 1000).pools.is_empty());
+    }
+
+    #[test]
+    fn reward_timeline_includes_finished_schedules() {
+        let (mut deps, mut env, _, lp_asset) = setup();
+        let funder = deps.api.addr_make("funder");
+        let incentivize = |deps: DepsMut, env: Env, amount: u128, duration_periods: u64| {
+            let reward = AssetInfo::native("ureward").with_balance(Uint128::new(amount));
+            execute(
+                deps,
+                env,
+                message_info(&funder, &[reward.as_coin().unwrap()]),
+                ExecuteMsg::Incentivize {
+                    lp_token: lp_asset.to_string(),
+                    schedule: InputSchedule {
+                        reward,
+                        duration_periods,
+                        refund_address: None,
+                    },
+                },
+            )
+            .unwrap()
+        };
+        incentivize(deps.as_mut(), env.clone(), 10_000_000_000, 1);
+        incentivize(deps.as_mut(), env.clone(), 30_000_000_000, 3);
+        // Accrual state keeps only the pending breakpoint. The active period lives in pool info
+        let reward_asset = AssetInfo::native("ureward");
+        let schedule_ends = EXTERNAL_REWARD_SCHEDULES
+            .prefix((&lp_asset, &reward_asset))
+            .keys(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+
+        let timeline = |deps: Deps, env: Env, start_after: Option<u64>, limit: Option<u8>| {
+            from_json::<Vec<RewardTimelineItem>>(
+                query(
+                    deps,
+                    env,
+                    QueryMsg::RewardTimeline {
+                        lp_token: lp_asset.to_string(),
+                        reward: "ureward".to_string(),
+                        start_after,
+                        limit,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        let before = timeline(deps.as_ref(), env.clone(), None, None);
+        assert_eq!(
+            before.iter().map(|item| item.status).collect_vec(),
+            vec![ScheduleStatus::Active, ScheduleStatus::Pending]
+        );
+        assert_eq!(before[0].start_ts, None);
+        assert_eq!(before[1].start_ts, Some(before[0].end_ts));
+        assert_eq!(schedule_ends, vec![before[1].end_ts]);
+
+        // The first period is over
+        env.block.time = Timestamp::from_seconds(before[0].end_ts + 1);
+        let after = timeline(deps.as_ref(), env.clone(), None, None);
+        assert_eq!(
+            after.iter().map(|item| item.status).collect_vec(),
+            vec![ScheduleStatus::Finished, ScheduleStatus::Active]
+        );
+        assert_eq!(after[0].rps, before[0].rps);
+        assert_eq!(after[1].rps, before[1].rps);
+
+        // Pagination
+        let first_page = timeline(deps.as_ref(), env.clone(), None, Some(1));
+        assert_eq!(first_page, after[..1]);
+        let second_page = timeline(
+            deps.as_ref(),
+            env.clone(),
+            Some(first_page[0].end_ts),
+            Some(1),
+        );
+        assert_eq!(second_page, after[1..]);
+
+        // History is kept after the reward is finished
+        env.block.time = Timestamp::from_seconds(after[1].end_ts + 1);
+        let mut pool_info = PoolInfo::load(deps.as_ref().storage, &lp_asset).unwrap();
+        pool_info
+            .update_rewards(deps.as_ref().storage, &env, &lp_asset)
+            .unwrap();
+        pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+        let finished = timeline(deps.as_ref(), env, None, None);
+        assert_eq!(finished.len(), 2);
+        assert!(finished
+            .iter()
+            .all(|item| item.status == ScheduleStatus::Finished));
+    }
+
+    #[test]
+    fn user_reward_rate_is_share_of_pool_rps() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        for (user, amount) in [(&alice, 100), (&bob, 300)] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(user, &coins(amount, lp_asset.to_string())),
+                ExecuteMsg::Deposit { recipient: None },
+            )
+            .unwrap();
+        }
+
+        let rates = |user: &Addr| -> StdResult<Vec<UserRewardRate>> {
+            from_json(query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::UserRewardRate {
+                    lp_token: lp_asset.to_string(),
+                    user: user.to_string(),
+                },
+            )?)
+        };
+        let alice_rates = rates(&alice).unwrap();
+        assert_eq!(alice_rates.len(), 1);
+        assert!(!alice_rates[0].reward.is_external());
+        assert_eq!(alice_rates[0].rps, Decimal256::from_ratio(5u8, 2u8));
+        assert_eq!(
+            rates(&bob).unwrap()[0].rps,
+            Decimal256::from_ratio(15u8, 2u8)
+        );
+
+        rates(&deps.api.addr_make("carol")).unwrap_err();
+    }
+
+    #[test]
+    fn inconsistent_pools_reports_active_pools_with_blocked_tokens() {
+        let (mut deps, env, _, lp_asset) = setup();
+        let pair_info = PairInfo {
+            asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+            contract_addr: deps.api.addr_make("pair"),
+            liquidity_token: Addr::unchecked(lp_asset.to_string()),
+            pair_type: PairType::Xyk {},
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let response = match from_json(msg) {
+                    Ok(FactoryQueryMsg::BlacklistedPairTypes {}) => {
+                        to_json_binary(&Vec::<PairType>::new())
+                    }
+                    _ => to_json_binary(&pair_info),
+                };
+                SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+        let inconsistent_pools = |deps: Deps| -> InconsistentPoolsResponse {
+            from_json(
+                query(
+                    deps,
+                    env.clone(),
+                    QueryMsg::InconsistentPools {
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        assert!(inconsistent_pools(deps.as_ref()).pools.is_empty());
+
+        // Token blocked bypassing update_blocked_tokens_list
+        BLOCKED_TOKENS
+            .save(
+                deps.as_mut().storage,
+                &asset_info_key(&AssetInfo::native("uatom")),
+                &(),
+            )
+            .unwrap();
+        let res = inconsistent_pools(deps.as_ref());
+        assert_eq!(
+            res.pools,
+            vec![InconsistentPool {
+                lp_token: lp_asset.to_string(),
+                blocked_tokens: vec![AssetInfo::native("uatom")],
+            }]
+        );
+        assert_eq!(res.last_scanned, Some(lp_asset.to_string()));
+
+        let keeper = deps.api.addr_make("keeper");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &[]),
+            ExecuteMsg::DeactivateBlockedPools {},
+        )
+        .unwrap();
+        let res = inconsistent_pools(deps.as_ref());
+        assert!(res.pools.is_empty());
+        assert_eq!(res.last_scanned, None);
+    }
+}
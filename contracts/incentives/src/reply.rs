@@ -1,14 +1,23 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{DepsMut, Env, Reply, Response, SubMsgResult};
+use cosmwasm_std::{attr, from_json, CosmosMsg, DepsMut, Env, Reply, Response, SubMsgResult};
 
 use crate::error::ContractError;
+use crate::state::{Op, PoolInfo, UserInfo};
+use crate::types::{
+    IncentivizationFeeInfo, PadexBridgeInfo, PalomaMsg, SendTx, WithdrawTransferInfo,
+};
+use crate::utils::sync_user_weight;
 
 pub const POST_TRANSFER_REPLY_ID: u64 = 1;
+pub const FEE_TRANSFER_REPLY_ID: u64 = 2;
+pub const PADEX_BRIDGE_REPLY_ID: u64 = 3;
+pub const PADEX_DENOM_REPLY_ID: u64 = 4;
+pub const WITHDRAW_TRANSFER_REPLY_ID: u64 = 5;
 
 /// The entry point to the contract for processing replies from submessages.
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response<PalomaMsg>, ContractError> {
     match msg {
         // Caller context: either utils:claim_rewards() or utils:remove_reward_from_pool().
         // If cw20 token reverts the transfer, we bypass it silently.
@@ -19,6 +28,204 @@ pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, Contract
             payload: _,
             gas_used: _,
         } => Ok(Response::new().add_attribute("transfer_error", err_msg)),
+        // Caller context: utils:incentivize().
+        // Incentivization fee transfer failure reverts the whole transaction anyway.
+        // Reply only enriches the error with the fee context.
+        Reply {
+            id: FEE_TRANSFER_REPLY_ID,
+            result: SubMsgResult::Err(err_msg),
+            payload,
+            gas_used: _,
+        } => {
+            let fee_info: IncentivizationFeeInfo = from_json(payload)?;
+            Err(ContractError::FeeTransferFailed {
+                fee: fee_info.fee.to_string(),
+                fee_receiver: fee_info.fee_receiver.to_string(),
+                reason: err_msg,
+            })
+        }
+        // Caller context: utils:claim_rewards().
+        // Claimed PADEX was minted to the contract. Now it can be bridged to the user.
+        Reply {
+            id: PADEX_BRIDGE_REPLY_ID,
+            result: SubMsgResult::Ok(_),
+            payload,
+            gas_used: _,
+        } => {
+            let bridge_info: PadexBridgeInfo = from_json(payload)?;
+            Ok(Response::new()
+                .add_message(CosmosMsg::Custom(PalomaMsg::SkywayMsg {
+                    send_tx: Some(SendTx {
+                        remote_chain_destination_address: bridge_info
+                            .target
+                            .remote_chain_destination_address
+                            .clone(),
+                        amount: format!("{}{}", bridge_info.amount, bridge_info.denom),
+                        chain_reference_id: bridge_info.target.chain_reference_id.clone(),
+                    }),
+                    set_erc20_to_denom: None,
+                }))
+                .add_attribute("action", "bridge_padex")
+                .add_attribute("amount", bridge_info.amount)
+                .add_attribute(
+                    "remote_chain_destination_address",
+                    bridge_info.target.remote_chain_destination_address,
+                )
+                .add_attribute("chain_reference_id", bridge_info.target.chain_reference_id))
+        }
+        // Caller context: instantiate().
+        // Denom creation failure reverts instantiation anyway.
+        // Reply only enriches the error with the denom context.
+        Reply {
+            id: PADEX_DENOM_REPLY_ID,
+            result: SubMsgResult::Err(err_msg),
+            payload,
+            gas_used: _,
+        } => {
+            let denom: String = from_json(payload)?;
+            Err(ContractError::PadexDenomCreationFailed {
+                denom,
+                reason: err_msg,
+            })
+        }
+        // Caller context: execute:withdraw().
+        // If cw20 LP token reverts the transfer, withdrawn LP tokens are returned to the user's position.
+        // Rewards were already claimed and indexes synced within the same block thus nothing is lost.
+        Reply {
+            id: WITHDRAW_TRANSFER_REPLY_ID,
+            result: SubMsgResult::Err(err_msg),
+            payload,
+            gas_used: _,
+        } => {
+            let transfer_info: WithdrawTransferInfo = from_json(payload)?;
+            let lp_token = &transfer_info.lp_token;
+            let user = &transfer_info.user;
+
+            let mut pool_info = PoolInfo::load(deps.storage, lp_token)?;
+            pool_info.update_rewards(deps.storage, &env, lp_token)?;
+            let mut user_info = match UserInfo::may_load_position(deps.storage, user, lp_token)? {
+                Some(user_info) => user_info,
+                None => {
+                    // Position was removed by the withdrawal. Restoring it must not hit the pools limit
+                    UserInfo::open_position(deps.storage, user, None)?;
+                    UserInfo::new(&env)
+                }
+            };
+            user_info.update_and_sync_position(Op::Add(transfer_info.amount), &mut pool_info);
+            sync_user_weight(deps.as_ref(), user, &mut pool_info, &mut user_info)?;
+            let events = pool_info.save(deps.storage, lp_token)?;
+            user_info.save(deps.storage, user, lp_token)?;
+
+            Ok(Response::new().add_events(events).add_attributes([
+                attr("action", "restore_withdrawn_position"),
+                attr("lp_token", lp_token.to_string()),
+                attr("user", user),
+                attr("amount", transfer_info.amount),
+                attr("transfer_error", err_msg),
+            ]))
+        }
         _ => Err(ContractError::FailedToParseReply {}),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coin, to_json_binary, Addr, SubMsgResponse, Uint128};
+
+    use super::*;
+    use crate::types::BridgeTarget;
+
+    #[test]
+    fn failed_fee_transfer_reports_context() {
+        let mut deps = mock_dependencies();
+        let fee_info = IncentivizationFeeInfo {
+            fee_receiver: Addr::unchecked("fee_receiver"),
+            fee: coin(1000, "upaloma"),
+        };
+
+        let err = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: FEE_TRANSFER_REPLY_ID,
+                result: SubMsgResult::Err("receiver rejected funds".to_string()),
+                payload: to_json_binary(&fee_info).unwrap(),
+                gas_used: 0,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::FeeTransferFailed {
+                fee: "1000upaloma".to_string(),
+                fee_receiver: "fee_receiver".to_string(),
+                reason: "receiver rejected funds".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn failed_denom_creation_reports_context() {
+        let mut deps = mock_dependencies();
+        let err = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: PADEX_DENOM_REPLY_ID,
+                result: SubMsgResult::Err("denom already exists".to_string()),
+                payload: to_json_binary("factory/incentives/padex").unwrap(),
+                gas_used: 0,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PadexDenomCreationFailed {
+                denom: "factory/incentives/padex".to_string(),
+                reason: "denom already exists".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn minted_padex_is_bridged() {
+        let mut deps = mock_dependencies();
+        let bridge_info = PadexBridgeInfo {
+            denom: "factory/incentives/padex".to_string(),
+            amount: Uint128::new(1000),
+            target: BridgeTarget {
+                remote_chain_destination_address: "0x1234".to_string(),
+                chain_reference_id: "eth-main".to_string(),
+            },
+        };
+
+        #[allow(deprecated)]
+        let res = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: PADEX_BRIDGE_REPLY_ID,
+                result: SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: None,
+                    msg_responses: vec![],
+                }),
+                payload: to_json_binary(&bridge_info).unwrap(),
+                gas_used: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Custom(PalomaMsg::SkywayMsg {
+                send_tx: Some(SendTx {
+                    remote_chain_destination_address: "0x1234".to_string(),
+                    amount: "1000factory/incentives/padex".to_string(),
+                    chain_reference_id: "eth-main".to_string(),
+                }),
+                set_erc20_to_denom: None,
+            })
+        );
+    }
+}
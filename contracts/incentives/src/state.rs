@@ -1,16 +1,20 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Decimal256, Env, Order, StdError, StdResult, Storage, Uint128, Uint256};
-use cw_storage_plus::{Bound, Item, Map};
+use cosmwasm_std::{
+    attr, ensure, Addr, Api, Decimal256, Env, Event, Order, StdError, StdResult, Storage, Uint128,
+    Uint256,
+};
+use cw_storage_plus::{Bound, Item, Map, PrimaryKey};
 use itertools::Itertools;
 
-use crate::asset::{Asset, AssetInfo, AssetInfoExt};
-use crate::constants::{MAX_PAGE_LIMIT, MAX_REWARD_TOKENS};
+use crate::asset::{determine_asset_info, Asset, AssetInfo, AssetInfoExt};
+use crate::constants::{MAX_CLAIM_HISTORY_LEN, MAX_PAGE_LIMIT, MAX_REWARD_TOKENS};
 use crate::error::ContractError;
 use crate::traits::RewardInfoExt;
 use crate::types::{
-    Config, IncentivesSchedule, OwnershipProposal, PoolInfoResponse, RewardInfo, RewardType,
+    ClaimRecord, Config, IncentivesSchedule, OwnershipProposal, PoolInfoResponse,
+    RewardDistribution, RewardInfo, RewardType, ScheduledPadexRate,
 };
 use crate::utils::asset_info_key;
 
@@ -24,6 +28,10 @@ pub const ACTIVE_POOLS: Item<Vec<(AssetInfo, Uint128)>> = Item::new("active_pool
 /// Prohibited tokens set. Key: binary representing [`AssetInfo`] converted with [`crate::utils::asset_info_key`].
 pub const BLOCKED_TOKENS: Map<&[u8], ()> = Map::new("blocked_tokens");
 
+/// LP tokens allowed to be used as external rewards.
+/// Key: binary representing [`AssetInfo`] converted with [`crate::utils::asset_info_key`].
+pub const LP_REWARD_WHITELIST: Map<&[u8], ()> = Map::new("lp_reward_whitelist");
+
 /// Contains reward indexes for finished rewards. They are removed from [`PoolInfo`] and stored here.
 /// Next time user claims rewards they will be able to claim outstanding rewards from this index.
 /// key: (LP token asset, deregistration timestamp), value: array of tuples (reward token asset, reward index).
@@ -37,6 +45,37 @@ pub const USER_INFO: Map<(&AssetInfo, &String), UserInfo> = Map::new("user_info"
 /// key: (LP token asset, reward token asset, schedule end point), value: reward per second
 pub const EXTERNAL_REWARD_SCHEDULES: Map<(&AssetInfo, &AssetInfo, u64), Decimal256> =
     Map::new("reward_schedules");
+/// Periods which were active in pool info and thus aren't always in [`EXTERNAL_REWARD_SCHEDULES`].
+/// Kept only for the reward timeline, accruals never read it.
+/// key: (LP token asset, reward token asset, period end), value: reward per second
+pub const REWARD_ACTIVE_PERIODS: Map<(&AssetInfo, &AssetInfo, u64), Decimal256> =
+    Map::new("reward_active_periods");
+
+/// Custody accounts opted in by users.
+/// Rewards claimed by the trader on behalf of a user are sent to the user's custody account.
+/// key: user address, value: custody account address
+pub const CUSTODY_RECEIVERS: Map<&String, Addr> = Map::new("custody_receivers");
+
+/// Addresses which users allowed to receive their claimed rewards.
+/// If a user has none, rewards can be sent to any receiver.
+/// key: (user address, receiver address)
+pub const ALLOWED_RECEIVERS: Map<(&String, &Addr), ()> = Map::new("allowed_receivers");
+
+/// Pairs of token factory LP tokens which are minted by another contract (e.g. the factory) instead of the pair itself.
+/// Such LP tokens can't be resolved to a pair by their minter.
+/// key: LP token denom, value: pair address
+pub const LP_TOKEN_PAIRS: Map<&str, Addr> = Map::new("lp_token_pairs");
+
+/// Per-user ring buffer of claimed rewards. Filled only if claim history is enabled in config.
+/// Keeps at most [`MAX_CLAIM_HISTORY_LEN`] latest records per user.
+/// key: (user address, record sequence number), value: claim record
+pub const CLAIM_HISTORY: Map<(&String, u64), ClaimRecord> = Map::new("claim_history");
+/// key: user address, value: sequence number of the next claim history record
+pub const CLAIM_HISTORY_NEXT_ID: Map<&String, u64> = Map::new("claim_history_next_id");
+
+/// Number of pools each user is staked in. Positions opened before the counter was introduced are not counted.
+/// key: user address, value: number of open positions
+pub const USER_POSITIONS_COUNT: Map<&String, u32> = Map::new("user_positions_count");
 
 /// Accumulates all orphaned rewards i.e. those which were added to a pool
 /// but this pool never received any LP tokens deposits.
@@ -44,13 +83,354 @@ pub const EXTERNAL_REWARD_SCHEDULES: Map<(&AssetInfo, &AssetInfo, u64), Decimal2
 /// value: total amount of orphaned tokens
 pub const ORPHANED_REWARDS: Map<&[u8], Uint128> = Map::new("orphaned_rewards");
 
+/// Refund addresses set by incentivizers which added reward to a pool.
+/// Removed once the reward is finished in this pool or a schedule without refund address is added to it.
+/// key: (LP token asset, reward asset), value: refund address
+pub const REWARD_REFUND_ADDRESSES: Map<(&AssetInfo, &AssetInfo), Addr> =
+    Map::new("reward_refund_addresses");
+
+/// Orphaned rewards which are returned to their refund address instead of the orphaned rewards receiver.
+/// key: (binary representing reward [`AssetInfo`] converted with [`asset_info_key`], refund address),
+/// value: total amount of orphaned tokens
+pub const REFUNDABLE_ORPHANED_REWARDS: Map<(&[u8], &Addr), Uint128> =
+    Map::new("refundable_orphaned_rewards");
+
+/// Native LP tokens registered in Skyway by the owner. Withdrawn LP tokens can be bridged only to these chains.
+/// key: (LP token denom, chain reference id), value: ERC20 address on the remote chain
+pub const LP_TOKEN_BRIDGES: Map<(&str, &str), String> = Map::new("lp_token_bridges");
+
+/// Reflects reward tokens added to or removed from the pool in [`REWARD_TOKEN_POOLS`]
+/// and [`REWARD_TOKEN_POOLS_COUNT`]
+fn update_reward_tokens_index(
+    storage: &mut dyn Storage,
+    lp_token: &AssetInfo,
+    prev_rewards: &[RewardInfo],
+    rewards: &[RewardInfo],
+) -> StdResult<()> {
+    let reward_keys = |rewards: &[RewardInfo]| {
+        rewards
+            .iter()
+            .map(|reward_info| asset_info_key(reward_info.reward.asset_info()))
+            .collect::<BTreeSet<_>>()
+    };
+    let prev_keys = reward_keys(prev_rewards);
+    let keys = reward_keys(rewards);
+
+    for key in prev_keys.difference(&keys) {
+        REWARD_TOKEN_POOLS.remove(storage, (key, lp_token));
+        match REWARD_TOKEN_POOLS_COUNT.may_load(storage, key)? {
+            Some(count) if count > 1 => {
+                REWARD_TOKEN_POOLS_COUNT.save(storage, key, &(count - 1))?
+            }
+            _ => REWARD_TOKEN_POOLS_COUNT.remove(storage, key),
+        }
+    }
+    for key in keys.difference(&prev_keys) {
+        REWARD_TOKEN_POOLS.save(storage, (key, lp_token), &())?;
+        REWARD_TOKEN_POOLS_COUNT
+            .update::<_, StdError>(storage, key, |count| Ok(count.unwrap_or_default() + 1))?;
+    }
+
+    Ok(())
+}
+
+/// Rebuilds [`REWARD_TOKEN_POOLS`] and [`REWARD_TOKEN_POOLS_COUNT`] from all pools.
+/// Used on migration from versions without the index
+pub fn rebuild_reward_tokens_index(storage: &mut dyn Storage, api: &dyn Api) -> StdResult<()> {
+    let pools = POOLS
+        .range_raw(storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (key, pool_info) = item?;
+            let lp_token = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            Ok((determine_asset_info(&lp_token, api)?, pool_info.rewards))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    REWARD_TOKEN_POOLS.clear(storage);
+    REWARD_TOKEN_POOLS_COUNT.clear(storage);
+    for (lp_token, rewards) in pools {
+        update_reward_tokens_index(storage, &lp_token, &[], &rewards)?;
+    }
+
+    Ok(())
+}
+
+/// Number of entries in [`ORPHANED_REWARDS`] and [`REFUNDABLE_ORPHANED_REWARDS`].
+/// Entries recorded before the counter was introduced are not counted.
+pub const ORPHANED_ENTRIES_COUNT: Item<u32> = Item::new("orphaned_entries_count");
+
+/// External reward tokens remapped by the owner after their cw20 contract migrated to a new address.
+/// Applied lazily to user positions on load.
+/// key: binary representing the old [`AssetInfo`] converted with [`asset_info_key`],
+/// value: new reward asset info
+pub const REWARD_TOKEN_REMAPS: Map<&[u8], AssetInfo> = Map::new("reward_token_remaps");
+
+/// Total amount of external reward tokens the contract still owes i.e. deposited via incentivize
+/// but not yet claimed by users, returned to the owner or claimed as orphaned.
+/// PADEX rewards are minted on claim thus never tracked here.
+/// key: binary representing [`AssetInfo`] converted with [`asset_info_key`],
+/// value: outstanding amount
+pub const REWARD_LIABILITIES: Map<&[u8], Uint128> = Map::new("reward_liabilities");
+
+/// Number of pools with the reward token. Maintained by [`PoolInfo::save`] so that reward tokens
+/// can be listed without scanning all pools.
+/// key: binary representing [`AssetInfo`] converted with [`asset_info_key`], value: number of pools
+pub const REWARD_TOKEN_POOLS_COUNT: Map<&[u8], u32> = Map::new("reward_token_pools_count");
+
+/// Pools with the reward token. Maintained by [`PoolInfo::save`] along with [`REWARD_TOKEN_POOLS_COUNT`].
+/// key: (binary representing reward [`AssetInfo`] converted with [`asset_info_key`], LP token asset)
+pub const REWARD_TOKEN_POOLS: Map<(&[u8], &AssetInfo), ()> = Map::new("reward_token_pools");
+
+/// Reward tokens frozen by the owner. Claimed frozen rewards aren't transferred but held for the user.
+/// Key: binary representing [`AssetInfo`] converted with [`asset_info_key`].
+pub const FROZEN_REWARDS: Map<&[u8], ()> = Map::new("frozen_rewards");
+
+/// Claimed rewards which were frozen at the moment of claim.
+/// They are paid out on the first claim after the token is unfrozen.
+/// key: (user address, binary representing reward [`AssetInfo`]), value: held amount
+pub const HELD_REWARDS: Map<(&String, &[u8]), Uint128> = Map::new("held_rewards");
+
+/// Funds reserved for keeper bounties. Kept apart from reward liabilities so bounties
+/// never touch tokens owed to stakers.
+/// key: bounty denom, value: remaining amount
+pub const KEEPER_BOUNTY_FUNDS: Map<&str, Uint128> = Map::new("keeper_bounty_funds");
+
+/// PADEX emission rate change queued by the owner. Pools switch to it lazily in
+/// [`PoolInfo::update_rewards`] and config is updated by the next action redistributing emissions.
+pub const SCHEDULED_PADEX_RATE: Item<ScheduledPadexRate> = Item::new("scheduled_padex_rate");
+
+/// PADEX rps of active pools once [`SCHEDULED_PADEX_RATE`] takes effect.
+/// Rewritten by [`save_scheduled_pools_rps`] whenever emissions are redistributed while the change is queued.
+/// key: LP token asset, value: scheduled PADEX rps of the pool
+pub const SCHEDULED_POOLS_RPS: Map<&AssetInfo, Decimal256> = Map::new("scheduled_pools_rps");
+
+/// Splits PADEX per second among active pools proportionally to their alloc points.
+/// Shares are truncated to [`Decimal256`] precision and the remaining atomic units are handed out
+/// one by one to pools with the largest truncated remainders (ties go to the earlier pool).
+/// Thus the sum of returned rps equals padex_per_second exactly as long as total alloc points
+/// in config equal the sum of pools' alloc points.
+pub fn padex_rps_distribution(config: &Config, pools: &[(AssetInfo, Uint128)]) -> Vec<Decimal256> {
+    if config.total_alloc_points.is_zero() {
+        return vec![Decimal256::zero(); pools.len()];
+    }
+
+    let total_alloc_points = Uint256::from(config.total_alloc_points);
+    let total_atomics = Decimal256::from_ratio(config.padex_per_second, 1u8).atomics();
+    let (mut shares, remainders): (Vec<_>, Vec<_>) = pools
+        .iter()
+        .map(|(_, alloc_points)| {
+            let numerator = total_atomics * Uint256::from(*alloc_points);
+            (
+                numerator / total_alloc_points,
+                numerator % total_alloc_points,
+            )
+        })
+        .unzip();
+
+    let distributed = shares
+        .iter()
+        .fold(Uint256::zero(), |acc, share| acc + share);
+    let leftover = total_atomics.saturating_sub(distributed);
+    remainders
+        .iter()
+        .enumerate()
+        .sorted_by(|(a_ind, a_rem), (b_ind, b_rem)| b_rem.cmp(a_rem).then(a_ind.cmp(b_ind)))
+        // Leftover is always less than the number of pools
+        .take(Uint128::try_from(leftover).map_or(usize::MAX, |leftover| leftover.u128() as usize))
+        .for_each(|(ind, _)| shares[ind] += Uint256::one());
+
+    shares.into_iter().map(Decimal256::new).collect()
+}
+
+/// Splits the queued PADEX rate among active pools and stores pools' shares in [`SCHEDULED_POOLS_RPS`].
+/// Must be called with the new config and active pools every time emissions are redistributed.
+pub fn save_scheduled_pools_rps(
+    storage: &mut dyn Storage,
+    config: &Config,
+    active_pools: &[(AssetInfo, Uint128)],
+) -> StdResult<()> {
+    SCHEDULED_POOLS_RPS.clear(storage);
+    let Some(scheduled_rate) = SCHEDULED_PADEX_RATE.may_load(storage)? else {
+        return Ok(());
+    };
+
+    let config = Config {
+        padex_per_second: scheduled_rate.padex_per_second,
+        ..config.clone()
+    };
+    for ((lp_asset, _), rps) in active_pools
+        .iter()
+        .zip(padex_rps_distribution(&config, active_pools))
+    {
+        SCHEDULED_POOLS_RPS.save(storage, lp_asset, &rps)?;
+    }
+
+    Ok(())
+}
+
+/// Loads config with the queued PADEX rate if it already took effect but wasn't applied yet
+pub fn load_effective_config(storage: &dyn Storage, env: &Env) -> StdResult<Config> {
+    let mut config = CONFIG.load(storage)?;
+    if let Some(scheduled_rate) = SCHEDULED_PADEX_RATE
+        .may_load(storage)?
+        .filter(|rate| rate.effective_ts <= env.block.time.seconds())
+    {
+        config.padex_per_second = scheduled_rate.padex_per_second;
+    }
+
+    Ok(config)
+}
+
+/// Moves the scheduled PADEX rate into config once it took effect.
+/// Active pools are updated first so they accrue the old rate until the effective time.
+/// Must be called before emissions are redistributed among pools.
+pub fn apply_scheduled_padex_rate(
+    storage: &mut dyn Storage,
+    env: &Env,
+) -> Result<Vec<Event>, ContractError> {
+    let Some(scheduled_rate) = SCHEDULED_PADEX_RATE
+        .may_load(storage)?
+        .filter(|rate| rate.effective_ts <= env.block.time.seconds())
+    else {
+        return Ok(vec![]);
+    };
+
+    let mut events = vec![];
+    for (lp_asset, _) in ACTIVE_POOLS.load(storage)? {
+        let mut pool_info = PoolInfo::load(storage, &lp_asset)?;
+        pool_info.update_rewards(storage, env, &lp_asset)?;
+        events.extend(pool_info.save(storage, &lp_asset)?);
+    }
+
+    let mut config = CONFIG.load(storage)?;
+    config.padex_per_second = scheduled_rate.padex_per_second;
+    CONFIG.save(storage, &config)?;
+    SCHEDULED_PADEX_RATE.remove(storage);
+    SCHEDULED_POOLS_RPS.clear(storage);
+
+    Ok(events)
+}
+
+/// Accumulates orphaned rewards of a pool reward.
+/// They go to the reward's refund address if set or to [`ORPHANED_REWARDS`] otherwise.
+pub fn accrue_orphaned_reward(
+    storage: &mut dyn Storage,
+    lp_asset: &AssetInfo,
+    reward: &AssetInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let reward_key = asset_info_key(reward);
+    match REWARD_REFUND_ADDRESSES.may_load(storage, (lp_asset, reward))? {
+        Some(refund_address) => add_orphaned_entry(
+            storage,
+            REFUNDABLE_ORPHANED_REWARDS,
+            (&reward_key, &refund_address),
+            amount,
+        ),
+        None => add_orphaned_entry(storage, ORPHANED_REWARDS, &reward_key, amount),
+    }
+}
+
+/// Adds amount to the orphaned rewards entry keeping [`ORPHANED_ENTRIES_COUNT`] in sync.
+/// A new entry is rejected once [`Config::max_orphaned_entries`] is reached.
+pub fn add_orphaned_entry<'a, K: PrimaryKey<'a> + Copy>(
+    storage: &mut dyn Storage,
+    map: Map<K, Uint128>,
+    key: K,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+
+    let total = match map.may_load(storage, key)? {
+        Some(total) => total,
+        None => {
+            ensure_orphaned_capacity(storage, true)?;
+            let count = ORPHANED_ENTRIES_COUNT
+                .may_load(storage)?
+                .unwrap_or_default();
+            ORPHANED_ENTRIES_COUNT.save(storage, &(count + 1))?;
+            Uint128::zero()
+        }
+    };
+    map.save(storage, key, &total.checked_add(amount)?)?;
+
+    Ok(())
+}
+
+/// Removes the orphaned rewards entry keeping [`ORPHANED_ENTRIES_COUNT`] in sync.
+/// Returns the removed amount if the entry existed.
+pub fn remove_orphaned_entry<'a, K: PrimaryKey<'a> + Copy>(
+    storage: &mut dyn Storage,
+    map: Map<K, Uint128>,
+    key: K,
+) -> StdResult<Option<Uint128>> {
+    let total = map.may_load(storage, key)?;
+    if total.is_some() {
+        map.remove(storage, key);
+        // Entries recorded before the counter was introduced aren't counted
+        let count = ORPHANED_ENTRIES_COUNT
+            .may_load(storage)?
+            .unwrap_or_default();
+        ORPHANED_ENTRIES_COUNT.save(storage, &count.saturating_sub(1))?;
+    }
+    Ok(total)
+}
+
+/// Whether orphans of a pool reward would create a new orphaned rewards entry.
+/// They go to the refund address if set or to [`ORPHANED_REWARDS`] otherwise.
+pub fn is_new_orphaned_entry(
+    storage: &dyn Storage,
+    reward: &AssetInfo,
+    refund_address: Option<&Addr>,
+) -> bool {
+    let reward_key = asset_info_key(reward);
+    match refund_address {
+        Some(refund_address) => {
+            !REFUNDABLE_ORPHANED_REWARDS.has(storage, (&reward_key, refund_address))
+        }
+        None => !ORPHANED_REWARDS.has(storage, &reward_key),
+    }
+}
+
+/// Rejects creating a new orphaned rewards entry once [`Config::max_orphaned_entries`] is reached.
+/// Existing entries can always be topped up. The owner must claim orphaned rewards first.
+pub fn ensure_orphaned_capacity(
+    storage: &dyn Storage,
+    is_new_entry: bool,
+) -> Result<(), ContractError> {
+    if !is_new_entry {
+        return Ok(());
+    }
+    if let Some(max_orphaned_entries) = CONFIG.load(storage)?.max_orphaned_entries {
+        ensure!(
+            ORPHANED_ENTRIES_COUNT
+                .may_load(storage)?
+                .unwrap_or_default()
+                < max_orphaned_entries,
+            ContractError::OrphanedEntriesLimitReached {
+                max_orphaned_entries
+            }
+        );
+    }
+
+    Ok(())
+}
+
 impl RewardInfoExt for RewardInfo {
     /// This function is tightly coupled with [`UserInfo`] structure. It iterates over all user's
     /// reward indexes and tries to find the one that matches current reward info. If found, it
     /// calculates the reward amount.
     /// Otherwise it assumes user never claimed this particular reward and their reward index is 0.
     /// Their position will be synced with pool indexes later on.
-    fn calculate_reward(&self, user_info: &UserInfo) -> StdResult<Uint128> {
+    /// Fraction carried over from the previous claim is added to the result.
+    /// Returns the integer reward amount and the truncated fraction.
+    fn calculate_reward(
+        &self,
+        user_info: &UserInfo,
+        distribution: RewardDistribution,
+    ) -> StdResult<(Uint128, Decimal256)> {
         let user_index_opt = user_info
             .last_rewards_index
             .iter()
@@ -61,7 +441,10 @@ impl RewardInfoExt for RewardInfo {
         // rewards from past schedules.
         // Outstanding rewards from finished schedules are handled in claim_finished_rewards().
         // To account current active period properly we need to consider user index as 0.
-        let user_amount = Decimal256::from_ratio(Uint256::from(user_info.amount), 1u8);
+        let user_amount = Decimal256::from_ratio(
+            Uint256::from(user_info.shares(&self.reward, distribution)),
+            1u8,
+        );
         let u256_result = match user_index_opt {
             Some((_, user_reward_index)) if *user_reward_index > self.index => {
                 self.index * user_amount
@@ -69,9 +452,10 @@ impl RewardInfoExt for RewardInfo {
             None => self.index * user_amount,
             Some((_, user_reward_index)) => (self.index - *user_reward_index) * user_amount,
         };
-        let uint256_result = u256_result.to_uint_floor();
+        let total = u256_result.checked_add(user_info.pending_fraction(&self.reward))?;
+        let fraction = total - total.floor();
 
-        Ok(uint256_result.try_into()?)
+        Ok((total.to_uint_floor().try_into()?, fraction))
     }
 }
 
@@ -84,6 +468,28 @@ pub struct PoolInfo {
     pub rewards: Vec<RewardInfo>,
     /// Last time when reward indexes were updated
     pub last_update_ts: u64,
+    /// How PADEX emissions are split between stakers
+    #[serde(default)]
+    pub distribution: RewardDistribution,
+    /// Sum of stakers' vePADEX weights. Used instead of total_lp to accrue PADEX reward index
+    /// in voting power weighted pools.
+    #[serde(default)]
+    pub total_weight: Uint128,
+    /// Last time when the pool started receiving PADEX emissions
+    #[serde(default)]
+    pub activated_at: Option<u64>,
+    /// Owner override of the maximum number of external reward tokens.
+    /// MAX_REWARD_TOKENS is used if not set.
+    #[serde(default)]
+    pub max_reward_tokens: Option<u8>,
+    /// Minimum amount of LP tokens deposited into the pool while it has no stakers.
+    /// Prevents dust deposits from distorting reward indexes. Not enforced if not set
+    #[serde(default)]
+    pub min_first_deposit: Option<Uint128>,
+    /// Time when the pool received its first reward: PADEX emissions, incentive schedule or donation.
+    /// None for pools which never had rewards or were incentivized before this field was introduced
+    #[serde(default)]
+    pub first_incentivized_ts: Option<u64>,
     /// Rewards to remove; In-memory hash map to avoid unnecessary state writes;
     /// Key: reward type, value: (reward index, orphaned rewards)
     /// NOTE: this is not part of serialized structure in state!
@@ -108,6 +514,16 @@ impl PoolInfo {
             return Ok(());
         }
 
+        // Nothing to accrue for a pool without rewards, e.g. a default pool touched for the first time
+        if self.rewards.is_empty() {
+            self.last_update_ts = block_ts;
+            return Ok(());
+        }
+
+        let scheduled_rate = SCHEDULED_PADEX_RATE.may_load(storage)?.filter(|rate| {
+            self.last_update_ts < rate.effective_ts && rate.effective_ts <= block_ts
+        });
+
         for reward_info in self.rewards.iter_mut() {
             let mut collected_rewards = Decimal256::zero();
             let mut time_passed_inner = time_passed;
@@ -162,17 +578,38 @@ impl PoolInfo {
                         reward_info.rps = Decimal256::zero();
                     }
                 }
+            } else if let Some(scheduled_rate) = &scheduled_rate {
+                // Scheduled PADEX rate took effect since the last update.
+                // Collect PADEX at the old rate till the effective time
+                collected_rewards += reward_info.rps
+                    * Decimal256::from_ratio(
+                        scheduled_rate.effective_ts - self.last_update_ts,
+                        1u8,
+                    );
+                // Pools which are not active anymore have no scheduled share
+                reward_info.rps = SCHEDULED_POOLS_RPS
+                    .may_load(storage, lp_asset)?
+                    .unwrap_or_default();
+                time_passed_inner = (block_ts - scheduled_rate.effective_ts).into();
             }
 
             collected_rewards += reward_info.rps * Decimal256::from_ratio(time_passed_inner, 1u8);
 
-            if self.total_lp.is_zero() {
+            // PADEX index in voting power weighted pools is accrued per unit of vePADEX weight
+            let total_shares = if !reward_info.reward.is_external()
+                && self.distribution == RewardDistribution::VotingPowerWeighted
+            {
+                self.total_weight
+            } else {
+                self.total_lp
+            };
+
+            if total_shares.is_zero() {
                 reward_info.orphaned += collected_rewards;
             } else {
-                // Allowing the first depositor to claim orphaned rewards
-                reward_info.index += (reward_info.orphaned + collected_rewards)
-                    / Decimal256::from_ratio(self.total_lp, 1u8);
-                reward_info.orphaned = Decimal256::zero();
+                // Orphaned rewards are kept aside rather than given to the first depositor.
+                // They are moved to ORPHANED_REWARDS once the reward is removed from the pool
+                reward_info.index += collected_rewards / Decimal256::from_ratio(total_shares, 1u8);
             }
 
             if need_remove {
@@ -194,40 +631,60 @@ impl PoolInfo {
 
     /// This function calculates all rewards for a specific user position.
     /// Converts them to [`Asset`]. Returns array of tuples (is_external_reward, Asset).
+    /// Truncated fractions are saved in the user position and added to the next claim.
     pub fn calculate_rewards(&self, user_info: &mut UserInfo) -> StdResult<Vec<(bool, Asset)>> {
-        self.rewards
+        let mut pending_fractions = vec![];
+        let rewards = self
+            .rewards
             .iter()
             .map(|reward_info| {
-                let amount = reward_info.calculate_reward(user_info)?;
+                let (amount, fraction) =
+                    reward_info.calculate_reward(user_info, self.distribution)?;
+                if !fraction.is_zero() {
+                    pending_fractions.push((reward_info.reward.clone(), fraction));
+                }
                 Ok((
                     reward_info.reward.is_external(),
                     reward_info.reward.asset_info().with_balance(amount),
                 ))
             })
-            .collect()
+            .collect::<StdResult<Vec<_>>>()?;
+        // Fractions of rewards which are not in the pool anymore are dropped
+        user_info.pending_fractions = pending_fractions;
+
+        Ok(rewards)
     }
 
-    /// Set padex per second for this pool according to alloc points and general padex per second value
-    pub fn set_padex_rewards(&mut self, config: &Config, alloc_points: Uint128) {
+    /// Set padex per second for this pool. Pool's share must be calculated with [`padex_rps_distribution`].
+    /// Records activation time if the pool wasn't receiving PADEX emissions before.
+    /// Assumes update_rewards() was called before.
+    pub fn set_padex_rewards(&mut self, config: &Config, rps: Decimal256) {
+        let was_active = self.is_active_pool();
+
         if let Some(padex_reward_info) = self.rewards.iter_mut().find(|r| !r.reward.is_external()) {
-            padex_reward_info.rps = Decimal256::from_ratio(
-                config.padex_per_second * alloc_points,
-                config.total_alloc_points,
-            );
+            padex_reward_info.rps = rps;
         } else {
             self.rewards.push(RewardInfo {
                 reward: RewardType::Int(config.padex_token.clone()),
-                rps: Decimal256::from_ratio(
-                    config.padex_per_second * alloc_points,
-                    config.total_alloc_points,
-                ),
+                rps,
                 index: Default::default(),
                 orphaned: Default::default(),
             });
         }
+
+        if !was_active && self.is_active_pool() {
+            self.activated_at = Some(self.last_update_ts);
+            self.first_incentivized_ts
+                .get_or_insert(self.last_update_ts);
+        }
     }
 
     /// Check whether this pools receiving PADEX emissions
+    /// Maximum number of external reward tokens allowed in this pool
+    pub fn max_reward_tokens(&self) -> u8 {
+        self.max_reward_tokens.unwrap_or(MAX_REWARD_TOKENS)
+    }
+
     pub fn is_active_pool(&self) -> bool {
         self.rewards
             .iter()
@@ -261,26 +718,37 @@ impl PoolInfo {
     ///     - Fetch all schedules from EXTERNAL_REWARD_SCHEDULES (array of pairs (end_s, rps_s)) where end_s > start_x;
     ///     - If end_s >= end_x then new schedule is fully covered by the first one. Set point (end_x, rps_s + rps_x);
     ///     - Otherwise loop over all schedules and update them until end_s >= end_x or until all schedules passed.
+    ///
+    /// Overlapping schedules of the same reward are merged rather than replaced:
+    /// rps of all schedules are summed within the overlap window and untouched outside of it.
+    /// Thus total distributed amount always equals the sum of all schedules' amounts.
+    /// E.g. with active (now, end_1, rps_1) and new (now, end_2, rps_2) where end_1 < end_2
+    /// the result is (now, end_1, rps_1 + rps_2) followed by (end_1, end_2, rps_2).
     pub fn incentivize(
         &mut self,
         storage: &mut dyn Storage,
         lp_asset: &AssetInfo,
         schedule: &IncentivesSchedule,
     ) -> Result<(), ContractError> {
+        self.first_incentivized_ts
+            .get_or_insert(self.last_update_ts);
+
         let ext_rewards_len = self
             .rewards
             .iter()
             .filter(|r| r.reward.is_external())
             .count();
 
+        let max_reward_tokens = self.max_reward_tokens();
         let maybe_active_schedule = self.rewards.iter_mut().find(
             |r| matches!(&r.reward, RewardType::Ext { info, .. } if info == &schedule.reward_info),
         );
 
         // Check that we don't exceed the maximum number of reward tokens per pool
-        if ext_rewards_len == MAX_REWARD_TOKENS as usize && maybe_active_schedule.is_none() {
+        if ext_rewards_len >= max_reward_tokens as usize && maybe_active_schedule.is_none() {
             return Err(ContractError::TooManyRewardTokens {
                 lp_token: lp_asset.to_string(),
+                max_reward_tokens,
             });
         }
 
@@ -343,7 +811,20 @@ impl PoolInfo {
 
             // New schedule anyway hits an active one
             active_schedule.rps += schedule.rps;
+
+            if let RewardType::Ext { next_update_ts, .. } = &active_schedule.reward {
+                REWARD_ACTIVE_PERIODS.save(
+                    storage,
+                    (lp_asset, &schedule.reward_info, *next_update_ts),
+                    &active_schedule.rps,
+                )?;
+            }
         } else {
+            REWARD_ACTIVE_PERIODS.save(
+                storage,
+                (lp_asset, &schedule.reward_info, schedule.end_ts),
+                &schedule.rps,
+            )?;
             self.rewards.push(RewardInfo {
                 reward: RewardType::Ext {
                     info: schedule.reward_info.clone(),
@@ -391,6 +872,7 @@ impl PoolInfo {
 
         // Remove active schedule from state
         EXTERNAL_REWARD_SCHEDULES.remove(storage, (lp_asset, reward_asset, next_update_ts));
+        REWARD_ACTIVE_PERIODS.remove(storage, (lp_asset, reward_asset, next_update_ts));
 
         // If there is too much spam in the state, we can bypass upcoming schedules
         if !bypass_upcoming_schedules {
@@ -425,6 +907,111 @@ impl PoolInfo {
         Ok(remaining.to_uint_floor().try_into()?)
     }
 
+    /// Distribute donated reward among current stakers by bumping reward index at once.
+    /// If the token isn't rewarded in this pool yet, it is registered with zero rps and
+    /// finishes on the first update in a later block so stakers can claim it as a finished reward.
+    /// Assumes update_rewards() was called before and pool has stakers.
+    pub fn donate(
+        &mut self,
+        lp_asset: &AssetInfo,
+        reward_asset: &AssetInfo,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        self.first_incentivized_ts
+            .get_or_insert(self.last_update_ts);
+        let index_delta = Decimal256::from_ratio(amount, self.total_lp);
+
+        if let Some(reward_info) = self
+            .rewards
+            .iter_mut()
+            .find(|r| matches!(&r.reward, RewardType::Ext { info, .. } if info == reward_asset))
+        {
+            reward_info.index += index_delta;
+        } else {
+            let ext_rewards_len = self
+                .rewards
+                .iter()
+                .filter(|r| r.reward.is_external())
+                .count();
+            let max_reward_tokens = self.max_reward_tokens();
+            if ext_rewards_len >= max_reward_tokens as usize {
+                return Err(ContractError::TooManyRewardTokens {
+                    lp_token: lp_asset.to_string(),
+                    max_reward_tokens,
+                });
+            }
+
+            self.rewards.push(RewardInfo {
+                reward: RewardType::Ext {
+                    info: reward_asset.clone(),
+                    next_update_ts: self.last_update_ts + 1,
+                },
+                rps: Decimal256::zero(),
+                index: index_delta,
+                orphaned: Default::default(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Caps external reward schedule at the current point. Active and upcoming schedules are removed
+    /// from state and their undistributed part is returned. Reward stays in pool info with zero rps
+    /// and is moved to finished rewards during the next [`PoolInfo::update_rewards`] call
+    /// thus all accrued rewards remain claimable by stakers.
+    /// Assumes update_rewards() was called before.
+    pub fn expire_reward(
+        &mut self,
+        storage: &mut dyn Storage,
+        lp_asset: &AssetInfo,
+        reward_asset: &AssetInfo,
+    ) -> Result<Uint128, ContractError> {
+        let last_update_ts = self.last_update_ts;
+        let reward_info = self
+            .rewards
+            .iter_mut()
+            .find(|reward| matches!(&reward.reward, RewardType::Ext { info, .. } if info == reward_asset))
+            .ok_or_else(|| ContractError::RewardNotFound { pool: lp_asset.to_string(), reward: reward_asset.to_string() })?;
+
+        let next_update_ts = match &reward_info.reward {
+            RewardType::Ext { next_update_ts, .. } => *next_update_ts,
+            RewardType::Int(_) => unreachable!("Only external rewards can be expired"),
+        };
+
+        let mut remaining = reward_info.rps
+            * Decimal256::from_ratio(next_update_ts.saturating_sub(last_update_ts), 1u8);
+        REWARD_ACTIVE_PERIODS.remove(storage, (lp_asset, reward_asset, next_update_ts));
+
+        // Collect rewards from active and upcoming schedules and remove them from state
+        let schedules = EXTERNAL_REWARD_SCHEDULES
+            .prefix((lp_asset, reward_asset))
+            .range(
+                storage,
+                Some(Bound::inclusive(next_update_ts)),
+                None,
+                Order::Ascending,
+            )
+            .collect::<StdResult<Vec<_>>>()?;
+        let mut prev_time = next_update_ts;
+        for (update_ts, period_reward_per_sec) in schedules {
+            if update_ts > next_update_ts {
+                remaining +=
+                    period_reward_per_sec * Decimal256::from_ratio(update_ts - prev_time, 1u8);
+                prev_time = update_ts;
+            }
+
+            EXTERNAL_REWARD_SCHEDULES.remove(storage, (lp_asset, reward_asset, update_ts));
+        }
+
+        reward_info.rps = Decimal256::zero();
+        reward_info.reward = RewardType::Ext {
+            info: reward_asset.clone(),
+            next_update_ts: last_update_ts,
+        };
+
+        Ok(remaining.to_uint_floor().try_into()?)
+    }
+
     pub fn load(storage: &dyn Storage, lp_token: &AssetInfo) -> StdResult<Self> {
         POOLS.load(storage, lp_token)
     }
@@ -433,17 +1020,49 @@ impl PoolInfo {
         POOLS.may_load(storage, lp_token)
     }
 
+    /// Same as [`PoolInfo::load`] but fails with [`ContractError::PoolNotFound`] if the pool
+    /// was never created, e.g. the LP token is misspelled.
+    pub fn load_existing(
+        storage: &dyn Storage,
+        lp_token: &AssetInfo,
+    ) -> Result<Self, ContractError> {
+        Self::may_load(storage, lp_token)?.ok_or_else(|| ContractError::PoolNotFound {
+            lp_token: lp_token.to_string(),
+        })
+    }
+
     /// Reflect changes to pool info in state. Save finished rewards indexes from in-memory hash map.
-    /// If reward schedule has orphaned rewards accumulate them in ORPHANED_REWARDS.
+    /// If reward schedule has orphaned rewards accumulate them with [`accrue_orphaned_reward`].
     /// This function consumes self just to make sure it becomes unusable after calling save().
-    pub fn save(self, storage: &mut dyn Storage, lp_token: &AssetInfo) -> StdResult<()> {
+    /// Returns `reward_schedule_end` events for rewards removed from the pool.
+    /// Removal is persisted only here thus each event is emitted exactly once.
+    pub fn save(
+        self,
+        storage: &mut dyn Storage,
+        lp_token: &AssetInfo,
+    ) -> Result<Vec<Event>, ContractError> {
+        let events = self
+            .rewards_to_remove
+            .iter()
+            // Hash map order is not deterministic
+            .sorted_by_key(|(reward, _)| reward.asset_info().to_string())
+            .map(|(reward, (_, orphaned))| {
+                Event::new("reward_schedule_end").add_attributes([
+                    attr("lp_token", lp_token.to_string()),
+                    attr("reward", reward.asset_info().to_string()),
+                    attr("removed_at", self.last_update_ts.to_string()),
+                    attr("orphaned", orphaned.to_uint_floor().to_string()),
+                ])
+            })
+            .collect_vec();
+
         if !self.rewards_to_remove.is_empty() {
             self.rewards_to_remove
                 .iter()
                 .map(|(reward, index)| (reward.asset_info().clone(), *index))
                 .chunk_by(|(_, (_, orphaned_amount))| orphaned_amount.is_zero())
                 .into_iter()
-                .try_for_each(|(is_zero, group)| {
+                .try_for_each(|(is_zero, group)| -> Result<(), ContractError> {
                     if is_zero {
                         let finished_indexes = group
                             .map(|(reward_asset_info, (index, _))| (reward_asset_info, index))
@@ -452,26 +1071,36 @@ impl PoolInfo {
                             storage,
                             (lp_token, self.last_update_ts),
                             &finished_indexes,
-                        )
+                        )?;
                     } else {
                         // Processing finished schedules with orphaned rewards
                         for (reward, (_, orphaned_amount)) in group {
-                            ORPHANED_REWARDS.update::<_, StdError>(
+                            accrue_orphaned_reward(
                                 storage,
-                                &asset_info_key(&reward),
-                                |amount| {
-                                    Ok(amount.unwrap_or_default()
-                                        + Uint128::try_from(orphaned_amount.to_uint_floor())?)
-                                },
+                                lp_token,
+                                &reward,
+                                Uint128::try_from(orphaned_amount.to_uint_floor())?,
                             )?;
                         }
-
-                        Ok(())
                     }
+
+                    Ok(())
                 })?;
+
+            // Refund address belongs to the finished reward
+            for reward in self.rewards_to_remove.keys() {
+                REWARD_REFUND_ADDRESSES.remove(storage, (lp_token, reward.asset_info()));
+            }
         }
 
-        POOLS.save(storage, lp_token, &self)
+        let prev_rewards = POOLS
+            .may_load(storage, lp_token)?
+            .map(|pool_info| pool_info.rewards)
+            .unwrap_or_default();
+        update_reward_tokens_index(storage, lp_token, &prev_rewards, &self.rewards)?;
+        POOLS.save(storage, lp_token, &self)?;
+
+        Ok(events)
     }
 
     pub fn into_response(self) -> PoolInfoResponse {
@@ -479,11 +1108,14 @@ impl PoolInfo {
             total_lp: self.total_lp,
             rewards: self.rewards,
             last_update_ts: self.last_update_ts,
+            distribution: self.distribution,
+            total_weight: self.total_weight,
         }
     }
 }
 
-/// List all stakers of a specific pool.
+/// List all stakers of a specific pool sorted by address bytes in ascending order.
+/// `start_after` is exclusive.
 pub fn list_pool_stakers(
     storage: &dyn Storage,
     lp_token: &AssetInfo,
@@ -491,7 +1123,7 @@ pub fn list_pool_stakers(
     limit: Option<u8>,
 ) -> StdResult<Vec<(String, Uint128)>> {
     let start = start_after.as_ref().map(Bound::exclusive);
-    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).max(MAX_PAGE_LIMIT);
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
     USER_INFO
         .prefix(lp_token)
         .range(storage, start, None, Order::Ascending)
@@ -500,6 +1132,40 @@ pub fn list_pool_stakers(
         .collect()
 }
 
+/// Appends a record to the user's claim history.
+/// The oldest record is dropped once the history exceeds [`MAX_CLAIM_HISTORY_LEN`].
+pub fn push_claim_record(
+    storage: &mut dyn Storage,
+    user: &String,
+    record: &ClaimRecord,
+) -> StdResult<()> {
+    let id = CLAIM_HISTORY_NEXT_ID
+        .may_load(storage, user)?
+        .unwrap_or_default();
+
+    CLAIM_HISTORY.save(storage, (user, id), record)?;
+    if id >= MAX_CLAIM_HISTORY_LEN {
+        CLAIM_HISTORY.remove(storage, (user, id - MAX_CLAIM_HISTORY_LEN));
+    }
+    CLAIM_HISTORY_NEXT_ID.save(storage, user, &(id + 1))
+}
+
+/// Returns user's claim history records along with their sequence numbers, oldest first.
+pub fn list_claim_history(
+    storage: &dyn Storage,
+    user: &String,
+    start_after: Option<u64>,
+    limit: Option<u8>,
+) -> StdResult<Vec<(u64, ClaimRecord)>> {
+    let start = start_after.map(Bound::exclusive);
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+    CLAIM_HISTORY
+        .prefix(user)
+        .range(storage, start, None, Order::Ascending)
+        .take(limit as usize)
+        .collect()
+}
+
 /// This structure is for internal use only.
 /// Used to add/subtract LP tokens from user position and pool.
 pub enum Op<T> {
@@ -517,6 +1183,14 @@ pub struct UserInfo {
     pub last_rewards_index: Vec<(RewardType, Decimal256)>,
     /// The last time user claimed rewards
     pub last_claim_time: u64,
+    /// vePADEX voting power snapshotted at the last position update.
+    /// Only used in voting power weighted pools
+    #[serde(default)]
+    pub weight: Uint128,
+    /// Fractional parts of active rewards truncated on the last claim.
+    /// They are added to the next claim of the same reward so that truncation loss doesn't accumulate.
+    #[serde(default)]
+    pub pending_fractions: Vec<(RewardType, Decimal256)>,
 }
 
 impl UserInfo {
@@ -526,9 +1200,51 @@ impl UserInfo {
             amount: Uint128::zero(),
             last_rewards_index: vec![],
             last_claim_time: env.block.time.seconds(),
+            weight: Uint128::zero(),
+            pending_fractions: vec![],
         }
     }
 
+    /// Returns the reward fraction carried over from the last claim.
+    pub fn pending_fraction(&self, reward: &RewardType) -> Decimal256 {
+        self.pending_fractions
+            .iter()
+            .find(|(reward_type, _)| reward_type.matches(reward))
+            .map(|(_, fraction)| *fraction)
+            .unwrap_or_default()
+    }
+
+    /// Returns the amount of shares this position holds for the specified reward.
+    /// PADEX in voting power weighted pools is shared by vePADEX weight, all other rewards by LP amount.
+    pub fn shares(&self, reward: &RewardType, distribution: RewardDistribution) -> Uint128 {
+        match (reward, distribution) {
+            (RewardType::Int(_), RewardDistribution::VotingPowerWeighted) => self.weight,
+            _ => self.amount,
+        }
+    }
+
+    /// Set user weight and reflect the change in pool's total weight.
+    /// Positions without LP tokens have no weight.
+    /// Pool rewards must be updated before calling this function.
+    pub fn set_weight(
+        &mut self,
+        weight: Uint128,
+        pool_info: &mut PoolInfo,
+    ) -> Result<(), ContractError> {
+        let weight = if self.amount.is_zero() {
+            Uint128::zero()
+        } else {
+            weight
+        };
+        pool_info.total_weight = pool_info
+            .total_weight
+            .checked_sub(self.weight)?
+            .checked_add(weight)?;
+        self.weight = weight;
+
+        Ok(())
+    }
+
     /// Loads user position from state. If position doesn't exist returns an error.
     /// Can be used in context where position must exist.
     pub fn load_position(
@@ -546,12 +1262,31 @@ impl UserInfo {
 
     /// Tries to load user position from state. If position doesn't exist returns None.
     /// Can be used in context where position may or may not exist. For example, in deposit context.
+    /// Reward tokens remapped by the owner are replaced in user indexes.
     pub fn may_load_position(
         storage: &dyn Storage,
         user: &String,
         lp_token: &AssetInfo,
     ) -> StdResult<Option<Self>> {
-        USER_INFO.may_load(storage, (lp_token, user))
+        let Some(mut user_info) = USER_INFO.may_load(storage, (lp_token, user))? else {
+            return Ok(None);
+        };
+
+        for (reward, _) in user_info
+            .last_rewards_index
+            .iter_mut()
+            .chain(user_info.pending_fractions.iter_mut())
+        {
+            if let RewardType::Ext { info, .. } = reward {
+                if let Some(new_info) =
+                    REWARD_TOKEN_REMAPS.may_load(storage, &asset_info_key(info))?
+                {
+                    *info = new_info;
+                }
+            }
+        }
+
+        Ok(Some(user_info))
     }
 
     /// Reset user index for all finished rewards.
@@ -703,8 +1438,42 @@ impl UserInfo {
         USER_INFO.save(storage, (lp_token, user), &self)
     }
 
-    /// Remove user position from state.
-    pub fn remove(self, storage: &mut dyn Storage, user: &String, lp_token: &AssetInfo) {
-        USER_INFO.remove(storage, (lp_token, user))
+    /// Register a new user position. Fails if the user is already staked in `max_user_pools` pools.
+    pub fn open_position(
+        storage: &mut dyn Storage,
+        user: &String,
+        max_user_pools: Option<u32>,
+    ) -> Result<(), ContractError> {
+        let count = USER_POSITIONS_COUNT
+            .may_load(storage, user)?
+            .unwrap_or_default();
+        if let Some(max_user_pools) = max_user_pools {
+            ensure!(
+                count < max_user_pools,
+                ContractError::TooManyPositions { max_user_pools }
+            );
+        }
+
+        USER_POSITIONS_COUNT.save(storage, user, &(count + 1))?;
+
+        Ok(())
+    }
+
+    /// Remove user position from state and free the user's pool slot.
+    pub fn remove(
+        self,
+        storage: &mut dyn Storage,
+        user: &String,
+        lp_token: &AssetInfo,
+    ) -> StdResult<()> {
+        USER_INFO.remove(storage, (lp_token, user));
+
+        match USER_POSITIONS_COUNT.may_load(storage, user)? {
+            Some(count) if count > 1 => USER_POSITIONS_COUNT.save(storage, user, &(count - 1)),
+            _ => {
+                USER_POSITIONS_COUNT.remove(storage, user);
+                Ok(())
+            }
+        }
     }
 }
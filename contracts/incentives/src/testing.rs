@@ -0,0 +1,119 @@
+//! Helpers shared by unit tests of different modules.
+
+use cosmwasm_std::testing::{
+    message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage,
+};
+use cosmwasm_std::{
+    to_json_binary, Addr, BankMsg, ContractResult, CosmosMsg, DepsMut, Env, OwnedDeps, Response,
+    SystemError, SystemResult, Timestamp, Uint128, WasmQuery,
+};
+
+use crate::asset::{AssetInfo, PairInfo};
+use crate::constants::{EPOCHS_START, EPOCH_LENGTH};
+use crate::error::ContractError;
+use crate::execute::execute;
+use crate::msg::ExecuteMsg;
+use crate::state::{padex_rps_distribution, PoolInfo, ACTIVE_POOLS, CONFIG};
+use crate::types::{Config, PairType, PalomaMsg};
+
+/// Sets up a single active pool receiving 10 PADEX per second.
+/// Block time is 1000 seconds after the start of the 10th epoch.
+pub fn setup() -> (
+    OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    Env,
+    Addr,
+    AssetInfo,
+) {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    env.block.time = Timestamp::from_seconds(EPOCHS_START + 10 * EPOCH_LENGTH + 1000);
+    let owner = deps.api.addr_make("owner");
+    let pair = deps.api.addr_make("pair");
+    let lp_asset = AssetInfo::native(format!("factory/{pair}/lp"));
+
+    let pair_info = PairInfo {
+        asset_infos: vec![AssetInfo::native("uatom"), AssetInfo::native("uusdc")],
+        contract_addr: pair.clone(),
+        liquidity_token: Addr::unchecked(lp_asset.to_string()),
+        pair_type: PairType::Xyk {},
+    };
+    let factory = deps.api.addr_make("factory");
+    let factory_addr = factory.clone();
+    // Both the pair and the factory respond with the same pair info
+    deps.querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, .. }
+            if contract_addr == pair.as_str() || contract_addr == factory_addr.as_str() =>
+        {
+            SystemResult::Ok(ContractResult::Ok(to_json_binary(&pair_info).unwrap()))
+        }
+        _ => SystemResult::Err(SystemError::Unknown {}),
+    });
+
+    let config = Config {
+        owner: owner.clone(),
+        trader: None,
+        factory,
+        generator_controller: None,
+        padex_token: AssetInfo::native("factory/incentives/padex"),
+        padex_per_second: Uint128::new(10),
+        total_alloc_points: Uint128::one(),
+        incentivization_fee_info: None,
+        max_periods: 25,
+        claim_history_enabled: false,
+        vepadex: None,
+        fee_exempt: vec![],
+        paused: false,
+        max_user_pools: None,
+        block_ibc_rewards: false,
+        min_schedule_duration: EPOCH_LENGTH,
+        max_orphaned_entries: None,
+        keeper_bounty: None,
+    };
+    let mut pool_info = PoolInfo {
+        last_update_ts: env.block.time.seconds(),
+        ..Default::default()
+    };
+    let rps = padex_rps_distribution(&config, &[(lp_asset.clone(), Uint128::one())]);
+    pool_info.set_padex_rewards(&config, rps[0]);
+    pool_info.save(deps.as_mut().storage, &lp_asset).unwrap();
+    ACTIVE_POOLS
+        .save(
+            deps.as_mut().storage,
+            &vec![(lp_asset.clone(), Uint128::one())],
+        )
+        .unwrap();
+    CONFIG.save(deps.as_mut().storage, &config).unwrap();
+
+    (deps, env, owner, lp_asset)
+}
+
+pub fn block_token(
+    deps: DepsMut,
+    env: Env,
+    owner: &Addr,
+    token: AssetInfo,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    execute(
+        deps,
+        env,
+        message_info(owner, &[]),
+        ExecuteMsg::UpdateBlockedTokenslist {
+            add: vec![token],
+            remove: vec![],
+        },
+    )
+}
+
+/// Sums up amounts of the denom sent with bank messages
+pub fn claimed_amount(res: &Response<PalomaMsg>, denom: &str) -> Uint128 {
+    res.messages
+        .iter()
+        .filter_map(|sub_msg| match &sub_msg.msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => Some(amount),
+            _ => None,
+        })
+        .flatten()
+        .filter(|coin| coin.denom == denom)
+        .map(|coin| coin.amount)
+        .sum()
+}
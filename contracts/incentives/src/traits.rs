@@ -1,8 +1,13 @@
-use cosmwasm_std::{StdResult, Uint128};
+use cosmwasm_std::{Decimal256, StdResult, Uint128};
 
 use crate::state::UserInfo;
+use crate::types::RewardDistribution;
 
 /// This trait is meant to extend [`palomadex::incentives::RewardInfo`].
 pub trait RewardInfoExt {
-    fn calculate_reward(&self, user_info: &UserInfo) -> StdResult<Uint128>;
+    fn calculate_reward(
+        &self,
+        user_info: &UserInfo,
+        distribution: RewardDistribution,
+    ) -> StdResult<(Uint128, Decimal256)>;
 }
@@ -2,10 +2,11 @@ use std::fmt::{Display, Formatter, Result};
 use std::hash::{Hash, Hasher};
 
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Coin, CustomMsg, Decimal256, Env, StdError, StdResult, Uint128};
+use cosmwasm_std::{Addr, Coin, CustomMsg, Decimal256, Env, StdError, StdResult, Uint128, Uint256};
 
 use crate::asset::Asset;
 use crate::asset::AssetInfo;
+use crate::asset::AssetInfoExt;
 use crate::asset::PairInfo;
 use crate::constants::{EPOCHS_START, EPOCH_LENGTH, MAX_PERIODS};
 
@@ -13,6 +14,41 @@ use crate::constants::{EPOCHS_START, EPOCH_LENGTH, MAX_PERIODS};
 pub struct InputSchedule {
     pub reward: Asset,
     pub duration_periods: u64,
+    /// Address receiving orphaned rewards of this pool reward instead of the orphaned rewards receiver.
+    /// Can be set only by the schedule which adds a new reward to the pool; later schedules must repeat it.
+    /// A later schedule without refund address drops it as orphans can't be attributed to funders anymore.
+    #[serde(default)]
+    pub refund_address: Option<String>,
+}
+
+/// Schedule expressed as a fixed amount per epoch instead of the total amount
+#[cw_serde]
+pub struct PerEpochSchedule {
+    pub reward_info: AssetInfo,
+    /// Amount distributed during each epoch
+    pub amount_per_epoch: Uint128,
+    pub periods: u64,
+}
+
+impl InputSchedule {
+    /// Converts per epoch schedule into the total amount form.
+    /// Each full epoch distributes amount_per_epoch and the rest of the current epoch
+    /// is paid at the same rate, so the total is
+    /// ceil(amount_per_epoch * (end_ts - now) / EPOCH_LENGTH).
+    pub fn per_epoch(env: &Env, schedule: &PerEpochSchedule, max_periods: u64) -> StdResult<Self> {
+        let (_, end_ts) = IncentivesSchedule::epoch_bounds(env, schedule.periods, max_periods)?;
+        let duration = end_ts - env.block.time.seconds();
+        let amount = (schedule.amount_per_epoch.full_mul(duration)
+            + Uint256::from(EPOCH_LENGTH - 1))
+            / Uint256::from(EPOCH_LENGTH);
+        Ok(Self {
+            reward: schedule
+                .reward_info
+                .with_balance(Uint128::try_from(amount)?),
+            duration_periods: schedule.periods,
+            refund_address: None,
+        })
+    }
 }
 
 #[cw_serde]
@@ -62,6 +98,9 @@ pub struct OwnershipProposal {
     pub owner: Addr,
     /// Time until the proposal to change ownership expires
     pub ttl: u64,
+    /// Proposed owner can't claim ownership before this time
+    #[serde(default)]
+    pub earliest_claim_ts: u64,
 }
 
 #[cw_serde]
@@ -82,6 +121,19 @@ pub enum PairQueryMsg {
     },
 }
 
+/// Defines how PADEX emissions of a specific pool are split between stakers.
+#[cw_serde]
+#[derive(Copy, Default, Eq)]
+pub enum RewardDistribution {
+    /// PADEX is distributed pro-rata to staked LP tokens
+    #[default]
+    LpProportional,
+    /// PADEX is distributed pro-rata to stakers' vePADEX voting power ("bribe" style pool).
+    /// Voting power is snapshotted whenever user deposits, withdraws or claims rewards.
+    /// External rewards are still distributed pro-rata to staked LP tokens.
+    VotingPowerWeighted,
+}
+
 #[cw_serde]
 #[derive(Eq)]
 /// This enum is a tiny wrapper over [`AssetInfo`] to differentiate between internal and external rewards.
@@ -90,6 +142,7 @@ pub enum RewardType {
     /// Internal rewards aka PADEX emissions don't have next_update_ts field and they are paid out.
     Int(AssetInfo),
     /// External rewards always have corresponding schedules. Reward is paid out from Generator contract balance.
+    /// Schedules in the PADEX token are external too and coexist with PADEX emissions in the same pool.
     Ext {
         info: AssetInfo,
         /// Time when next schedule should start
@@ -149,8 +202,9 @@ impl Hash for RewardType {
 pub struct Config {
     /// Address allowed to change contract parameters
     pub owner: Addr,
-    /// Trader contract
-    pub trader: Addr,
+    /// Trader contract allowed to act on behalf of users.
+    /// If not set, on-behalf-of actions are disabled
+    pub trader: Option<Addr>,
     /// The Factory address
     pub factory: Addr,
     /// Contract address which can only set active generators and their alloc points
@@ -164,6 +218,64 @@ pub struct Config {
     /// Defines native fee along with fee receiver.
     /// Fee is paid on adding NEW external reward to a specific pool
     pub incentivization_fee_info: Option<IncentivizationFeeInfo>,
+    /// Maximum number of periods (epochs) a single external reward schedule can last
+    #[serde(default = "default_max_periods")]
+    pub max_periods: u64,
+    /// Whether claimed rewards are recorded in per-user claim history
+    #[serde(default)]
+    pub claim_history_enabled: bool,
+    /// vePADEX contract queried for stakers' voting power in voting power weighted pools
+    pub vepadex: Option<Addr>,
+    /// Trusted incentivizers which don't pay incentivization fee
+    #[serde(default)]
+    pub fee_exempt: Vec<Addr>,
+    /// Whether deposits, withdrawals, claims and new rewards are suspended.
+    /// Emergency withdrawals are always available
+    #[serde(default)]
+    pub paused: bool,
+    /// Maximum number of pools a single user can be staked in. None means unlimited
+    pub max_user_pools: Option<u32>,
+    /// Whether IBC denoms are rejected as external rewards
+    #[serde(default)]
+    pub block_ibc_rewards: bool,
+    /// Minimum number of seconds a new external reward schedule must last counting from its start
+    #[serde(default = "default_min_schedule_duration")]
+    pub min_schedule_duration: u64,
+    /// Maximum number of orphaned rewards entries. Donations and schedules which may orphan rewards
+    /// are rejected once it is reached until the owner claims orphaned rewards. None means unlimited
+    #[serde(default)]
+    pub max_orphaned_entries: Option<u32>,
+    /// Paid to the caller of DeactivateBlockedPools which deactivates at least one pool.
+    /// Paid from [`crate::state::KEEPER_BOUNTY_FUNDS`] only. None disables the bounty
+    #[serde(default)]
+    pub keeper_bounty: Option<Coin>,
+}
+
+fn default_max_periods() -> u64 {
+    MAX_PERIODS
+}
+
+fn default_min_schedule_duration() -> u64 {
+    EPOCH_LENGTH
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum VepadexQueryMsg {
+    /// Returns vePADEX locker info of the specified address
+    #[returns(LockerResponse)]
+    Locker {
+        address: String,
+        timestamp: Option<u64>,
+    },
+}
+
+#[cw_serde]
+pub struct LockerResponse {
+    pub deposited_amount: Uint128,
+    pub locked_amount: Uint128,
+    /// vePADEX voting power
+    pub balance: Uint128,
 }
 
 #[cw_serde]
@@ -224,6 +336,51 @@ pub struct ScheduleResponse {
     pub rps: Decimal256,
     pub start_ts: u64,
     pub end_ts: u64,
+    /// Rewards distributed within this period from start_ts until the next epoch start (or end_ts
+    /// if it comes first). None if the period starts exactly at an epoch start.
+    /// rps of all overlapping schedules is summed up, thus it isn't a single schedule's first epoch amount
+    pub partial_epoch_amount: Option<Uint128>,
+}
+
+/// Status of an external reward schedule period relative to the current time
+#[cw_serde]
+#[derive(Copy, Eq)]
+pub enum ScheduleStatus {
+    Finished,
+    Active,
+    Pending,
+}
+
+#[cw_serde]
+pub struct RewardTimelineItem {
+    /// None for the first recorded period as its start isn't stored
+    pub start_ts: Option<u64>,
+    pub end_ts: u64,
+    pub rps: Decimal256,
+    pub status: ScheduleStatus,
+}
+
+impl ScheduleResponse {
+    pub fn new(rps: Decimal256, start_ts: u64, end_ts: u64) -> StdResult<Self> {
+        let next_epoch_start_ts = IncentivesSchedule::next_epoch_start(start_ts);
+        let partial_epoch_amount = if next_epoch_start_ts == start_ts {
+            None
+        } else {
+            let duration = next_epoch_start_ts.min(end_ts) - start_ts;
+            Some(
+                (rps * Decimal256::from_ratio(duration, 1u8))
+                    .to_uint_floor()
+                    .try_into()?,
+            )
+        };
+
+        Ok(Self {
+            rps,
+            start_ts,
+            end_ts,
+            partial_epoch_amount,
+        })
+    }
 }
 
 #[cw_serde]
@@ -235,7 +392,8 @@ pub struct RewardInfo {
     /// Last checkpointed reward per LP token
     pub index: Decimal256,
     /// Orphaned rewards might appear between the time when pool
-    /// gets incentivized and the time when first user stakes
+    /// gets incentivized and the time when first user stakes.
+    /// Stakers never receive them
     pub orphaned: Decimal256,
 }
 
@@ -247,6 +405,238 @@ pub struct PoolInfoResponse {
     pub rewards: Vec<RewardInfo>,
     /// Last time when reward indexes were updated
     pub last_update_ts: u64,
+    /// How PADEX emissions are split between stakers
+    pub distribution: RewardDistribution,
+    /// Sum of stakers' vePADEX weights. Zero for LP proportional pools
+    pub total_weight: Uint128,
+}
+
+#[cw_serde]
+pub struct IsPoolActiveResponse {
+    /// Whether the pool is receiving PADEX emissions
+    pub is_active: bool,
+    /// Allocation points of the pool. None if the pool is not active
+    pub alloc_points: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct ScheduledPadexRate {
+    /// PADEX rewards per second once the change takes effect
+    pub padex_per_second: Uint128,
+    /// Time when the change takes effect
+    pub effective_ts: u64,
+}
+
+#[cw_serde]
+pub struct PadexRateResponse {
+    /// PADEX rewards per second in effect at the current block
+    pub padex_per_second: Uint128,
+    /// Queued rate change which hasn't taken effect yet
+    pub scheduled: Option<ScheduledPadexRate>,
+}
+
+#[cw_serde]
+pub struct ConfigExtendedResponse {
+    pub config: Config,
+    /// Token factory denom of PADEX
+    pub padex_denom: String,
+    /// Number of pools receiving PADEX emissions
+    pub active_pools: u32,
+    /// Whether adding a new external reward to a pool requires incentivization fee.
+    /// Fee exempt senders don't pay it regardless
+    pub incentivization_fee_required: bool,
+}
+
+#[cw_serde]
+pub struct SimulateIncentivizeResponse {
+    /// Incentivization fee taken from sent funds. None if the fee isn't charged
+    pub fee: Option<Coin>,
+    /// Reward recorded in the new schedule
+    pub reward: Asset,
+    /// Whether the schedule adds a new reward to the pool
+    pub new_reward: bool,
+}
+
+#[cw_serde]
+pub struct TopPoolResponse {
+    /// The LP token cw20 address or token factory denom
+    pub lp_token: String,
+    pub alloc_points: Uint128,
+    /// PADEX distributed per second in this pool
+    pub padex_per_second: Decimal256,
+}
+
+/// Token metadata returned along with rewards on demand
+#[cw_serde]
+pub struct TokenMetadata {
+    /// Token symbol. Native tokens report their denom
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Same as [`RewardInfo`] but optionally enriched with reward token metadata
+#[cw_serde]
+pub struct RewardInfoResponse {
+    pub reward: RewardType,
+    pub rps: Decimal256,
+    pub index: Decimal256,
+    pub orphaned: Decimal256,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<TokenMetadata>,
+}
+
+/// Same as [`Asset`] but optionally enriched with reward token metadata
+#[cw_serde]
+pub struct PendingRewardResponse {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<TokenMetadata>,
+}
+
+/// Raw reward indexes of a user position
+#[cw_serde]
+pub struct UserRewardStateResponse {
+    /// Amount of LP tokens staked
+    pub amount: Uint128,
+    /// Snapshotted vePADEX weight. Used instead of amount for PADEX in voting power weighted pools
+    pub weight: Uint128,
+    /// Index state of each reward currently registered in the pool
+    pub rewards: Vec<UserRewardIndex>,
+}
+
+#[cw_serde]
+pub struct UserRewardIndex {
+    pub reward: RewardType,
+    /// Pool index updated to the current block time
+    pub pool_index: Decimal256,
+    /// Index stored in the user position. None if user has never synced this reward
+    pub user_index: Option<Decimal256>,
+    /// Pool rewards accrued while there were no stakers
+    pub orphaned: Decimal256,
+}
+
+#[cw_serde]
+pub struct UserRewardRate {
+    pub reward: RewardType,
+    /// Reward tokens per second the user is currently earning:
+    /// pool rps * user shares / total shares. Zero if the pool has no stakers
+    pub rps: Decimal256,
+}
+
+/// Pool without stakers where rewards are being accrued as orphaned
+#[cw_serde]
+pub struct ZeroStakerPool {
+    /// The LP token cw20 address or token factory denom
+    pub lp_token: String,
+    /// Rewards which are currently being accrued
+    pub rewards: Vec<AssetInfo>,
+}
+
+#[cw_serde]
+pub struct ZeroStakerPoolsResponse {
+    /// Pools from the scanned page which have no stakers
+    pub pools: Vec<ZeroStakerPool>,
+    /// Last scanned LP token. Use it as start_after to fetch the next page.
+    /// None when there are no more pools to scan
+    pub last_scanned: Option<String>,
+}
+
+/// Pool whose reward indexes haven't been updated for a while
+#[cw_serde]
+pub struct StalePool {
+    /// The LP token cw20 address or token factory denom
+    pub lp_token: String,
+    /// Last time reward indexes of the pool were updated
+    pub last_update_ts: u64,
+}
+
+/// Active pool containing a blocked token
+#[cw_serde]
+pub struct InconsistentPool {
+    /// The LP token cw20 address or token factory denom
+    pub lp_token: String,
+    /// Blocked LP token and pair assets of the pool
+    pub blocked_tokens: Vec<AssetInfo>,
+}
+
+#[cw_serde]
+pub struct InconsistentPoolsResponse {
+    /// Pools from the scanned page which contain blocked tokens
+    pub pools: Vec<InconsistentPool>,
+    /// Last scanned LP token. Use it as start_after to fetch the next page.
+    /// None when there are no more pools to scan
+    pub last_scanned: Option<String>,
+}
+
+#[cw_serde]
+pub struct StalePoolsResponse {
+    /// Pools from the scanned page which are stale
+    pub pools: Vec<StalePool>,
+    /// Last scanned LP token. Use it as start_after to fetch the next page.
+    /// None when there are no more pools to scan
+    pub last_scanned: Option<String>,
+}
+
+/// Actual contract balance of a reward token compared to the amount the contract owes
+#[cw_serde]
+pub struct BalanceReconciliationResponse {
+    /// Actual contract balance
+    pub balance: Uint128,
+    /// Total owed amount of scheduled, unclaimed and orphaned rewards
+    pub liability: Uint128,
+    /// Orphaned part of the liability
+    pub orphaned: Uint128,
+}
+
+/// Pending reward split into unboosted amount and boost contribution
+#[cw_serde]
+pub struct PendingRewardDetailedResponse {
+    pub info: AssetInfo,
+    /// Amount the user would receive without boost
+    pub base: Uint128,
+    /// Extra amount the user receives thanks to boost. Always zero until boost is introduced
+    pub boosted_delta: Uint128,
+}
+
+#[cw_serde]
+pub struct PadexEmissionWindowResponse {
+    /// Time when the pool started receiving PADEX emissions. None if the pool is not active
+    pub start: Option<u64>,
+    /// PADEX emissions have no predefined end and flow while the pool is active. Always None
+    pub end: Option<u64>,
+}
+
+/// Estimated size of a ClaimRewards call
+#[cw_serde]
+pub struct ClaimComplexityResponse {
+    /// Number of submessages the claim would emit: one transfer per external reward token and one PADEX mint
+    pub submessages: u32,
+    /// Number of distinct reward tokens with non-zero pending amount
+    pub reward_tokens: u32,
+}
+
+/// Timing information of a pool
+#[cw_serde]
+pub struct PoolLifecycleResponse {
+    /// Time when the pool received its first reward. None if the pool never had rewards
+    /// or was incentivized before this information was tracked
+    pub first_incentivized_ts: Option<u64>,
+    /// Last time when pool reward indexes were updated
+    pub last_update_ts: u64,
+    /// Whether the pool is receiving PADEX emissions
+    pub is_active: bool,
+}
+
+/// Single entry of user's claim history
+#[cw_serde]
+pub struct ClaimRecord {
+    /// Block time when rewards were claimed
+    pub timestamp: u64,
+    /// The LP token cw20 address or token factory denom
+    pub lp_token: String,
+    /// Claimed reward assets
+    pub rewards: Vec<Asset>,
 }
 
 #[cw_serde]
@@ -263,26 +653,12 @@ pub struct IncentivesSchedule {
 
 impl IncentivesSchedule {
     /// Creates a new incentives schedule starting now and lasting for the specified number of periods.
-    pub fn from_input(env: &Env, input: &InputSchedule) -> StdResult<Self> {
-        if input.duration_periods > MAX_PERIODS || input.duration_periods == 0 {
-            return Err(StdError::generic_err(format!(
-                "Duration must be more 0 and less than or equal to {MAX_PERIODS}",
-            )));
-        }
-
+    /// Duration must not exceed `max_periods` configured in [`Config`].
+    pub fn from_input(env: &Env, input: &InputSchedule, max_periods: u64) -> StdResult<Self> {
+        let (next_epoch_start_ts, end_ts) =
+            Self::epoch_bounds(env, input.duration_periods, max_periods)?;
         let block_ts = env.block.time.seconds();
 
-        let rem = block_ts % EPOCHS_START;
-        let next_epoch_start_ts = if rem % EPOCH_LENGTH == 0 {
-            // Hit at the beginning of the current epoch
-            block_ts
-        } else {
-            // Hit somewhere in the middle.
-            // Partially distribute rewards for the current epoch and add input.duration_periods periods more
-            EPOCHS_START + (rem / EPOCH_LENGTH + 1) * EPOCH_LENGTH
-        };
-        let end_ts = next_epoch_start_ts + input.duration_periods * EPOCH_LENGTH;
-
         let rps = Decimal256::from_ratio(input.reward.amount, end_ts - block_ts);
 
         if rps < Decimal256::one() {
@@ -298,6 +674,64 @@ impl IncentivesSchedule {
             rps,
         })
     }
+
+    /// Rewards distributed from now until the first full epoch of the schedule starts.
+    /// Zero if the schedule starts exactly at the epoch start.
+    pub fn prorated_amount(&self, env: &Env) -> StdResult<Uint128> {
+        let duration = self.next_epoch_start_ts - env.block.time.seconds();
+        Ok((self.rps * Decimal256::from_ratio(duration, 1u8))
+            .to_uint_floor()
+            .try_into()?)
+    }
+
+    /// Returns the minimum reward amount accepted by [`IncentivesSchedule::from_input`]
+    /// for a schedule starting now and lasting for the specified number of periods.
+    pub fn min_reward_amount(
+        env: &Env,
+        duration_periods: u64,
+        max_periods: u64,
+    ) -> StdResult<Uint128> {
+        let (_, end_ts) = Self::epoch_bounds(env, duration_periods, max_periods)?;
+        // Reward per second must be at least 1 unit
+        Ok(Uint128::from(end_ts - env.block.time.seconds()))
+    }
+
+    /// Returns the next epoch start and the end of a schedule starting now.
+    fn epoch_bounds(env: &Env, duration_periods: u64, max_periods: u64) -> StdResult<(u64, u64)> {
+        if duration_periods > max_periods || duration_periods == 0 {
+            return Err(StdError::generic_err(format!(
+                "Duration must be more 0 and less than or equal to {max_periods}",
+            )));
+        }
+
+        // Partially distribute rewards for the current epoch and add duration_periods periods more
+        let next_epoch_start_ts = Self::next_epoch_start(env.block.time.seconds());
+        let end_ts = next_epoch_start_ts + duration_periods * EPOCH_LENGTH;
+
+        Ok((next_epoch_start_ts, end_ts))
+    }
+
+    /// Returns the start of the next epoch or the timestamp itself if it hits the epoch start.
+    pub fn next_epoch_start(ts: u64) -> u64 {
+        let rem = ts % EPOCHS_START;
+        let offset = rem % EPOCH_LENGTH;
+        if offset == 0 {
+            // Hit at the beginning of the current epoch
+            ts
+        } else {
+            // Hit somewhere in the middle
+            EPOCHS_START + (rem / EPOCH_LENGTH + 1) * EPOCH_LENGTH
+        }
+    }
+}
+
+/// Reward amount limits for a new external reward schedule
+#[cw_serde]
+pub struct RewardBoundsResponse {
+    /// Minimum reward amount which gives at least 1 unit of reward per second
+    pub min: Uint128,
+    /// Maximum reward amount. None if not limited
+    pub max: Option<Uint128>,
 }
 
 #[cw_serde]
@@ -307,8 +741,13 @@ pub enum PalomaMsg {
         create_denom: Option<CreateDenomMsg>,
         mint_tokens: Option<MintMsg>,
     },
+    /// Message struct for Skyway bridge calls. Unset fields are omitted, so
+    /// `set_erc20_to_denom` calls keep their original wire format.
     SkywayMsg {
-        set_erc20_to_denom: SetErc20ToDenom,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        send_tx: Option<SendTx>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        set_erc20_to_denom: Option<SetErc20ToDenom>,
     },
 }
 
@@ -349,4 +788,36 @@ pub struct SetErc20ToDenom {
     pub chain_reference_id: String,
 }
 
+#[cw_serde]
+pub struct SendTx {
+    pub remote_chain_destination_address: String,
+    pub amount: String,
+    pub chain_reference_id: String,
+}
+
 impl CustomMsg for PalomaMsg {}
+
+/// Destination of PADEX rewards bridged out of Paloma via Skyway
+#[cw_serde]
+pub struct BridgeTarget {
+    /// ERC20 address receiving bridged PADEX on the remote chain
+    pub remote_chain_destination_address: String,
+    /// Remote chain reference id, e.g. "eth-main"
+    pub chain_reference_id: String,
+}
+
+/// Context passed from the cw20 LP token withdrawal to the reply restoring the position on failure
+#[cw_serde]
+pub struct WithdrawTransferInfo {
+    pub user: String,
+    pub lp_token: AssetInfo,
+    pub amount: Uint128,
+}
+
+/// Context passed from the PADEX mint to the reply which bridges minted tokens
+#[cw_serde]
+pub struct PadexBridgeInfo {
+    pub denom: String,
+    pub amount: Uint128,
+    pub target: BridgeTarget,
+}
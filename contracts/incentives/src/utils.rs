@@ -1,41 +1,58 @@
+use std::collections::{HashMap, HashSet};
+
 use cosmwasm_std::{
-    attr, ensure, wasm_execute, Addr, BankMsg, CosmosMsg, CustomQuery, Deps, DepsMut, Env,
-    MessageInfo, Order, QuerierWrapper, ReplyOn, Response, StdError, StdResult, Storage, SubMsg,
-    Uint128,
+    attr, ensure, to_json_binary, wasm_execute, Addr, BankMsg, Coin, CosmosMsg, CustomQuery,
+    Decimal256, Deps, DepsMut, Env, Event, MessageInfo, Order, QuerierWrapper, ReplyOn, Response,
+    StdError, StdResult, Storage, SubMsg, Uint128,
 };
 use cw_storage_plus::Item;
+use cw_utils::PaymentError;
 use itertools::Itertools;
 
 use crate::asset::{
-    determine_asset_info, pair_info_by_pool, AssetInfo, AssetInfoExt, CoinsExt, PairInfo,
+    determine_asset_info, lp_minter, pair_info_by_pool, Asset, AssetInfo, AssetInfoExt, CoinsExt,
+    LpToken, PairInfo,
 };
-use crate::constants::{MAX_ORPHANED_REWARD_LIMIT, MAX_PROPOSAL_TTL};
+use crate::constants::{MAX_ORPHANED_REWARD_LIMIT, MAX_POOL_REWARD_TOKENS, MAX_PROPOSAL_TTL};
 use crate::error::ContractError;
 use crate::msg::FactoryQueryMsg;
-use crate::reply::POST_TRANSFER_REPLY_ID;
+use crate::reply::{FEE_TRANSFER_REPLY_ID, PADEX_BRIDGE_REPLY_ID, POST_TRANSFER_REPLY_ID};
 use crate::state::{
-    Op, PoolInfo, UserInfo, ACTIVE_POOLS, BLOCKED_TOKENS, CONFIG, ORPHANED_REWARDS,
+    accrue_orphaned_reward, add_orphaned_entry, apply_scheduled_padex_rate,
+    ensure_orphaned_capacity, is_new_orphaned_entry, padex_rps_distribution, push_claim_record,
+    remove_orphaned_entry, save_scheduled_pools_rps, Op, PoolInfo, UserInfo, ACTIVE_POOLS,
+    ALLOWED_RECEIVERS, BLOCKED_TOKENS, CONFIG, CUSTODY_RECEIVERS, EXTERNAL_REWARD_SCHEDULES,
+    FINISHED_REWARD_INDEXES, FROZEN_REWARDS, HELD_REWARDS, KEEPER_BOUNTY_FUNDS,
+    LP_REWARD_WHITELIST, LP_TOKEN_PAIRS, ORPHANED_REWARDS, POOLS, REFUNDABLE_ORPHANED_REWARDS,
+    REWARD_ACTIVE_PERIODS, REWARD_LIABILITIES, REWARD_REFUND_ADDRESSES, REWARD_TOKEN_POOLS,
+    REWARD_TOKEN_REMAPS,
 };
 use crate::types::{
-    Config, IncentivesSchedule, InputSchedule, MintMsg, OwnershipProposal, PairQueryMsg, PairType,
-    PalomaMsg,
+    BridgeTarget, ClaimRecord, Config, IncentivesSchedule, IncentivizationFeeInfo, InputSchedule,
+    LockerResponse, MintMsg, OwnershipProposal, PadexBridgeInfo, PairQueryMsg, PairType, PalomaMsg,
+    RewardDistribution, RewardType, VepadexQueryMsg,
 };
 
 /// Claim all rewards and compose [`Response`] object containing all attributes and messages.
-/// This function doesn't mutate the state but mutates in-memory objects.
+/// This function doesn't mutate pools and positions in the state but mutates in-memory objects.
 /// Function caller is responsible for updating the state.
+/// The only state change is appending user's claim history if it is enabled in config.
+/// If `bridge` is set, PADEX is minted to the contract and bridged in the reply.
 pub fn claim_rewards(
-    storage: &dyn Storage,
+    storage: &mut dyn Storage,
     env: Env,
     sender: Addr,
     user: &String,
     pool_tuples: Vec<(&AssetInfo, &mut PoolInfo, &mut UserInfo)>,
+    bridge: Option<BridgeTarget>,
 ) -> Result<Response<PalomaMsg>, ContractError> {
     let mut attrs = vec![attr("action", "claim_rewards"), attr("user", user)];
     let mut external_rewards = vec![];
     let mut protocol_reward_amount = Uint128::zero();
+    let config = CONFIG.load(storage)?;
     for (lp_token_asset, pool_info, pos) in pool_tuples {
         attrs.push(attr("claimed_position", lp_token_asset.to_string()));
+        let mut claimed = vec![];
 
         pool_info.update_rewards(storage, &env, lp_token_asset)?;
 
@@ -43,6 +60,7 @@ pub fn claim_rewards(
         for finished_reward in pos.claim_finished_rewards(storage, lp_token_asset, pool_info)? {
             if !finished_reward.amount.is_zero() {
                 attrs.push(attr("claimed_finished_reward", finished_reward.to_string()));
+                claimed.push(finished_reward.clone());
                 external_rewards.push(finished_reward);
             }
         }
@@ -54,6 +72,9 @@ pub fn claim_rewards(
             attrs.push(attr("claimed_reward", reward_asset.to_string()));
 
             if !reward_asset.amount.is_zero() {
+                claimed.push(reward_asset.clone());
+                // External schedules in the PADEX token are funded by transfers, thus they are
+                // paid out from the contract balance. Only protocol emissions are minted
                 if is_external {
                     external_rewards.push(reward_asset);
                 } else {
@@ -64,41 +85,100 @@ pub fn claim_rewards(
 
         // Sync user index with pool index. It removes all finished schedules from user info.
         pos.update_and_sync_position(Op::Noop, pool_info);
+
+        if config.claim_history_enabled && !claimed.is_empty() {
+            push_claim_record(
+                storage,
+                user,
+                &ClaimRecord {
+                    timestamp: env.block.time.seconds(),
+                    lp_token: lp_token_asset.to_string(),
+                    rewards: claimed,
+                },
+            )?;
+        }
+    }
+
+    // Rewards held while their token was frozen are released once it is unfrozen
+    let held = HELD_REWARDS
+        .prefix(user)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (key, amount) in held {
+        if !FROZEN_REWARDS.has(storage, &key) {
+            HELD_REWARDS.remove(storage, (user, &key));
+            let held_reward = from_key_to_asset_info(key)?.with_balance(amount);
+            attrs.push(attr("released_held_reward", held_reward.to_string()));
+            external_rewards.push(held_reward);
+        }
     }
 
     // Aggregating rewards by asset info.
     // This allows to reduce number of output messages thus reducing total gas cost.
-    let mut messages = external_rewards
+    let (frozen, paid_out): (Vec<_>, Vec<_>) = external_rewards
         .into_iter()
         .chunk_by(|asset| asset.info.clone())
         .into_iter()
-        .map(|(info, assets)| {
-            let amount: Uint128 = assets.into_iter().map(|asset| asset.amount).sum();
-            info.with_balance(amount).into_submsg(
-                sender.to_string(),
-                Some((ReplyOn::Error, POST_TRANSFER_REPLY_ID)),
-            )
-        })
-        .collect::<StdResult<Vec<_>>>()?;
+        .map(|(info, assets)| (info, assets.into_iter().map(|asset| asset.amount).sum()))
+        .partition(|(info, _): &(AssetInfo, Uint128)| {
+            FROZEN_REWARDS.has(storage, &asset_info_key(info))
+        });
 
-    // Claim Palomadex rewards
-    if !protocol_reward_amount.is_zero() {
-        let padex = CONFIG.load(storage)?.padex_token;
+    // Frozen tokens aren't touched. Liability stays as rewards are still owed to the user
+    for (info, amount) in frozen {
+        HELD_REWARDS.update::<_, StdError>(storage, (user, &asset_info_key(&info)), |held| {
+            Ok(held.unwrap_or_default() + amount)
+        })?;
+        attrs.push(attr("held_reward", info.with_balance(amount).to_string()));
+    }
 
-        let padex = match padex {
+    let mut messages = paid_out
+        .into_iter()
+        .map(|(info, amount)| reward_transfer_submsg(storage, info.with_balance(amount), &sender))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Claim Palomadex rewards.
+    // PADEX is created as a native denom on instantiate and validated on migrate. The check below is a defensive fallback
+    if !protocol_reward_amount.is_zero() {
+        let padex = match config.padex_token {
             AssetInfo::NativeToken { denom } => denom,
             AssetInfo::Token { contract_addr: _ } => {
                 return Err(ContractError::PADEXNotNativeCoin {});
             }
         };
-        messages.push(SubMsg::new(CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
-            create_denom: None,
-            mint_tokens: Some(MintMsg {
-                denom: padex,
-                amount: protocol_reward_amount,
-                mint_to_address: sender.to_string(),
-            }),
-        })))
+        match bridge {
+            // Skyway can bridge only tokens held by the contract.
+            // Mint to self first and send the bridge message once the mint succeeded.
+            Some(target) => {
+                attrs.push(attr("bridge_to", &target.remote_chain_destination_address));
+                attrs.push(attr("bridge_chain", &target.chain_reference_id));
+                let mint_msg = CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
+                    create_denom: None,
+                    mint_tokens: Some(MintMsg {
+                        denom: padex.clone(),
+                        amount: protocol_reward_amount,
+                        mint_to_address: env.contract.address.to_string(),
+                    }),
+                });
+                messages.push(
+                    SubMsg::reply_on_success(mint_msg, PADEX_BRIDGE_REPLY_ID).with_payload(
+                        to_json_binary(&PadexBridgeInfo {
+                            denom: padex,
+                            amount: protocol_reward_amount,
+                            target,
+                        })?,
+                    ),
+                )
+            }
+            None => messages.push(SubMsg::new(CosmosMsg::Custom(PalomaMsg::TokenFactoryMsg {
+                create_denom: None,
+                mint_tokens: Some(MintMsg {
+                    denom: padex,
+                    amount: protocol_reward_amount,
+                    mint_to_address: sender.to_string(),
+                }),
+            }))),
+        }
     }
 
     Ok(Response::new()
@@ -106,21 +186,92 @@ pub fn claim_rewards(
         .add_submessages(messages))
 }
 
+/// Refreshes user's vePADEX weight in voting power weighted pools.
+/// Does nothing in LP proportional pools.
+/// Pool rewards must be updated before calling this function.
+pub fn sync_user_weight(
+    deps: Deps,
+    user: &str,
+    pool_info: &mut PoolInfo,
+    user_info: &mut UserInfo,
+) -> Result<(), ContractError> {
+    if pool_info.distribution != RewardDistribution::VotingPowerWeighted {
+        return Ok(());
+    }
+
+    let vepadex = CONFIG
+        .load(deps.storage)?
+        .vepadex
+        .ok_or(ContractError::VepadexNotSet {})?;
+    let locker: LockerResponse = deps.querier.query_wasm_smart(
+        vepadex,
+        &VepadexQueryMsg::Locker {
+            address: user.to_string(),
+            timestamp: None,
+        },
+    )?;
+    user_info.set_weight(locker.balance, pool_info)
+}
+
+/// Determines the address which receives claimed rewards.
+/// If the trader claims on behalf of a user who opted in a custody account, rewards go to the custody account.
+/// Otherwise rewards are sent to the sender.
+/// If the user restricted receivers, the receiver must be either the user or one of [`ALLOWED_RECEIVERS`].
+pub fn rewards_receiver(
+    storage: &dyn Storage,
+    sender: Addr,
+    user: &String,
+) -> Result<Addr, ContractError> {
+    if sender.as_str() == user {
+        return Ok(sender);
+    }
+
+    let receiver = CUSTODY_RECEIVERS.may_load(storage, user)?.unwrap_or(sender);
+    if receiver.as_str() != user
+        && !ALLOWED_RECEIVERS.prefix(user).is_empty(storage)
+        && !ALLOWED_RECEIVERS.has(storage, (user, &receiver))
+    {
+        return Err(ContractError::ReceiverNotAllowed {
+            receiver: receiver.to_string(),
+        });
+    }
+
+    Ok(receiver)
+}
+
+/// Subtracts allocation points of a deactivated pool from the total.
+/// Fails with a typed error if the stored total is less than the pool's points.
+pub fn reduce_total_alloc_points(
+    config: &mut Config,
+    alloc_points: Uint128,
+) -> Result<(), ContractError> {
+    config.total_alloc_points = config
+        .total_alloc_points
+        .checked_sub(alloc_points)
+        .map_err(|_| ContractError::AllocPointsMismatch {
+            total_alloc_points: config.total_alloc_points,
+            alloc_points,
+        })?;
+
+    Ok(())
+}
+
 /// Only factory can set the allocation points to zero for the specified pool.
 /// Called from deregistration context in factory.
 pub fn deactivate_pool(
     deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    lp_token: String,
+    lp_token: LpToken,
 ) -> Result<Response<PalomaMsg>, ContractError> {
+    let rate_events = apply_scheduled_padex_rate(deps.storage, &env)?;
     let mut config = CONFIG.load(deps.storage)?;
 
     if info.sender != config.factory {
         return Err(ContractError::Unauthorized {});
     }
 
-    let lp_token_asset = determine_asset_info(&lp_token, deps.api)?;
+    let lp_token_asset = lp_token.asset_info().clone();
 
     match PoolInfo::may_load(deps.storage, &lp_token_asset)? {
         Some(mut pool_info) if pool_info.is_active_pool() => {
@@ -134,41 +285,54 @@ pub fn deactivate_pool(
 
             pool_info.update_rewards(deps.storage, &env, &lp_token_asset)?;
             pool_info.disable_padex_rewards();
-            pool_info.save(deps.storage, &lp_token_asset)?;
-
-            config.total_alloc_points = config.total_alloc_points.checked_sub(alloc_points)?;
+            let mut events = rate_events;
+            events.extend(pool_info.save(deps.storage, &lp_token_asset)?);
+
+            // Deregistration in the factory must not be blocked by drifted state.
+            // Total is recovered from the remaining active pools instead
+            let reconciled = reduce_total_alloc_points(&mut config, alloc_points).is_err();
+            if reconciled {
+                config.total_alloc_points = active_pools.iter().map(|(_, points)| points).sum();
+            }
 
-            for (lp_asset, alloc_points) in &active_pools {
+            let distribution = padex_rps_distribution(&config, &active_pools);
+            for ((lp_asset, _), rps) in active_pools.iter().zip(distribution) {
                 let mut pool_info = PoolInfo::load(deps.storage, lp_asset)?;
                 pool_info.update_rewards(deps.storage, &env, lp_asset)?;
-                pool_info.set_padex_rewards(&config, *alloc_points);
-                pool_info.save(deps.storage, lp_asset)?;
+                pool_info.set_padex_rewards(&config, rps);
+                events.extend(pool_info.save(deps.storage, lp_asset)?);
             }
 
             ACTIVE_POOLS.save(deps.storage, &active_pools)?;
+            save_scheduled_pools_rps(deps.storage, &config, &active_pools)?;
             CONFIG.save(deps.storage, &config)?;
 
-            Ok(Response::new().add_attributes([
+            let mut response = Response::new().add_events(events).add_attributes([
                 attr("action", "deactivate_pool"),
-                attr("lp_token", lp_token),
-            ]))
+                attr("lp_token", lp_token.to_string()),
+            ]);
+            if reconciled {
+                response = response
+                    .add_attribute("reconciled_total_alloc_points", config.total_alloc_points);
+            }
+
+            Ok(response)
         }
-        _ => Ok(Response::new()),
+        _ => Ok(Response::new().add_events(rate_events)),
     }
 }
 
-/// Removes pools from active pools if their pair type is blocked.
+/// Removes pools from active pools if their pair type or any of their tokens is blocked.
 pub fn deactivate_blocked_pools(
     deps: DepsMut,
     env: Env,
+    info: MessageInfo,
 ) -> Result<Response<PalomaMsg>, ContractError> {
-    let mut response = Response::new();
+    let mut response = Response::new().add_events(apply_scheduled_padex_rate(deps.storage, &env)?);
     let mut active_pools = ACTIVE_POOLS.load(deps.storage)?;
     let mut config = CONFIG.load(deps.storage)?;
 
-    let blocked_pair_types: Vec<PairType> = deps
-        .querier
-        .query_wasm_smart(&config.factory, &FactoryQueryMsg::BlacklistedPairTypes {})?;
+    let blocked_pair_types = query_blacklisted_pair_types(deps.querier, &config)?;
 
     let mut to_remove = vec![];
 
@@ -177,13 +341,17 @@ pub fn deactivate_blocked_pools(
 
         let pair_info = query_pair_info(deps.as_ref(), lp_token_asset)?;
 
-        // check if pair type is blocked
-        if blocked_pair_types.contains(&pair_info.pair_type) {
+        // check if pair type or any of pool tokens is blocked
+        if blocked_pair_types.contains(&pair_info.pair_type)
+            || !find_blocked_tokens(deps.storage, lp_token_asset, &pair_info).is_empty()
+        {
             pool_info.update_rewards(deps.storage, &env, lp_token_asset)?;
             pool_info.disable_padex_rewards();
-            pool_info.save(deps.storage, lp_token_asset)?;
+            response
+                .events
+                .extend(pool_info.save(deps.storage, lp_token_asset)?);
 
-            config.total_alloc_points = config.total_alloc_points.checked_sub(*alloc_points)?;
+            reduce_total_alloc_points(&mut config, *alloc_points)?;
 
             to_remove.push(lp_token_asset.clone());
 
@@ -197,97 +365,251 @@ pub fn deactivate_blocked_pools(
     if !to_remove.is_empty() {
         active_pools.retain(|(lp_token_asset, _)| !to_remove.contains(lp_token_asset));
 
-        for (lp_asset, alloc_points) in &active_pools {
+        let distribution = padex_rps_distribution(&config, &active_pools);
+        for ((lp_asset, _), rps) in active_pools.iter().zip(distribution) {
             let mut pool_info = PoolInfo::load(deps.storage, lp_asset)?;
             pool_info.update_rewards(deps.storage, &env, lp_asset)?;
-            pool_info.set_padex_rewards(&config, *alloc_points);
-            pool_info.save(deps.storage, lp_asset)?;
+            pool_info.set_padex_rewards(&config, rps);
+            response
+                .events
+                .extend(pool_info.save(deps.storage, lp_asset)?);
         }
 
         ACTIVE_POOLS.save(deps.storage, &active_pools)?;
+        save_scheduled_pools_rps(deps.storage, &config, &active_pools)?;
         CONFIG.save(deps.storage, &config)?;
+
+        // Bounty is skipped rather than failing the deactivation when the fund is short
+        if let Some(bounty) = config.keeper_bounty {
+            let fund = KEEPER_BOUNTY_FUNDS
+                .may_load(deps.storage, &bounty.denom)?
+                .unwrap_or_default();
+            if fund >= bounty.amount {
+                KEEPER_BOUNTY_FUNDS.save(deps.storage, &bounty.denom, &(fund - bounty.amount))?;
+                response = response
+                    .add_attribute("keeper_bounty", bounty.to_string())
+                    .add_message(BankMsg::Send {
+                        to_address: info.sender.to_string(),
+                        amount: vec![bounty],
+                    });
+            }
+        }
     }
 
     Ok(response)
 }
 
+/// Runs all checks of a new external reward schedule which don't depend on the pool state.
+pub fn validate_schedule(
+    deps: Deps,
+    env: &Env,
+    config: &Config,
+    lp_token: &LpToken,
+    input: &InputSchedule,
+) -> Result<IncentivesSchedule, ContractError> {
+    let schedule = IncentivesSchedule::from_input(env, input, config.max_periods)?;
+    let duration = schedule.end_ts - env.block.time.seconds();
+    ensure!(
+        duration >= config.min_schedule_duration,
+        ContractError::ScheduleTooShort {
+            duration,
+            min_duration: config.min_schedule_duration
+        }
+    );
+    validate_reward(deps, config, lp_token, &schedule.reward_info)?;
+
+    Ok(schedule)
+}
+
+/// Checks that the token can be added as external reward to the pool.
+/// Shared by schedules and donations.
+pub fn validate_reward(
+    deps: Deps,
+    config: &Config,
+    lp_token: &LpToken,
+    reward_info: &AssetInfo,
+) -> Result<(), ContractError> {
+    // Prohibit rewards with blocked token
+    if BLOCKED_TOKENS.has(deps.storage, &asset_info_key(reward_info)) {
+        return Err(ContractError::BlockedToken {
+            token: reward_info.to_string(),
+        });
+    }
+    ensure_reward_not_frozen(deps.storage, reward_info)?;
+
+    // IBC rewards depend on the channel staying operational
+    ensure!(
+        !(config.block_ibc_rewards && reward_info.is_ibc()),
+        ContractError::IbcRewardBlocked {
+            reward: reward_info.to_string()
+        }
+    );
+
+    // Prohibit LP tokens of registered pools as rewards unless explicitly allowed by owner
+    if !LP_REWARD_WHITELIST.has(deps.storage, &asset_info_key(reward_info))
+        && is_registered_lp_token(deps, config, reward_info)
+    {
+        return Err(ContractError::LpTokenAsReward {
+            reward: reward_info.to_string(),
+        });
+    }
+
+    let pair_info = query_pair_info(deps, lp_token.asset_info())?;
+    is_pool_registered(deps.querier, config, &pair_info, &lp_token.to_string())?;
+
+    Ok(())
+}
+
+/// Deducts incentivization fee from sent funds if the reward is new for the pool
+/// and the sender isn't exempted. Unknown sender is never exempted.
+/// Returns fee info if the fee was charged.
+pub fn deduct_incentivization_fee<'a>(
+    config: &'a Config,
+    sender: Option<&Addr>,
+    funds: &mut Vec<Coin>,
+    lp_token: &LpToken,
+    reward_info: &AssetInfo,
+    is_new_reward: bool,
+) -> Result<Option<&'a IncentivizationFeeInfo>, ContractError> {
+    // Check whether this is a new external reward token.
+    // 3rd parties are encouraged to keep endless schedules without breaks even with the small rewards.
+    // Otherwise, reward token will be removed from the pool info and go to outstanding rewards.
+    // Next schedules with the same token will be considered as "new".
+    if !is_new_reward {
+        return Ok(None);
+    }
+
+    // If fee set we expect to receive it unless sender is exempted
+    let Some(incentivization_fee_info) = config
+        .incentivization_fee_info
+        .as_ref()
+        .filter(|_| !sender.is_some_and(|sender| config.fee_exempt.contains(sender)))
+    else {
+        return Ok(None);
+    };
+
+    let fee_expected = || ContractError::IncentivizationFeeExpected {
+        fee: incentivization_fee_info.fee.to_string(),
+        lp_token: lp_token.to_string(),
+        new_reward_token: reward_info.to_string(),
+    };
+    let (ind, fee_coin) = funds
+        .iter()
+        .find_position(|coin| coin.denom == incentivization_fee_info.fee.denom)
+        .ok_or_else(fee_expected)?;
+    // Mutate funds array so we can assert below that reward coins properly sent
+    funds[ind].amount = fee_coin
+        .amount
+        .checked_sub(incentivization_fee_info.fee.amount)
+        .map_err(|_| fee_expected())?;
+    if funds[ind].amount.is_zero() {
+        funds.remove(ind);
+    }
+
+    Ok(Some(incentivization_fee_info))
+}
+
+/// Sends incentivization fee to fee receiver. Fee info is passed to the reply to report failure context.
+fn fee_transfer_submsg(fee_info: &IncentivizationFeeInfo) -> StdResult<SubMsg<PalomaMsg>> {
+    Ok(SubMsg::reply_on_error(
+        BankMsg::Send {
+            to_address: fee_info.fee_receiver.to_string(),
+            amount: vec![fee_info.fee.clone()],
+        },
+        FEE_TRANSFER_REPLY_ID,
+    )
+    .with_payload(to_json_binary(fee_info)?))
+}
+
 pub fn incentivize(
     deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    lp_token: String,
+    lp_token: LpToken,
     input: InputSchedule,
+    refund_excess: bool,
 ) -> Result<Response<PalomaMsg>, ContractError> {
-    let schedule = IncentivesSchedule::from_input(&env, &input)?;
+    let config = CONFIG.load(deps.storage)?;
+    let schedule = validate_schedule(deps.as_ref(), &env, &config, &lp_token, &input)?;
+    let lp_token_asset = lp_token.asset_info();
 
     let mut response = Response::new().add_attributes([
         attr("action", "incentivize"),
-        attr("lp_token", lp_token.clone()),
+        attr("lp_token", lp_token.to_string()),
         attr("start_ts", env.block.time.seconds().to_string()),
         attr("end_ts", schedule.end_ts.to_string()),
         attr("reward", schedule.reward_info.to_string()),
+        attr(
+            "prorated_first_epoch_amount",
+            schedule.prorated_amount(&env)?.to_string(),
+        ),
     ]);
 
-    let lp_token_asset = determine_asset_info(&lp_token, deps.api)?;
-
-    // Prohibit reward schedules with blocked token
-    if BLOCKED_TOKENS.has(deps.storage, &asset_info_key(&schedule.reward_info)) {
-        return Err(ContractError::BlockedToken {
-            token: schedule.reward_info.to_string(),
-        });
+    let mut pool_info = PoolInfo::may_load(deps.storage, lp_token_asset)?.unwrap_or_default();
+    pool_info.update_rewards(deps.storage, &env, lp_token_asset)?;
+    // Rewards of pools without stakers are orphaned
+    if pool_info.total_lp.is_zero() {
+        let refund_address = input
+            .refund_address
+            .as_deref()
+            .map(|addr| deps.api.addr_validate(addr))
+            .transpose()?;
+        ensure_orphaned_capacity(
+            deps.storage,
+            is_new_orphaned_entry(deps.storage, &schedule.reward_info, refund_address.as_ref()),
+        )?;
     }
 
-    let pair_info = query_pair_info(deps.as_ref(), &lp_token_asset)?;
-    let config = CONFIG.load(deps.storage)?;
-    is_pool_registered(deps.querier, &config, &pair_info, &lp_token)?;
-
-    let mut pool_info = PoolInfo::may_load(deps.storage, &lp_token_asset)?.unwrap_or_default();
-    pool_info.update_rewards(deps.storage, &env, &lp_token_asset)?;
-
     let rewards_number_before = pool_info.rewards.len();
-    pool_info.incentivize(deps.storage, &lp_token_asset, &schedule)?;
-
-    let mut funds = info.funds.clone();
-
-    // Check whether this is a new external reward token.
-    // 3rd parties are encouraged to keep endless schedules without breaks even with the small rewards.
-    // Otherwise, reward token will be removed from the pool info and go to outstanding rewards.
-    // Next schedules with the same token will be considered as "new".
-    if rewards_number_before < pool_info.rewards.len() {
-        // If fee set we expect to receive it
-        if let Some(incentivization_fee_info) = &config.incentivization_fee_info {
-            let fee_coin_pos = funds
-                .iter()
-                .find_position(|coin| coin.denom == incentivization_fee_info.fee.denom);
-            if let Some((ind, fee_coin)) = fee_coin_pos {
-                // Mutate funds array so we can assert below that reward coins properly sent
-                funds[ind].amount = fee_coin
-                    .amount
-                    .checked_sub(incentivization_fee_info.fee.amount)
-                    .map_err(|_| ContractError::IncentivizationFeeExpected {
-                        fee: incentivization_fee_info.fee.to_string(),
-                        lp_token,
-                        new_reward_token: schedule.reward_info.to_string(),
-                    })?;
-                if funds[ind].amount.is_zero() {
-                    funds.remove(ind);
-                }
-
-                // Send fee to fee receiver
-                response = response.add_message(BankMsg::Send {
-                    to_address: incentivization_fee_info.fee_receiver.to_string(),
-                    amount: vec![incentivization_fee_info.fee.clone()],
-                });
-            } else {
-                return Err(ContractError::IncentivizationFeeExpected {
-                    fee: incentivization_fee_info.fee.to_string(),
-                    lp_token,
-                    new_reward_token: schedule.reward_info.to_string(),
-                });
+    pool_info.incentivize(deps.storage, lp_token_asset, &schedule)?;
+    let is_new_reward = rewards_number_before < pool_info.rewards.len();
+    response = response.add_event(Event::new("reward_schedule_start").add_attributes([
+        attr("lp_token", lp_token.to_string()),
+        attr("reward", schedule.reward_info.to_string()),
+        attr("start_ts", env.block.time.seconds().to_string()),
+        attr("end_ts", schedule.end_ts.to_string()),
+        attr("new_reward", is_new_reward.to_string()),
+    ]));
+
+    // Orphans can't be split between funders thus they go to the orphaned rewards receiver
+    // once a schedule without refund address is mixed in.
+    // Orphaned rewards accrued so far belong to the schedules with refund address
+    let refund_key = (lp_token_asset, &schedule.reward_info);
+    if input.refund_address.is_none() {
+        if let Some(refund_address) = REWARD_REFUND_ADDRESSES.may_load(deps.storage, refund_key)? {
+            if let Some(reward_info) = pool_info
+                .rewards
+                .iter_mut()
+                .find(|r| r.reward.asset_info() == &schedule.reward_info)
+            {
+                let accrued = reward_info.orphaned.to_uint_floor();
+                reward_info.orphaned -= Decimal256::from_ratio(accrued, 1u8);
+                add_orphaned_entry(
+                    deps.storage,
+                    REFUNDABLE_ORPHANED_REWARDS,
+                    (&asset_info_key(&schedule.reward_info), &refund_address),
+                    accrued.try_into()?,
+                )?;
             }
+            REWARD_REFUND_ADDRESSES.remove(deps.storage, refund_key);
+            response = response.add_attribute("dropped_refund_address", refund_address);
         }
     }
 
+    let mut funds = info.funds.clone();
+    if let Some(incentivization_fee_info) = deduct_incentivization_fee(
+        &config,
+        Some(&info.sender),
+        &mut funds,
+        &lp_token,
+        &schedule.reward_info,
+        is_new_reward,
+    )? {
+        response = response.add_submessage(fee_transfer_submsg(incentivization_fee_info)?);
+    }
+
+    let reward_amount = input.reward.amount;
+
     // Assert that we received reward tokens
     match &schedule.reward_info {
         AssetInfo::Token { contract_addr } => {
@@ -301,21 +623,57 @@ pub fn incentivize(
                 vec![],
             )?);
         }
-        AssetInfo::NativeToken { .. } => {
-            funds.assert_coins_properly_sent(&[input.reward], &[schedule.reward_info.clone()])?
+        AssetInfo::NativeToken { denom } => {
+            if refund_excess {
+                if let Some(coin) = funds
+                    .iter_mut()
+                    .find(|coin| &coin.denom == denom && coin.amount > reward_amount)
+                {
+                    let excess = Coin::new(coin.amount - reward_amount, denom);
+                    coin.amount = reward_amount;
+                    response = response
+                        .add_attribute("refund", excess.to_string())
+                        .add_message(BankMsg::Send {
+                            to_address: info.sender.to_string(),
+                            amount: vec![excess],
+                        });
+                }
+            }
+            funds.assert_coins_properly_sent(
+                &[input.reward],
+                std::slice::from_ref(&schedule.reward_info),
+            )?
         }
     }
 
-    pool_info.save(deps.storage, &lp_token_asset)?;
+    let events = pool_info.save(deps.storage, lp_token_asset)?;
+    increase_reward_liability(deps.storage, &schedule.reward_info, reward_amount)?;
+
+    // Saved after the pool as finished rewards clear their refund address on save
+    if let Some(refund_address) = &input.refund_address {
+        let refund_address = deps.api.addr_validate(refund_address)?;
+        if is_new_reward {
+            REWARD_REFUND_ADDRESSES.save(deps.storage, refund_key, &refund_address)?;
+        } else {
+            ensure!(
+                REWARD_REFUND_ADDRESSES.may_load(deps.storage, refund_key)?
+                    == Some(refund_address.clone()),
+                ContractError::RefundAddressNotAllowed {
+                    reward: schedule.reward_info.to_string()
+                }
+            );
+        }
+        response = response.add_attribute("refund_address", refund_address);
+    }
 
-    Ok(response)
+    Ok(response.add_events(events))
 }
 
 pub fn remove_reward_from_pool(
     deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    lp_token: String,
+    lp_token: LpToken,
     reward: String,
     bypass_upcoming_schedules: bool,
     receiver: String,
@@ -325,56 +683,551 @@ pub fn remove_reward_from_pool(
         return Err(ContractError::Unauthorized {});
     }
 
-    let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+    let lp_asset = lp_token.asset_info();
     let reward_asset = determine_asset_info(&reward, deps.api)?;
 
-    let mut pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
-    pool_info.update_rewards(deps.storage, &env, &lp_asset)?;
+    let mut pool_info = PoolInfo::load(deps.storage, lp_asset)?;
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
     let unclaimed = pool_info.deregister_reward(
         deps.storage,
-        &lp_asset,
+        lp_asset,
         &reward_asset,
         bypass_upcoming_schedules,
     )?;
 
-    pool_info.save(deps.storage, &lp_asset)?;
+    let events = pool_info.save(deps.storage, lp_asset)?;
 
-    let mut response = Response::new();
+    let mut response = Response::new().add_events(events);
 
     // Send unclaimed rewards
     if !unclaimed.is_zero() {
         deps.api.addr_validate(&receiver)?;
-        let transfer_msg = reward_asset
-            .with_balance(unclaimed)
-            .into_submsg(receiver, Some((ReplyOn::Error, POST_TRANSFER_REPLY_ID)))?;
+        let transfer_msg =
+            reward_transfer_submsg(deps.storage, reward_asset.with_balance(unclaimed), receiver)?;
         response = response.add_submessage(transfer_msg);
     }
 
     Ok(response.add_attributes([
         attr("action", "remove_reward_from_pool"),
-        attr("lp_token", lp_token),
+        attr("lp_token", lp_token.to_string()),
+        attr("reward", reward),
+    ]))
+}
+
+/// Batched version of [`remove_reward_from_pool`].
+pub fn remove_rewards_from_pool(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    lp_token: LpToken,
+    rewards: Vec<String>,
+    bypass_upcoming_schedules: bool,
+    receiver: String,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    ensure!(
+        rewards.len() <= MAX_POOL_REWARD_TOKENS as usize,
+        ContractError::TooManyRewardsToRemove {
+            max: MAX_POOL_REWARD_TOKENS
+        }
+    );
+
+    let lp_asset = lp_token.asset_info();
+    let mut pool_info = PoolInfo::load(deps.storage, lp_asset)?;
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+
+    let mut attrs = vec![
+        attr("action", "remove_rewards_from_pool"),
+        attr("lp_token", lp_token.to_string()),
+    ];
+    let mut unclaimed_rewards: Vec<Asset> = vec![];
+    for reward in rewards {
+        let reward_asset = determine_asset_info(&reward, deps.api)?;
+        let unclaimed = pool_info.deregister_reward(
+            deps.storage,
+            lp_asset,
+            &reward_asset,
+            bypass_upcoming_schedules,
+        )?;
+        attrs.push(attr("reward", reward));
+        attrs.push(attr("unclaimed", unclaimed));
+        // Each reward is registered in the pool only once thus there is one transfer per token
+        if !unclaimed.is_zero() {
+            unclaimed_rewards.push(reward_asset.with_balance(unclaimed));
+        }
+    }
+
+    let events = pool_info.save(deps.storage, lp_asset)?;
+
+    let mut response = Response::new().add_events(events);
+
+    // Send unclaimed rewards
+    if !unclaimed_rewards.is_empty() {
+        deps.api.addr_validate(&receiver)?;
+    }
+    for asset in unclaimed_rewards {
+        let transfer_msg = reward_transfer_submsg(deps.storage, asset, &receiver)?;
+        response = response.add_submessage(transfer_msg);
+    }
+
+    Ok(response.add_attributes(attrs))
+}
+
+/// Distributes sent coin among current pool stakers at once. No schedule is created.
+/// If pool has no stakers the donation goes to orphaned rewards.
+/// Donated token is validated and charged with incentivization fee the same way as schedules.
+pub fn donate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lp_token: LpToken,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let lp_asset = lp_token.asset_info();
+
+    // The donated coin may be accompanied by incentivization fee
+    let fee_denom = config
+        .incentivization_fee_info
+        .as_ref()
+        .map(|fee_info| fee_info.fee.denom.as_str());
+    let reward_info = match &info.funds[..] {
+        [] => return Err(PaymentError::NoFunds {}.into()),
+        [coin] => AssetInfo::native(&coin.denom),
+        [fee, coin] | [coin, fee] if Some(fee.denom.as_str()) == fee_denom => {
+            AssetInfo::native(&coin.denom)
+        }
+        _ => return Err(PaymentError::MultipleDenoms {}.into()),
+    };
+    validate_reward(deps.as_ref(), &config, &lp_token, &reward_info)?;
+
+    let mut pool_info = PoolInfo::may_load(deps.storage, lp_asset)?.unwrap_or_default();
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+
+    let is_new_reward = !pool_info
+        .rewards
+        .iter()
+        .any(|r| r.reward.is_external() && r.reward.asset_info() == &reward_info);
+    let mut funds = info.funds.clone();
+    let mut response = Response::new();
+    if let Some(incentivization_fee_info) = deduct_incentivization_fee(
+        &config,
+        Some(&info.sender),
+        &mut funds,
+        &lp_token,
+        &reward_info,
+        is_new_reward,
+    )? {
+        response = response.add_submessage(fee_transfer_submsg(incentivization_fee_info)?);
+    }
+    // Only the donated coin is left once the fee is deducted
+    let [coin] = &funds[..] else {
+        return Err(PaymentError::NoFunds {}.into());
+    };
+    let reward = Asset::native(&coin.denom, coin.amount);
+
+    let orphaned = pool_info.total_lp.is_zero();
+    if orphaned {
+        add_orphaned_entry(
+            deps.storage,
+            ORPHANED_REWARDS,
+            &asset_info_key(&reward.info),
+            reward.amount,
+        )?;
+    } else {
+        pool_info.donate(lp_asset, &reward.info, reward.amount)?;
+    }
+    let events = pool_info.save(deps.storage, lp_asset)?;
+    increase_reward_liability(deps.storage, &reward.info, reward.amount)?;
+
+    Ok(response.add_events(events).add_attributes([
+        attr("action", "donate"),
+        attr("lp_token", lp_token.to_string()),
+        attr("reward", reward.to_string()),
+        attr("orphaned", orphaned.to_string()),
+    ]))
+}
+
+/// Force-expires abandoned external reward schedule. Unlike [`remove_reward_from_pool`]
+/// nothing is sent out: undistributed rewards are moved to orphaned rewards
+/// and the reward is finished through the regular pool update path.
+pub fn expire_schedule(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    lp_token: LpToken,
+    reward: String,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure!(info.sender == config.owner, ContractError::Unauthorized {});
+
+    let lp_asset = lp_token.asset_info();
+    let reward_asset = determine_asset_info(&reward, deps.api)?;
+
+    let mut pool_info = PoolInfo::load(deps.storage, lp_asset)?;
+    pool_info.update_rewards(deps.storage, &env, lp_asset)?;
+    let remaining = pool_info.expire_reward(deps.storage, lp_asset, &reward_asset)?;
+    let events = pool_info.save(deps.storage, lp_asset)?;
+
+    if !remaining.is_zero() {
+        accrue_orphaned_reward(deps.storage, lp_asset, &reward_asset, remaining)?;
+    }
+
+    Ok(Response::new().add_events(events).add_attributes([
+        attr("action", "expire_schedule"),
+        attr("lp_token", lp_token.to_string()),
         attr("reward", reward),
+        attr("orphaned_amount", remaining),
     ]))
 }
 
+/// Rewrites external cw20 reward token in the whole state after its contract migrated to a new address.
+/// Scans all pools thus may be expensive on a big state.
+pub fn remap_reward_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    old: String,
+    new: String,
+) -> Result<Response<PalomaMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure!(info.sender == config.owner, ContractError::Unauthorized {});
+
+    let old_asset = determine_asset_info(&old, deps.api)?;
+    ensure!(
+        !old_asset.is_native_token(),
+        ContractError::RewardRemapNotCw20 { reward: old }
+    );
+    let new_asset = AssetInfo::cw20(deps.api.addr_validate(&new)?);
+    ensure!(
+        !BLOCKED_TOKENS.has(deps.storage, &asset_info_key(&new_asset)),
+        ContractError::BlockedToken { token: new }
+    );
+
+    let mut attrs = vec![
+        attr("action", "remap_reward_token"),
+        attr("old", &old),
+        attr("new", &new),
+    ];
+
+    let pools = POOLS
+        .range_raw(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (key, mut pool_info) in pools {
+        let lp_token = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+        let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+
+        let pool_remapped = pool_info.rewards.iter().any(|reward_info| {
+            reward_info.reward.is_external() && reward_info.reward.asset_info() == &old_asset
+        });
+        if pool_remapped {
+            ensure!(
+                !pool_info
+                    .rewards
+                    .iter()
+                    .any(|reward_info| reward_info.reward.asset_info() == &new_asset),
+                ContractError::RewardRemapCollision {
+                    reward: new,
+                    lp_token
+                }
+            );
+            for reward_info in pool_info.rewards.iter_mut() {
+                if let RewardType::Ext { info, .. } = &mut reward_info.reward {
+                    if *info == old_asset {
+                        *info = new_asset.clone();
+                    }
+                }
+            }
+
+            let schedules = EXTERNAL_REWARD_SCHEDULES
+                .prefix((&lp_asset, &old_asset))
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (end_ts, rps) in &schedules {
+                EXTERNAL_REWARD_SCHEDULES.remove(deps.storage, (&lp_asset, &old_asset, *end_ts));
+                EXTERNAL_REWARD_SCHEDULES.save(
+                    deps.storage,
+                    (&lp_asset, &new_asset, *end_ts),
+                    rps,
+                )?;
+            }
+            let active_periods = REWARD_ACTIVE_PERIODS
+                .prefix((&lp_asset, &old_asset))
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (end_ts, rps) in &active_periods {
+                REWARD_ACTIVE_PERIODS.remove(deps.storage, (&lp_asset, &old_asset, *end_ts));
+                REWARD_ACTIVE_PERIODS.save(deps.storage, (&lp_asset, &new_asset, *end_ts), rps)?;
+            }
+            if let Some(refund_address) =
+                REWARD_REFUND_ADDRESSES.may_load(deps.storage, (&lp_asset, &old_asset))?
+            {
+                REWARD_REFUND_ADDRESSES.remove(deps.storage, (&lp_asset, &old_asset));
+                REWARD_REFUND_ADDRESSES.save(
+                    deps.storage,
+                    (&lp_asset, &new_asset),
+                    &refund_address,
+                )?;
+            }
+            pool_info.save(deps.storage, &lp_asset)?;
+            attrs.push(attr("remapped_pool", &lp_token));
+            attrs.push(attr("remapped_schedules", schedules.len().to_string()));
+        }
+
+        // Finished indexes are kept even after the reward is gone from the pool
+        let finished = FINISHED_REWARD_INDEXES
+            .prefix(&lp_asset)
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (ts, mut indexes) in finished {
+            let mut changed = false;
+            for (reward_asset, _) in indexes.iter_mut() {
+                if *reward_asset == old_asset {
+                    *reward_asset = new_asset.clone();
+                    changed = true;
+                }
+            }
+            if changed {
+                FINISHED_REWARD_INDEXES.save(deps.storage, (&lp_asset, ts), &indexes)?;
+                attrs.push(attr("remapped_finished_index", format!("{lp_token}:{ts}")));
+            }
+        }
+    }
+
+    let old_key = asset_info_key(&old_asset);
+    let new_key = asset_info_key(&new_asset);
+    if let Some(amount) = REWARD_LIABILITIES.may_load(deps.storage, &old_key)? {
+        REWARD_LIABILITIES.remove(deps.storage, &old_key);
+        REWARD_LIABILITIES.update::<_, StdError>(deps.storage, &new_key, |total| {
+            Ok(total.unwrap_or_default().checked_add(amount)?)
+        })?;
+        attrs.push(attr("remapped_liability", amount));
+    }
+    if let Some(amount) = remove_orphaned_entry(deps.storage, ORPHANED_REWARDS, &old_key)? {
+        add_orphaned_entry(deps.storage, ORPHANED_REWARDS, &new_key, amount)?;
+        attrs.push(attr("remapped_orphaned", amount));
+    }
+    let refundable = REFUNDABLE_ORPHANED_REWARDS
+        .prefix(&old_key)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for refund_address in refundable {
+        if let Some(amount) = remove_orphaned_entry(
+            deps.storage,
+            REFUNDABLE_ORPHANED_REWARDS,
+            (&old_key, &refund_address),
+        )? {
+            add_orphaned_entry(
+                deps.storage,
+                REFUNDABLE_ORPHANED_REWARDS,
+                (&new_key, &refund_address),
+                amount,
+            )?;
+            attrs.push(attr("remapped_refundable_orphaned", amount));
+        }
+    }
+
+    // Users' positions are remapped on load. Earlier remaps pointing to the old token are redirected
+    let chained = REWARD_TOKEN_REMAPS
+        .range_raw(deps.storage, None, None, Order::Ascending)
+        .filter(|item| {
+            item.as_ref()
+                .map(|(_, target)| *target == old_asset)
+                .unwrap_or(true)
+        })
+        .map(|item| item.map(|(key, _)| key))
+        .collect::<StdResult<Vec<_>>>()?;
+    for key in chained {
+        REWARD_TOKEN_REMAPS.save(deps.storage, &key, &new_asset)?;
+    }
+    REWARD_TOKEN_REMAPS.save(deps.storage, &old_key, &new_asset)?;
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
 /// Queries pair info corresponding to given LP token.
 /// Handles both native and cw20 tokens. If the token is native it must follow the following format:
 /// factory/{lp_minter}/{token_name} where lp_minter is a valid bech32 address on the current chain.
+/// Native LP tokens registered in [`LP_TOKEN_PAIRS`] are resolved to the stored pair instead of their minter.
 pub fn query_pair_info(deps: Deps, lp_asset: &AssetInfo) -> StdResult<PairInfo> {
     match lp_asset {
         AssetInfo::Token { contract_addr } => pair_info_by_pool(&deps.querier, contract_addr),
         AssetInfo::NativeToken { denom } => {
-            let parts = denom.split('/').collect_vec();
-            if denom.starts_with("factory") && parts.len() >= 3 {
-                let lp_minter = parts[1];
-                deps.api.addr_validate(lp_minter)?;
-                deps.querier
-                    .query_wasm_smart(lp_minter, &PairQueryMsg::Pair {})
-            } else {
-                Err(StdError::generic_err(format!(
-                    "LP token {denom} doesn't follow token factory format: factory/{{lp_minter}}/{{token_name}}",
-                )))
+            let pair = match LP_TOKEN_PAIRS.may_load(deps.storage, denom)? {
+                Some(pair) => pair,
+                None => lp_minter(denom, deps.api)?,
+            };
+            deps.querier.query_wasm_smart(pair, &PairQueryMsg::Pair {})
+        }
+    }
+}
+
+/// Queries pair types blacklisted in the factory. Pools of these types can't receive PADEX emissions.
+pub fn query_blacklisted_pair_types(
+    querier: QuerierWrapper,
+    config: &Config,
+) -> StdResult<Vec<PairType>> {
+    querier.query_wasm_smart(&config.factory, &FactoryQueryMsg::BlacklistedPairTypes {})
+}
+
+/// Checks whether the token is an LP token of a pair registered in the factory.
+/// Any failure while resolving the pair means the token is not an LP token.
+pub fn is_registered_lp_token(deps: Deps, config: &Config, token: &AssetInfo) -> bool {
+    query_pair_info(deps, token)
+        .map_err(ContractError::from)
+        .and_then(|pair_info| {
+            is_pool_registered(deps.querier, config, &pair_info, &token.to_string())
+        })
+        .is_ok()
+}
+
+/// Returns the first pool where the specified token is an active external reward.
+/// Finished schedules are not considered. Only pools with the token in [`REWARD_TOKEN_POOLS`] are checked.
+pub fn find_pool_with_active_reward(
+    deps: Deps,
+    env: &Env,
+    reward_asset: &AssetInfo,
+) -> StdResult<Option<AssetInfo>> {
+    let reward_key = asset_info_key(reward_asset);
+    for key in
+        REWARD_TOKEN_POOLS
+            .prefix(&reward_key)
+            .keys_raw(deps.storage, None, None, Order::Ascending)
+    {
+        let lp_token = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+        let lp_asset = determine_asset_info(&lp_token, deps.api)?;
+        let mut pool_info = PoolInfo::load(deps.storage, &lp_asset)?;
+
+        // update_rewards() removes finished schedules
+        pool_info.update_rewards(deps.storage, env, &lp_asset)?;
+        let is_active_reward = pool_info
+            .rewards
+            .iter()
+            .any(|r| r.reward.is_external() && r.reward.asset_info() == reward_asset);
+
+        if is_active_reward {
+            return Ok(Some(lp_asset));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Validates pools passed to setup_pools without modifying state.
+/// Returns parsed pools along with their total allocation points.
+pub fn validate_setup_pools(
+    deps: Deps,
+    config: &Config,
+    pools: Vec<(String, Uint128)>,
+) -> Result<(Vec<(AssetInfo, Uint128)>, Uint128), ContractError> {
+    let mut pools_set: HashSet<_> = Default::default();
+    for (pool, alloc_points) in &pools {
+        if alloc_points.is_zero() {
+            return Err(ContractError::ZeroAllocPoint {
+                lp_token: pool.to_owned(),
+            });
+        }
+
+        if !pools_set.insert(pool) {
+            return Err(ContractError::DuplicatedPoolFound {});
+        }
+    }
+
+    let blacklisted_pair_types = query_blacklisted_pair_types(deps.querier, config)?;
+
+    let mut pair_infos = HashMap::new();
+    let setup_pools = pools
+        .into_iter()
+        .map(|(lp_token, alloc_point)| {
+            let lp_token = LpToken::new(&lp_token, deps.api)?;
+            let maybe_lp = lp_token.asset_info().clone();
+
+            // LP token itself is checked separately from the pair assets.
+            // Blocking an LP token doesn't block the pair but it can't receive PADEX emissions
+            if BLOCKED_TOKENS.has(deps.storage, &asset_info_key(&maybe_lp)) {
+                return Err(ContractError::BlockedLpToken {
+                    lp_token: lp_token.to_string(),
+                });
             }
+
+            let pair_info = query_pair_info_cached(deps, &mut pair_infos, &maybe_lp)?;
+
+            is_pool_registered(deps.querier, config, &pair_info, &lp_token.to_string())?;
+
+            // check if assets in the blocked list
+            for asset in &pair_info.asset_infos {
+                if BLOCKED_TOKENS.has(deps.storage, &asset_info_key(asset)) {
+                    return Err(ContractError::BlockedToken {
+                        token: asset.to_string(),
+                    });
+                }
+            }
+
+            // check if pair type is blacklisted
+            if blacklisted_pair_types.contains(&pair_info.pair_type) {
+                return Err(ContractError::BlockedPairType {
+                    pair_type: pair_info.pair_type,
+                });
+            }
+
+            Ok((maybe_lp, alloc_point))
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+
+    let total_alloc_points = setup_pools
+        .iter()
+        .try_fold(Uint128::zero(), |acc, (_, alloc)| acc.checked_add(*alloc))
+        .map_err(|_| ContractError::AllocPointsOverflow {})?;
+
+    Ok((setup_pools, total_alloc_points))
+}
+
+/// Returns the LP token and pair assets of the pool which are in [`BLOCKED_TOKENS`].
+/// Pools with any of them are not allowed to receive PADEX emissions.
+pub fn find_blocked_tokens(
+    storage: &dyn Storage,
+    lp_asset: &AssetInfo,
+    pair_info: &PairInfo,
+) -> Vec<AssetInfo> {
+    std::iter::once(lp_asset)
+        .chain(&pair_info.asset_infos)
+        .filter(|asset| BLOCKED_TOKENS.has(storage, &asset_info_key(asset)))
+        .cloned()
+        .collect()
+}
+
+/// Same as [`query_pair_info`] but each LP token is queried at most once per cache lifetime.
+/// The cache must live only within a single message execution so pair info can't become stale.
+pub fn query_pair_info_cached(
+    deps: Deps,
+    cache: &mut HashMap<AssetInfo, PairInfo>,
+    lp_asset: &AssetInfo,
+) -> StdResult<PairInfo> {
+    if let Some(pair_info) = cache.get(lp_asset) {
+        return Ok(pair_info.clone());
+    }
+
+    let pair_info = query_pair_info(deps, lp_asset)?;
+    cache.insert(lp_asset.clone(), pair_info.clone());
+
+    Ok(pair_info)
+}
+
+/// Loads the pool or returns a new one if the LP token is registered in the factory.
+/// Lets the owner configure a pool before it gets stakers or rewards
+/// without creating pools of unknown LP tokens.
+pub fn load_pool_or_registered(
+    deps: Deps,
+    config: &Config,
+    lp_token: &LpToken,
+) -> Result<PoolInfo, ContractError> {
+    match PoolInfo::may_load(deps.storage, lp_token.asset_info())? {
+        Some(pool_info) => Ok(pool_info),
+        None => {
+            let pair_info = query_pair_info(deps, lp_token.asset_info())?;
+            is_pool_registered(deps.querier, config, &pair_info, &lp_token.to_string())?;
+            Ok(PoolInfo::default())
         }
     }
 }
@@ -386,31 +1239,41 @@ pub fn is_pool_registered(
     config: &Config,
     pair_info: &PairInfo,
     lp_token_addr: &str,
-) -> StdResult<()> {
-    querier
+) -> Result<(), ContractError> {
+    let resp = querier
         .query_wasm_smart::<PairInfo>(
             &config.factory,
             &FactoryQueryMsg::Pair {
                 asset_infos: pair_info.asset_infos.to_vec(),
             },
         )
-        .map_err(|_| {
-            StdError::generic_err(format!(
-                "The pair is not registered: {}-{}",
-                pair_info.asset_infos[0], pair_info.asset_infos[1]
-            ))
+        .map_err(|_| ContractError::PoolNotRegistered {
+            lp_token: lp_token_addr.to_string(),
+            assets: format!("{}-{}", pair_info.asset_infos[0], pair_info.asset_infos[1]),
+        })?;
+
+    // liquidity_token holds either cw20 address or token factory denom registered in the factory
+    ensure!(
+        resp.liquidity_token.as_str() == lp_token_addr,
+        StdError::generic_err(format!(
+            "LP token {lp_token_addr} doesn't match LP token registered in factory {}",
+            resp.liquidity_token
+        ))
+    );
+
+    Ok(())
+}
+
+/// Checks whether LP token doesn't match the one registered in the factory anymore.
+/// It happens when the factory re-registers a pool with a new LP token.
+/// Such pools don't accept new deposits while existing stakers can still withdraw.
+pub fn is_lp_token_stale(deps: Deps, config: &Config, lp_asset: &AssetInfo) -> bool {
+    query_pair_info(deps, lp_asset)
+        .map_err(ContractError::from)
+        .and_then(|pair_info| {
+            is_pool_registered(deps.querier, config, &pair_info, &lp_asset.to_string())
         })
-        .map(|resp| {
-            // Eventually resp.liquidity_token will become just a String once token factory LP tokens are implemented
-            if resp.liquidity_token.as_str() == lp_token_addr {
-                Ok(())
-            } else {
-                Err(StdError::generic_err(format!(
-                    "LP token {lp_token_addr} doesn't match LP token registered in factory {}",
-                    resp.liquidity_token
-                )))
-            }
-        })?
+        .is_err()
 }
 
 pub fn claim_orphaned_rewards(
@@ -427,12 +1290,20 @@ pub fn claim_orphaned_rewards(
         .unwrap_or(MAX_ORPHANED_REWARD_LIMIT)
         .min(MAX_ORPHANED_REWARD_LIMIT);
 
+    // Frozen rewards stay in storage until the token is unfrozen
     let orphaned_rewards = ORPHANED_REWARDS
         .range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| !matches!(item, Ok((key, _)) if FROZEN_REWARDS.has(deps.storage, key)))
         .take(limit as usize)
         .collect::<StdResult<Vec<_>>>()?;
+    // Orphaned rewards with refund address go back to the incentivizer rather than the receiver
+    let refundable_rewards = REFUNDABLE_ORPHANED_REWARDS
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| !matches!(item, Ok(((key, _), _)) if FROZEN_REWARDS.has(deps.storage, key)))
+        .take(limit as usize - orphaned_rewards.len())
+        .collect::<StdResult<Vec<_>>>()?;
 
-    if orphaned_rewards.is_empty() {
+    if orphaned_rewards.is_empty() && refundable_rewards.is_empty() {
         return Err(ContractError::NoOrphanedRewards {});
     }
 
@@ -443,22 +1314,103 @@ pub fn claim_orphaned_rewards(
     ];
 
     for (reward_info_binary, amount) in orphaned_rewards {
+        remove_orphaned_entry(deps.storage, ORPHANED_REWARDS, &reward_info_binary)?;
         // Send orphaned rewards
         if !amount.is_zero() {
-            ORPHANED_REWARDS.remove(deps.storage, &reward_info_binary);
-
-            let reward_info = from_key_to_asset_info(reward_info_binary)?;
-            let reward_asset = reward_info.with_balance(amount);
+            let reward_asset = from_key_to_asset_info(reward_info_binary)?.with_balance(amount);
 
             attrs.push(attr("claimed_orphaned_reward", reward_asset.to_string()));
 
-            let transfer_msg = reward_asset
-                .into_submsg(&receiver, Some((ReplyOn::Error, POST_TRANSFER_REPLY_ID)))?;
-            messages.push(transfer_msg);
+            messages.push(reward_transfer_submsg(
+                deps.storage,
+                reward_asset,
+                &receiver,
+            )?);
         }
     }
 
-    Ok(Response::new().add_submessages(messages))
+    for ((reward_info_binary, refund_address), amount) in refundable_rewards {
+        remove_orphaned_entry(
+            deps.storage,
+            REFUNDABLE_ORPHANED_REWARDS,
+            (&reward_info_binary, &refund_address),
+        )?;
+        if !amount.is_zero() {
+            let reward_asset = from_key_to_asset_info(reward_info_binary)?.with_balance(amount);
+
+            attrs.push(attr(
+                "refunded_orphaned_reward",
+                format!("{reward_asset}:{refund_address}"),
+            ));
+
+            messages.push(reward_transfer_submsg(
+                deps.storage,
+                reward_asset,
+                &refund_address,
+            )?);
+        }
+    }
+
+    Ok(Response::new()
+        .add_attributes(attrs)
+        .add_submessages(messages))
+}
+
+/// Accounts external reward tokens received by the contract.
+pub fn increase_reward_liability(
+    storage: &mut dyn Storage,
+    reward: &AssetInfo,
+    amount: Uint128,
+) -> StdResult<()> {
+    REWARD_LIABILITIES.update(storage, &asset_info_key(reward), |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+/// Builds the transfer of external rewards out of the contract and accounts it in liabilities.
+/// Every outgoing reward transfer must go through here so frozen tokens are never moved.
+pub fn reward_transfer_submsg(
+    storage: &mut dyn Storage,
+    reward: Asset,
+    receiver: impl Into<String>,
+) -> Result<SubMsg<PalomaMsg>, ContractError> {
+    ensure_reward_not_frozen(storage, &reward.info)?;
+    decrease_reward_liability(storage, &reward.info, reward.amount)?;
+    Ok(reward.into_submsg(receiver, Some((ReplyOn::Error, POST_TRANSFER_REPLY_ID)))?)
+}
+
+pub fn ensure_reward_not_frozen(
+    storage: &dyn Storage,
+    reward: &AssetInfo,
+) -> Result<(), ContractError> {
+    ensure!(
+        !FROZEN_REWARDS.has(storage, &asset_info_key(reward)),
+        ContractError::RewardFrozen {
+            reward: reward.to_string()
+        }
+    );
+    Ok(())
+}
+
+/// Accounts external reward tokens sent out by the contract.
+/// Saturates at zero since liabilities are not tracked for schedules created before this counter was introduced.
+pub fn decrease_reward_liability(
+    storage: &mut dyn Storage,
+    reward: &AssetInfo,
+    amount: Uint128,
+) -> StdResult<()> {
+    let key = asset_info_key(reward);
+    let total = REWARD_LIABILITIES
+        .may_load(storage, &key)?
+        .unwrap_or_default()
+        .saturating_sub(amount);
+    if total.is_zero() {
+        REWARD_LIABILITIES.remove(storage, &key);
+    } else {
+        REWARD_LIABILITIES.save(storage, &key, &total)?;
+    }
+    Ok(())
 }
 
 pub fn asset_info_key(asset_info: &AssetInfo) -> Vec<u8> {
@@ -491,12 +1443,14 @@ pub fn from_key_to_asset_info(bytes: Vec<u8>) -> StdResult<AssetInfo> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn propose_new_owner<C, T>(
     deps: DepsMut<C>,
     info: MessageInfo,
     env: Env,
     new_owner: String,
     expires_in: u64,
+    delay: u64,
     owner: Addr,
     proposal: Item<OwnershipProposal>,
 ) -> StdResult<Response<T>>
@@ -521,17 +1475,27 @@ where
         )));
     }
 
+    // Otherwise the proposal expires before it can be claimed
+    if delay > expires_in {
+        return Err(StdError::generic_err(
+            "Parameter delay cannot be higher than expires_in",
+        ));
+    }
+
+    let earliest_claim_ts = env.block.time.seconds() + delay;
     proposal.save(
         deps.storage,
         &OwnershipProposal {
             owner: new_owner.clone(),
             ttl: env.block.time.seconds() + expires_in,
+            earliest_claim_ts,
         },
     )?;
 
     Ok(Response::new().add_attributes(vec![
         attr("action", "propose_new_owner"),
         attr("new_owner", new_owner),
+        attr("earliest_claim_ts", earliest_claim_ts.to_string()),
     ]))
 }
 
@@ -560,7 +1524,7 @@ pub fn claim_ownership<C, T>(
     env: Env,
     proposal: Item<OwnershipProposal>,
     cb: fn(DepsMut<C>, Addr) -> StdResult<()>,
-) -> StdResult<Response<T>>
+) -> Result<Response<T>, ContractError>
 where
     C: CustomQuery,
 {
@@ -570,13 +1534,20 @@ where
 
     // Check the sender
     if info.sender != p.owner {
-        return Err(StdError::generic_err("Unauthorized"));
+        return Err(StdError::generic_err("Unauthorized").into());
     }
 
     if env.block.time.seconds() > p.ttl {
-        return Err(StdError::generic_err("Ownership proposal expired"));
+        return Err(StdError::generic_err("Ownership proposal expired").into());
     }
 
+    ensure!(
+        env.block.time.seconds() >= p.earliest_claim_ts,
+        ContractError::OwnershipClaimTooEarly {
+            earliest_claim_ts: p.earliest_claim_ts
+        }
+    );
+
     proposal.remove(deps.storage);
 
     // Run callback
@@ -1,11 +1,16 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+use cosmwasm_std::{
+    to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult,
+};
 // use cw2::set_contract_version;
 
 use crate::error::ContractError;
 use crate::msg::{ExecuteMsg, InstantiateMsg, PalomaMsg, QueryMsg};
-use crate::state::{Config, State, CONFIG, STATE};
+use crate::state::{
+    migrate_legacy_locks, Config, State, CONFIG, FORCE_WITHDRAW_PROPOSALS, SECONDS_PER_WEEK, STATE,
+    USER_LOCK_IDS,
+};
 
 /*
 // version info for migration info
@@ -20,8 +25,14 @@ pub fn instantiate(
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response<PalomaMsg>, ContractError> {
+    if msg.week_offset_secs >= SECONDS_PER_WEEK {
+        return Err(ContractError::InvalidWeekOffset {});
+    }
+
     let config = Config {
         lock_denom: msg.lock_denom,
+        week_offset_secs: msg.week_offset_secs,
+        owner: Some(deps.api.addr_validate(msg.owner.as_str())?),
     };
 
     CONFIG.save(deps.storage, &config)?;
@@ -54,24 +65,63 @@ pub fn execute(
             end_lock_time,
             user,
         } => execute::execute_increase_end_lock_time(deps, env, info, end_lock_time, user),
+        ExecuteMsg::TransferLock { recipient } => {
+            execute::execute_transfer_lock(deps, env, info, recipient)
+        }
+        ExecuteMsg::IncreaseAmountAndTime {
+            end_lock_time,
+            user,
+        } => execute::execute_increase_amount_and_time(deps, env, info, end_lock_time, user),
+        ExecuteMsg::ForceWithdrawLock { lock_id, receiver } => {
+            execute::execute_force_withdraw_lock(deps, env, info, lock_id, receiver)
+        }
+        ExecuteMsg::ClaimForceWithdraw { lock_id } => {
+            execute::execute_claim_force_withdraw(deps, env, info, lock_id)
+        }
+        ExecuteMsg::CancelForceWithdraw { lock_id } => {
+            execute::execute_cancel_force_withdraw(deps, info, lock_id)
+        }
         ExecuteMsg::Checkpoint {} => execute::execute_global_checkpoint(deps, env, info),
+        ExecuteMsg::CheckpointUsers { users } => {
+            execute::execute_checkpoint_users(deps, env, info, users)
+        }
     }
 }
 
 pub mod execute {
-    use cosmwasm_std::Uint128;
+    use cosmwasm_std::{Storage, Uint128};
 
     use crate::{
         staking::{
             apply_pending_slope_changes_to_state_and_save_updates, send_coin, update_user_lock,
         },
         state::{
-            UserLockedBalance, MAX_SECONDS, MAX_WEEKS, SECONDS_PER_WEEK, USER_LOCKED_BALANCES,
+            load_user_lock, save_user_lock, ForceWithdrawProposal, UserLockedBalance,
+            FORCE_WITHDRAW_DELAY, FORCE_WITHDRAW_PROPOSALS, LOCKS, LOCK_OWNERS,
+            MAX_CHECKPOINT_USERS, MAX_SECONDS, MAX_WEEKS,
         },
     };
 
     use super::*;
 
+    /// Latest allowed end lock time for a lock created or extended now
+    fn max_end_lock_time(env: &Env) -> u64 {
+        env.block.time.seconds() + MAX_SECONDS
+    }
+
+    /// Rejects changes of the user's lock which would defeat its pending forced withdrawal
+    fn ensure_no_pending_force_withdraw(
+        storage: &dyn Storage,
+        user: &str,
+    ) -> Result<(), ContractError> {
+        match USER_LOCK_IDS.may_load(storage, user.to_string())? {
+            Some(lock_id) if FORCE_WITHDRAW_PROPOSALS.has(storage, lock_id) => {
+                Err(ContractError::ForceWithdrawPending { lock_id })
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn receive_cw20(
         _deps: DepsMut,
         _env: Env,
@@ -90,17 +140,19 @@ pub mod execute {
     ) -> Result<Response<PalomaMsg>, ContractError> {
         let user: String = user.unwrap_or(info.sender.to_string());
         // let user: String = info.sender;
-        let denom = CONFIG.load(deps.storage)?.lock_denom.clone();
+        let config = CONFIG.load(deps.storage)?;
+        // Otherwise anyone could block transfers of locks to the user
+        if user != info.sender.as_str() && config.owner.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
         let amount: Uint128 = info
             .funds
             .iter()
-            .find(|coin| coin.denom == denom)
+            .find(|coin| coin.denom == config.lock_denom)
             .map_or(Uint128::zero(), |coin| coin.amount);
-        let end_lock_time = end_lock_time / SECONDS_PER_WEEK * SECONDS_PER_WEEK;
+        let end_lock_time = config.week_start(end_lock_time);
 
-        let prev_user_locked_balance = USER_LOCKED_BALANCES
-            .may_load(deps.storage, user.clone())?
-            .unwrap_or_default();
+        let prev_user_locked_balance = load_user_lock(deps.storage, &user)?;
 
         // Validate that the old lock is finished
         if prev_user_locked_balance.exists() {
@@ -118,10 +170,11 @@ pub mod execute {
         }
 
         // Validate that the unlock week isn't too far in the future
-        if end_lock_time > env.block.time.seconds() + MAX_SECONDS {
+        if end_lock_time > max_end_lock_time(&env) {
             return Err(ContractError::EndLockTimeTooLate {
                 max_weeks: MAX_WEEKS,
-                lock_duration_in_weeks: (end_lock_time - env.block.time.seconds()) / MAX_WEEKS,
+                lock_duration_in_weeks: (end_lock_time - env.block.time.seconds())
+                    / SECONDS_PER_WEEK,
             });
         }
 
@@ -157,6 +210,7 @@ pub mod execute {
         user: Option<String>,
     ) -> Result<Response<PalomaMsg>, ContractError> {
         let user = user.unwrap_or(info.sender.to_string());
+        ensure_no_pending_force_withdraw(deps.storage, &user)?;
         let denom = CONFIG.load(deps.storage)?.lock_denom.clone();
         let increase_amount: Uint128 = info
             .funds
@@ -164,9 +218,7 @@ pub mod execute {
             .find(|coin| coin.denom == denom)
             .map_or(Uint128::zero(), |coin| coin.amount);
 
-        let prev_user_locked_balance = USER_LOCKED_BALANCES
-            .may_load(deps.storage, user.clone())?
-            .unwrap_or_default();
+        let prev_user_locked_balance = load_user_lock(deps.storage, &user)?;
 
         // Validate that a lock exists
         if prev_user_locked_balance.is_void_or_undefined() {
@@ -211,11 +263,10 @@ pub mod execute {
         user: Option<String>,
     ) -> Result<Response<PalomaMsg>, ContractError> {
         let user = user.unwrap_or(info.sender.to_string());
+        ensure_no_pending_force_withdraw(deps.storage, &user)?;
 
         // Get the user locked balance
-        let prev_user_locked_balance = USER_LOCKED_BALANCES
-            .may_load(deps.storage, user.clone())?
-            .unwrap_or_default();
+        let prev_user_locked_balance = load_user_lock(deps.storage, &user)?;
 
         // Validate that the lock isn't void
         if prev_user_locked_balance.is_void_or_undefined() {
@@ -238,10 +289,9 @@ pub mod execute {
             let locked_amount =
                 prev_user_locked_balance.locked_amount_at_timestamp(env.block.time.seconds());
 
-            // The amount available to be withdrawn
-            let unlocked_amount = prev_user_locked_balance.deposited_amount - locked_amount;
-
-            withdrawn_amount = unlocked_amount;
+            // The amount available to be withdrawn. Rounded in favor of the contract
+            withdrawn_amount =
+                prev_user_locked_balance.withdrawable_amount_at_timestamp(env.block.time.seconds());
 
             if withdrawn_amount == prev_user_locked_balance.deposited_amount {
                 // Set the new user locked balance to be zeroed out
@@ -261,6 +311,9 @@ pub mod execute {
             }
         }
 
+        let remaining_locked = new_user_locked_balance.deposited_amount;
+        let lock_end = new_user_locked_balance.end_lock_time;
+
         // Propogate the changes
         update_user_lock(
             deps.storage,
@@ -273,7 +326,16 @@ pub mod execute {
 
         let receiver = deps.api.addr_validate(user.as_str()).unwrap_or(info.sender);
 
-        send_coin(config.lock_denom, &receiver, withdrawn_amount, "withdraw")
+        // lock_end is 0 if nothing is left locked
+        Ok(
+            send_coin(config.lock_denom, &receiver, withdrawn_amount, "withdraw")?.add_attributes(
+                [
+                    ("withdrawn_amount", withdrawn_amount.to_string()),
+                    ("remaining_locked", remaining_locked.to_string()),
+                    ("lock_end", lock_end.to_string()),
+                ],
+            ),
+        )
     }
 
     pub fn execute_increase_end_lock_time(
@@ -284,11 +346,10 @@ pub mod execute {
         user: Option<String>,
     ) -> Result<Response<PalomaMsg>, ContractError> {
         let user = user.unwrap_or(info.sender.to_string());
-        let new_end_lock_time = new_end_lock_time / SECONDS_PER_WEEK * SECONDS_PER_WEEK;
+        ensure_no_pending_force_withdraw(deps.storage, &user)?;
+        let new_end_lock_time = CONFIG.load(deps.storage)?.week_start(new_end_lock_time);
 
-        let prev_user_locked_balance = USER_LOCKED_BALANCES
-            .may_load(deps.storage, user.clone())?
-            .unwrap_or_default();
+        let prev_user_locked_balance = load_user_lock(deps.storage, &user)?;
 
         // Validate that the lock exists
         if prev_user_locked_balance.is_void_or_undefined() {
@@ -306,10 +367,11 @@ pub mod execute {
         }
 
         // Validate that you aren't increasing the lock period too far
-        if new_end_lock_time > env.block.time.seconds() + MAX_SECONDS {
+        if new_end_lock_time > max_end_lock_time(&env) {
             return Err(ContractError::EndLockTimeTooLate {
                 max_weeks: MAX_WEEKS,
-                lock_duration_in_weeks: (new_end_lock_time - env.block.time.seconds()) / MAX_WEEKS,
+                lock_duration_in_weeks: (new_end_lock_time - env.block.time.seconds())
+                    / SECONDS_PER_WEEK,
             });
         }
 
@@ -338,6 +400,232 @@ pub mod execute {
         ]))
     }
 
+    pub fn execute_increase_amount_and_time(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        new_end_lock_time: u64,
+        user: Option<String>,
+    ) -> Result<Response<PalomaMsg>, ContractError> {
+        let user = user.unwrap_or(info.sender.to_string());
+        let config = CONFIG.load(deps.storage)?;
+        // Otherwise anyone could extend someone else's lock
+        if user != info.sender.as_str() && config.owner.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        ensure_no_pending_force_withdraw(deps.storage, &user)?;
+        let increase_amount: Uint128 = info
+            .funds
+            .iter()
+            .find(|coin| coin.denom == config.lock_denom)
+            .map_or(Uint128::zero(), |coin| coin.amount);
+        let new_end_lock_time = config.week_start(new_end_lock_time);
+
+        let prev_user_locked_balance = load_user_lock(deps.storage, &user)?;
+
+        // Validate that a lock exists
+        if prev_user_locked_balance.is_void_or_undefined() {
+            return Err(ContractError::LockDoesNotExist {});
+        }
+
+        // Validate that the current lock isn't expired
+        if prev_user_locked_balance.expired_at_timestamp(env.block.time.seconds()) {
+            return Err(ContractError::LockIsExpired {});
+        }
+
+        // Validate that the amount to increase by is positive
+        if increase_amount == Uint128::zero() {
+            return Err(ContractError::InsufficientLockIncreaseAmount {});
+        }
+
+        // Validate that the unlock week is further in the future than the current end lock time
+        if prev_user_locked_balance.end_lock_time >= new_end_lock_time {
+            return Err(ContractError::EndLockTimeTooEarly {});
+        }
+
+        // Validate that you aren't increasing the lock period too far
+        if new_end_lock_time > max_end_lock_time(&env) {
+            return Err(ContractError::EndLockTimeTooLate {
+                max_weeks: MAX_WEEKS,
+                lock_duration_in_weeks: (new_end_lock_time - env.block.time.seconds())
+                    / SECONDS_PER_WEEK,
+            });
+        }
+
+        // Create the new user ve token point with both changes applied
+        let new_user_locked_balance = UserLockedBalance {
+            // Locked balance info
+            deposited_amount: prev_user_locked_balance.deposited_amount + increase_amount,
+            end_lock_time: new_end_lock_time,
+            start_lock_time: env.block.time.seconds(),
+            // History tracking info
+            timestamp: env.block.time.seconds(),
+        };
+
+        // Propogate the changes
+        update_user_lock(
+            deps.storage,
+            user.clone(),
+            prev_user_locked_balance,
+            new_user_locked_balance,
+        )?;
+
+        Ok(Response::new().add_attributes(vec![
+            ("action", "increase_amount_and_time"),
+            ("user", user.as_str()),
+            ("amount", increase_amount.to_string().as_str()),
+            ("new_end_lock_time", new_end_lock_time.to_string().as_str()),
+        ]))
+    }
+
+    pub fn execute_transfer_lock(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        recipient: String,
+    ) -> Result<Response<PalomaMsg>, ContractError> {
+        let sender = info.sender.to_string();
+        let recipient = deps.api.addr_validate(&recipient)?.to_string();
+        let block_ts = env.block.time.seconds();
+
+        // Validate that the sender owns a lock
+        let id = USER_LOCK_IDS
+            .may_load(deps.storage, sender.clone())?
+            .ok_or(ContractError::LockDoesNotExist {})?;
+        if FORCE_WITHDRAW_PROPOSALS.has(deps.storage, id) {
+            return Err(ContractError::ForceWithdrawPending { lock_id: id });
+        }
+
+        // Validate that the recipient doesn't own a lock as there is one lock per address
+        if load_user_lock(deps.storage, &recipient)?.exists() {
+            return Err(ContractError::LockAlreadyExists {});
+        }
+
+        USER_LOCK_IDS.remove(deps.storage, sender.clone(), block_ts)?;
+        USER_LOCK_IDS.save(deps.storage, recipient.clone(), &id, block_ts)?;
+        LOCK_OWNERS.save(deps.storage, id, &recipient, block_ts)?;
+
+        Ok(Response::new().add_attributes(vec![
+            ("action", "transfer_lock"),
+            ("lock_id", id.to_string().as_str()),
+            ("from", sender.as_str()),
+            ("to", recipient.as_str()),
+        ]))
+    }
+
+    /// Trust assumption: the owner can move any lock to an address of its choice.
+    /// The only protection of the user is FORCE_WITHDRAW_DELAY between the proposal and the claim.
+    pub fn execute_force_withdraw_lock(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        lock_id: u64,
+        receiver: String,
+    ) -> Result<Response<PalomaMsg>, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        if config.owner.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let receiver = deps.api.addr_validate(&receiver)?;
+        let user = LOCK_OWNERS
+            .may_load(deps.storage, lock_id)?
+            .ok_or(ContractError::LockDoesNotExist {})?;
+        if LOCKS.load(deps.storage, lock_id)?.is_void_or_undefined() {
+            return Err(ContractError::LockDoesNotExist {});
+        }
+
+        let earliest_claim_ts = env.block.time.seconds() + FORCE_WITHDRAW_DELAY;
+        FORCE_WITHDRAW_PROPOSALS.save(
+            deps.storage,
+            lock_id,
+            &ForceWithdrawProposal {
+                receiver: receiver.clone(),
+                earliest_claim_ts,
+            },
+        )?;
+
+        Ok(Response::new().add_attributes(vec![
+            ("action", "propose_force_withdraw_lock"),
+            ("lock_id", lock_id.to_string().as_str()),
+            ("user", user.as_str()),
+            ("receiver", receiver.as_str()),
+            ("earliest_claim_ts", earliest_claim_ts.to_string().as_str()),
+        ]))
+    }
+
+    pub fn execute_claim_force_withdraw(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        lock_id: u64,
+    ) -> Result<Response<PalomaMsg>, ContractError> {
+        let proposal = FORCE_WITHDRAW_PROPOSALS
+            .may_load(deps.storage, lock_id)?
+            .ok_or(ContractError::ForceWithdrawNotProposed {})?;
+        if info.sender != proposal.receiver {
+            return Err(ContractError::Unauthorized {});
+        }
+        if env.block.time.seconds() < proposal.earliest_claim_ts {
+            return Err(ContractError::ForceWithdrawTimelocked {
+                earliest_claim_ts: proposal.earliest_claim_ts,
+            });
+        }
+
+        // The lock can't change hands while the proposal is pending
+        let user = LOCK_OWNERS.load(deps.storage, lock_id)?;
+        let prev_user_locked_balance = load_user_lock(deps.storage, &user)?;
+        if prev_user_locked_balance.is_void_or_undefined() {
+            return Err(ContractError::LockDoesNotExist {});
+        }
+
+        // The whole deposit is withdrawn regardless of the lock end time
+        let withdrawn_amount = prev_user_locked_balance.deposited_amount;
+        FORCE_WITHDRAW_PROPOSALS.remove(deps.storage, lock_id);
+
+        update_user_lock(
+            deps.storage,
+            user.clone(),
+            prev_user_locked_balance,
+            UserLockedBalance::void_lock_with_timestamp(env.block.time.seconds()),
+        )?;
+
+        let config = CONFIG.load(deps.storage)?;
+
+        Ok(send_coin(
+            config.lock_denom,
+            &proposal.receiver,
+            withdrawn_amount,
+            "force_withdraw_lock",
+        )?
+        .add_attributes([
+            ("user", user),
+            ("lock_id", lock_id.to_string()),
+            ("withdrawn_amount", withdrawn_amount.to_string()),
+        ]))
+    }
+
+    pub fn execute_cancel_force_withdraw(
+        deps: DepsMut,
+        info: MessageInfo,
+        lock_id: u64,
+    ) -> Result<Response<PalomaMsg>, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        if config.owner.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        if !FORCE_WITHDRAW_PROPOSALS.has(deps.storage, lock_id) {
+            return Err(ContractError::ForceWithdrawNotProposed {});
+        }
+        FORCE_WITHDRAW_PROPOSALS.remove(deps.storage, lock_id);
+
+        Ok(Response::new().add_attributes(vec![
+            ("action", "cancel_force_withdraw_lock"),
+            ("lock_id", lock_id.to_string().as_str()),
+        ]))
+    }
+
     pub fn execute_global_checkpoint(
         deps: DepsMut,
         env: Env,
@@ -354,6 +642,43 @@ pub mod execute {
         Ok(Response::new().add_attribute("action", "execute_global_checkpoint"))
     }
 
+    /// User slope changes are scheduled on lock updates, so applying pending slope changes
+    /// to the global state reconciles all expired locks.
+    /// Locks of the specified users are snapshotted at the current timestamp unchanged.
+    /// Users without a lock are skipped. Calling it repeatedly has no further effect.
+    pub fn execute_checkpoint_users(
+        deps: DepsMut,
+        env: Env,
+        _info: MessageInfo,
+        users: Vec<String>,
+    ) -> Result<Response<PalomaMsg>, ContractError> {
+        if users.len() > MAX_CHECKPOINT_USERS {
+            return Err(ContractError::TooManyCheckpointUsers {});
+        }
+
+        let block_ts = env.block.time.seconds();
+        let mut state = STATE.load(deps.storage)?;
+
+        apply_pending_slope_changes_to_state_and_save_updates(deps.storage, &mut state, block_ts)?;
+
+        let mut checkpointed = 0u64;
+        for user in users {
+            let user_locked_balance = load_user_lock(deps.storage, &user)?;
+
+            if user_locked_balance.is_void_or_undefined() {
+                continue;
+            }
+
+            save_user_lock(deps.storage, &user, &user_locked_balance, block_ts)?;
+            checkpointed += 1;
+        }
+
+        Ok(Response::new().add_attributes(vec![
+            ("action", "execute_checkpoint_users"),
+            ("checkpointed", checkpointed.to_string().as_str()),
+        ]))
+    }
+
     pub fn execute_register_contracts(
         deps: DepsMut,
         lock_denom: String,
@@ -384,21 +709,77 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::Locker { address, timestamp } => Ok(to_json_binary(&query::query_locker(
             deps, env, address, timestamp,
         )?)?),
+        QueryMsg::LockerBatch {
+            addresses,
+            timestamp,
+        } => Ok(to_json_binary(&query::query_locker_batch(
+            deps, env, addresses, timestamp,
+        )?)?),
+        QueryMsg::LockDecay { address, steps } => Ok(to_json_binary(&query::query_lock_decay(
+            deps, env, address, steps,
+        )?)?),
+        QueryMsg::LockById { id } => Ok(to_json_binary(&query::query_lock_by_id(deps, env, id)?)?),
+        QueryMsg::LockId { address } => Ok(to_json_binary(
+            &USER_LOCK_IDS.may_load(deps.storage, address)?,
+        )?),
+        QueryMsg::WithdrawableAt { address, timestamp } => Ok(to_json_binary(
+            &query::query_withdrawable_at(deps, address, timestamp)?,
+        )?),
+        QueryMsg::TotalDepositedAt { timestamp } => Ok(to_json_binary(
+            &query::query_total_deposited_at(deps, timestamp)?,
+        )?),
+        QueryMsg::ForceWithdrawProposal { lock_id } => Ok(to_json_binary(
+            &FORCE_WITHDRAW_PROPOSALS.may_load(deps.storage, lock_id)?,
+        )?),
+        QueryMsg::LockCurve { address } => Ok(to_json_binary(&query::query_lock_curve(
+            deps, env, address,
+        )?)?),
+        QueryMsg::VotingPowerSnapshot {
+            timestamp,
+            start_after,
+            limit,
+        } => Ok(to_json_binary(&query::query_voting_power_snapshot(
+            deps,
+            timestamp,
+            start_after,
+            limit,
+        )?)?),
     }
 }
 
+/// Moves locks keyed by owner, which were stored before lock ids were introduced, to the lock id storage
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: Empty) -> Result<Response, ContractError> {
+    let migrated = migrate_legacy_locks(deps.storage, env.block.time.seconds())?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "migrate"),
+        ("migrated_locks", migrated.to_string().as_str()),
+    ]))
+}
+
 pub mod query {
     use crate::{
-        msg::{ConfigResponse, LockerResponse, StateResponse},
+        msg::{
+            ConfigResponse, LockCurveResponse, LockResponse, LockerResponse, StateResponse,
+            VotingPowerSnapshotEntry, VotingPowerSnapshotResponse,
+        },
         staking::apply_pending_slope_changes_to_state,
-        state::USER_LOCKED_BALANCES,
+        state::{
+            load_user_lock, load_user_lock_at, LEGACY_LOCKS_MIGRATED_AT, LOCKS, LOCK_OWNERS,
+            MAX_LOCKER_BATCH, MAX_LOCK_DECAY_STEPS, MAX_SNAPSHOT_LIMIT,
+            VOTING_POWER_CONSTANT_DIVISOR,
+        },
     };
+    use cosmwasm_std::{Decimal256, Order, StdError, Uint128};
+    use cw_storage_plus::Bound;
 
     use super::*;
     pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
         let config = CONFIG.load(deps.storage)?;
         Ok(ConfigResponse {
             lock_denom: config.lock_denom,
+            week_offset_secs: config.week_offset_secs,
         })
     }
 
@@ -425,6 +806,126 @@ pub mod query {
         })
     }
 
+    pub fn query_lock_by_id(deps: Deps, env: Env, id: u64) -> StdResult<LockResponse> {
+        let now = env.block.time.seconds();
+        let lock = LOCKS.load(deps.storage, id)?;
+
+        Ok(LockResponse {
+            id,
+            owner: LOCK_OWNERS.load(deps.storage, id)?,
+            deposited_amount: lock.deposited_amount,
+            locked_amount: lock.locked_amount_at_timestamp(now),
+            balance: lock.voting_power_at_timestamp(now),
+            end_lock_time: lock.end_lock_time,
+        })
+    }
+
+    pub fn query_total_deposited_at(deps: Deps, timestamp: u64) -> StdResult<Uint128> {
+        Ok(STATE
+            .may_load_at_height(deps.storage, timestamp)?
+            .map(|state| state.total_deposit)
+            .unwrap_or_default())
+    }
+
+    pub fn query_withdrawable_at(
+        deps: Deps,
+        address: String,
+        timestamp: u64,
+    ) -> StdResult<Uint128> {
+        deps.api.addr_validate(&address)?;
+        let lock = load_user_lock(deps.storage, &address)?;
+
+        Ok(lock.withdrawable_amount_at_timestamp(timestamp))
+    }
+
+    pub fn query_lock_curve(deps: Deps, env: Env, address: String) -> StdResult<LockCurveResponse> {
+        deps.api.addr_validate(&address)?;
+        let now = env.block.time.seconds();
+        let lock = load_user_lock(deps.storage, &address)?;
+
+        if lock.is_void_or_undefined() || lock.expired_at_timestamp(now) {
+            return Ok(LockCurveResponse {
+                timestamp: now,
+                end_lock_time: lock.end_lock_time,
+                locked_slope: Decimal256::zero(),
+                locked_bias: Uint128::zero(),
+                slope: Decimal256::zero(),
+                bias: Uint128::zero(),
+            });
+        }
+
+        // The quadratic coefficient of the voting power is the linear one of the locked amount
+        let locked_slope = lock.voting_power_coefficients().quad_coefficient;
+        // Derivative of locked_slope * (end_lock_time - t)^2 / VOTING_POWER_CONSTANT_DIVISOR
+        let slope = locked_slope
+            * Decimal256::from_ratio(
+                2 * (lock.end_lock_time - now),
+                VOTING_POWER_CONSTANT_DIVISOR,
+            );
+
+        Ok(LockCurveResponse {
+            timestamp: now,
+            end_lock_time: lock.end_lock_time,
+            locked_slope,
+            locked_bias: lock.locked_amount_at_timestamp(now),
+            slope,
+            bias: lock.voting_power_at_timestamp(now),
+        })
+    }
+
+    pub fn query_voting_power_snapshot(
+        deps: Deps,
+        timestamp: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<VotingPowerSnapshotResponse> {
+        // Legacy locks have no ids thus can't be paginated by lock id
+        if LEGACY_LOCKS_MIGRATED_AT
+            .may_load(deps.storage)?
+            .is_some_and(|migrated_at| timestamp <= migrated_at)
+        {
+            return Err(StdError::generic_err(
+                "Voting power snapshot is not available up to the legacy locks migration",
+            ));
+        }
+
+        let limit = limit.unwrap_or(MAX_SNAPSHOT_LIMIT).min(MAX_SNAPSHOT_LIMIT) as usize;
+        // Lock ids are never removed thus the current key set covers all past locks
+        let ids = LOCKS
+            .keys(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut entries = vec![];
+        for id in &ids {
+            let voting_power = LOCKS
+                .may_load_at_height(deps.storage, *id, timestamp)?
+                .unwrap_or_default()
+                .voting_power_at_timestamp(timestamp);
+            if voting_power.is_zero() {
+                continue;
+            }
+
+            entries.push(VotingPowerSnapshotEntry {
+                lock_id: *id,
+                address: LOCK_OWNERS
+                    .may_load_at_height(deps.storage, *id, timestamp)?
+                    .ok_or_else(|| StdError::generic_err(format!("Lock {id} has no owner")))?,
+                voting_power,
+            });
+        }
+
+        Ok(VotingPowerSnapshotResponse {
+            entries,
+            last_scanned: ids.last().copied(),
+        })
+    }
+
     pub fn query_locker(
         deps: Deps,
         env: Env,
@@ -432,9 +933,7 @@ pub mod query {
         timestamp: Option<u64>,
     ) -> StdResult<crate::msg::LockerResponse> {
         let timestamp = timestamp.unwrap_or_else(|| env.block.time.seconds());
-        let user_locked_balance = USER_LOCKED_BALANCES
-            .may_load_at_height(deps.storage, address, timestamp)?
-            .unwrap_or_default();
+        let user_locked_balance = load_user_lock_at(deps.storage, &address, timestamp)?;
 
         Ok(LockerResponse {
             deposited_amount: user_locked_balance.deposited_amount,
@@ -442,7 +941,1117 @@ pub mod query {
             balance: user_locked_balance.voting_power_at_timestamp(timestamp),
         })
     }
+
+    pub fn query_locker_batch(
+        deps: Deps,
+        env: Env,
+        addresses: Vec<String>,
+        timestamp: Option<u64>,
+    ) -> StdResult<Vec<LockerResponse>> {
+        if addresses.len() > MAX_LOCKER_BATCH {
+            return Err(StdError::generic_err(format!(
+                "At most {MAX_LOCKER_BATCH} addresses can be queried at once"
+            )));
+        }
+
+        addresses
+            .into_iter()
+            .map(|address| query_locker(deps, env.clone(), address, timestamp))
+            .collect()
+    }
+
+    pub fn query_lock_decay(
+        deps: Deps,
+        env: Env,
+        address: String,
+        steps: Option<u64>,
+    ) -> StdResult<Vec<(u64, Uint128)>> {
+        let now = env.block.time.seconds();
+        let steps = steps
+            .unwrap_or(MAX_LOCK_DECAY_STEPS)
+            .min(MAX_LOCK_DECAY_STEPS);
+        let user_locked_balance = load_user_lock(deps.storage, &address)?;
+        if user_locked_balance.is_void_or_undefined()
+            || user_locked_balance.expired_at_timestamp(now)
+        {
+            return Ok(vec![]);
+        }
+
+        let config = CONFIG.load(deps.storage)?;
+        let mut timestamp = now;
+        let mut points = vec![];
+        while (points.len() as u64) < steps {
+            points.push((
+                timestamp,
+                user_locked_balance.voting_power_at_timestamp(timestamp),
+            ));
+            if timestamp >= user_locked_balance.end_lock_time {
+                break;
+            }
+            timestamp = (config.week_start(timestamp) + SECONDS_PER_WEEK)
+                .min(user_locked_balance.end_lock_time);
+        }
+
+        Ok(points)
+    }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use cosmwasm_std::testing::{
+        message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        attr, coins, Addr, BankMsg, CosmosMsg, Decimal256, DepsMut, Empty, OwnedDeps, Response,
+        Timestamp, Uint128,
+    };
+
+    use super::execute::{
+        execute_cancel_force_withdraw, execute_checkpoint_users, execute_claim_force_withdraw,
+        execute_create_lock, execute_force_withdraw_lock, execute_increase_amount_and_time,
+        execute_increase_end_lock_time, execute_increase_lock_amount, execute_transfer_lock,
+        execute_withdraw,
+    };
+    use super::migrate;
+    use super::query::{
+        query_lock_by_id, query_lock_curve, query_lock_decay, query_locker, query_locker_batch,
+        query_state, query_total_deposited_at, query_voting_power_snapshot, query_withdrawable_at,
+    };
+    use crate::error::ContractError;
+    use crate::msg::{LockerResponse, PalomaMsg};
+    use crate::staking::update_user_lock;
+    use crate::state::{
+        load_user_lock, load_user_lock_at, save_user_lock, Config, QuadraticEquationCoefficients,
+        State, UserLockedBalance, CONFIG, FORCE_WITHDRAW_DELAY, LEGACY_USER_LOCKED_BALANCES,
+        MAX_LOCKER_BATCH, MAX_SECONDS, SECONDS_PER_WEEK, STATE, USER_LOCK_IDS,
+    };
+
+    /// Instantiated contract with the state snapshotted at week 10
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        let owner = deps.api.addr_make("owner");
+        CONFIG
+            .save(
+                deps.as_mut().storage,
+                &Config {
+                    lock_denom: "upadex".to_string(),
+                    week_offset_secs: 0,
+                    owner: Some(owner),
+                },
+            )
+            .unwrap();
+        STATE
+            .save(
+                deps.as_mut().storage,
+                &State::default(),
+                10 * SECONDS_PER_WEEK,
+            )
+            .unwrap();
+
+        deps
+    }
+
+    /// Creates the lock of a user who has no lock and updates the global state
+    fn add_lock(deps: DepsMut, user: &str, lock: UserLockedBalance) {
+        update_user_lock(
+            deps.storage,
+            user.to_string(),
+            UserLockedBalance::default(),
+            lock,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn week_offset_shifts_boundaries() {
+        let mut config = Config {
+            lock_denom: "upadex".to_string(),
+            week_offset_secs: 0,
+            owner: None,
+        };
+        let thursday = 10 * SECONDS_PER_WEEK;
+        assert_eq!(config.week_start(thursday + 3600), thursday);
+
+        // Monday 00:00 UTC is 4 days after Thursday 00:00 UTC
+        let monday_offset = 4 * 86400;
+        config.week_offset_secs = monday_offset;
+        let monday = thursday + monday_offset;
+        assert_eq!(config.week_start(monday), monday);
+        assert_eq!(config.week_start(monday - 1), monday - SECONDS_PER_WEEK);
+        assert_eq!(
+            config.week_start(thursday + 3600),
+            monday - SECONDS_PER_WEEK
+        );
+
+        // There is no shifted boundary before the first one after the Unix epoch
+        assert_eq!(config.week_start(100), 0);
+        assert_eq!(config.week_start(monday_offset), monday_offset);
+    }
+
+    #[test]
+    fn zero_duration_lock_has_no_voting_power() {
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: 10 * SECONDS_PER_WEEK,
+            start_lock_time: 10 * SECONDS_PER_WEEK,
+            timestamp: 10 * SECONDS_PER_WEEK,
+        };
+
+        assert_eq!(
+            lock.voting_power_coefficients(),
+            QuadraticEquationCoefficients::default()
+        );
+        for timestamp in [0, 10 * SECONDS_PER_WEEK - 1, 10 * SECONDS_PER_WEEK] {
+            assert_eq!(lock.locked_amount_at_timestamp(timestamp), Uint128::zero());
+            assert_eq!(lock.voting_power_at_timestamp(timestamp), Uint128::zero());
+        }
+    }
+
+    #[test]
+    fn lock_ending_now_has_no_voting_power() {
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: 10 * SECONDS_PER_WEEK,
+            start_lock_time: 8 * SECONDS_PER_WEEK,
+            timestamp: 8 * SECONDS_PER_WEEK,
+        };
+        let coefficients = lock.voting_power_coefficients();
+
+        assert_eq!(
+            lock.locked_amount_at_timestamp(lock.end_lock_time),
+            Uint128::zero()
+        );
+        assert_eq!(
+            lock.voting_power_at_timestamp(lock.end_lock_time),
+            Uint128::zero()
+        );
+        assert_eq!(
+            coefficients.evaluate_voting_power_at_timestamp(lock.end_lock_time),
+            Uint128::zero()
+        );
+        // Truncation in the linear coefficient may leave a dust unit
+        assert!(
+            coefficients.evaluate_locked_balance_at_timestamp(lock.end_lock_time) <= Uint128::one()
+        );
+    }
+
+    #[test]
+    fn empty_coefficients_evaluate_to_zero() {
+        let coefficients = QuadraticEquationCoefficients::default();
+
+        assert_eq!(
+            coefficients.evaluate_voting_power_at_timestamp(u64::MAX),
+            Uint128::zero()
+        );
+        assert_eq!(
+            coefficients.evaluate_locked_balance_at_timestamp(u64::MAX),
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn checkpoint_users_is_idempotent() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 2 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        add_lock(deps.as_mut(), "user", lock.clone());
+
+        env.block.time = Timestamp::from_seconds(start + 3 * SECONDS_PER_WEEK);
+        let info = message_info(&Addr::unchecked("keeper"), &[]);
+        let users = vec!["user".to_string(), "unknown".to_string()];
+        for _ in 0..2 {
+            execute_checkpoint_users(deps.as_mut(), env.clone(), info.clone(), users.clone())
+                .unwrap();
+
+            let state = STATE.load(deps.as_ref().storage).unwrap();
+            assert_eq!(state.total_deposit, lock.deposited_amount);
+            assert_eq!(
+                state
+                    .voting_power_coefficients
+                    .evaluate_voting_power_at_timestamp(env.block.time.seconds()),
+                Uint128::zero()
+            );
+            assert_eq!(load_user_lock(deps.as_ref().storage, "user").unwrap(), lock);
+        }
+    }
+
+    #[test]
+    fn lock_decay_is_sampled_weekly() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 3 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        save_user_lock(deps.as_mut().storage, "user", &lock, start).unwrap();
+
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        let decay = query_lock_decay(deps.as_ref(), env.clone(), "user".to_string(), None).unwrap();
+        assert_eq!(
+            decay.iter().map(|(ts, _)| *ts).collect::<Vec<_>>(),
+            vec![
+                start + 1000,
+                start + SECONDS_PER_WEEK,
+                start + 2 * SECONDS_PER_WEEK,
+                lock.end_lock_time
+            ]
+        );
+        assert!(decay.windows(2).all(|w| w[0].1 > w[1].1));
+        assert_eq!(decay[3].1, Uint128::zero());
+
+        let decay =
+            query_lock_decay(deps.as_ref(), env.clone(), "user".to_string(), Some(2)).unwrap();
+        assert_eq!(decay.len(), 2);
+
+        env.block.time = Timestamp::from_seconds(lock.end_lock_time);
+        assert!(
+            query_lock_decay(deps.as_ref(), env, "user".to_string(), None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn lock_curve_reproduces_voting_power() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let user = deps.api.addr_make("user").to_string();
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 4 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        save_user_lock(deps.as_mut().storage, &user, &lock, start).unwrap();
+        env.block.time = Timestamp::from_seconds(start + SECONDS_PER_WEEK);
+
+        let curve = query_lock_curve(deps.as_ref(), env.clone(), user.clone()).unwrap();
+        let locker = query_locker(deps.as_ref(), env.clone(), user.clone(), None).unwrap();
+        assert_eq!(curve.timestamp, start + SECONDS_PER_WEEK);
+        assert_eq!(curve.end_lock_time, lock.end_lock_time);
+        assert_eq!(
+            curve.locked_slope,
+            Decimal256::from_ratio(1_000_000u128, 4 * SECONDS_PER_WEEK)
+        );
+        assert_eq!(curve.locked_bias, Uint128::new(750_000));
+        assert_eq!(curve.locked_bias, locker.locked_amount);
+        assert_eq!(curve.bias, locker.balance);
+        // Voting power decays at 2 * locked amount / MAX_SECONDS per second at this point
+        let expected_slope = Decimal256::from_ratio(2 * 750_000u128, MAX_SECONDS);
+        assert!(curve.slope.abs_diff(expected_slope) < Decimal256::from_ratio(1u8, 1_000_000u64));
+
+        env.block.time = Timestamp::from_seconds(lock.end_lock_time);
+        let curve = query_lock_curve(deps.as_ref(), env.clone(), user).unwrap();
+        assert_eq!(curve.slope, Decimal256::zero());
+        assert_eq!(curve.bias, Uint128::zero());
+
+        let other = deps.api.addr_make("other").to_string();
+        let curve = query_lock_curve(deps.as_ref(), env, other).unwrap();
+        assert_eq!(curve.locked_slope, Decimal256::zero());
+        assert_eq!(curve.end_lock_time, 0);
+    }
+
+    #[test]
+    fn voting_power_snapshot_is_reproducible() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        for (user, amount, weeks) in [(&alice, 1_000_000u128, 4), (&bob, 3_000_000, 2)] {
+            let lock = UserLockedBalance {
+                deposited_amount: Uint128::new(amount),
+                end_lock_time: start + weeks * SECONDS_PER_WEEK,
+                start_lock_time: start,
+                timestamp: start,
+            };
+            add_lock(deps.as_mut(), user.as_str(), lock);
+        }
+
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        execute_transfer_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            carol.to_string(),
+        )
+        .unwrap();
+
+        let snapshot_at = start + 500;
+        let first = query_voting_power_snapshot(deps.as_ref(), snapshot_at, None, Some(1)).unwrap();
+        assert_eq!(first.last_scanned, Some(1));
+        let second =
+            query_voting_power_snapshot(deps.as_ref(), snapshot_at, first.last_scanned, Some(1))
+                .unwrap();
+        assert_eq!(second.last_scanned, Some(2));
+        let last =
+            query_voting_power_snapshot(deps.as_ref(), snapshot_at, second.last_scanned, None)
+                .unwrap();
+        assert!(last.entries.is_empty());
+        assert_eq!(last.last_scanned, None);
+
+        let entries = [first.entries, second.entries].concat();
+        // The lock was transferred after the snapshot
+        assert_eq!(entries[0].address, alice.to_string());
+        assert_eq!(entries[1].address, bob.to_string());
+        let total: Uint128 = entries.iter().map(|entry| entry.voting_power).sum();
+        let mut snapshot_env = env.clone();
+        snapshot_env.block.time = Timestamp::from_seconds(snapshot_at);
+        let total_balance = query_state(deps.as_ref(), snapshot_env, Some(snapshot_at))
+            .unwrap()
+            .total_balance;
+        assert!(total_balance.abs_diff(total) <= Uint128::new(entries.len() as u128));
+
+        let now = query_voting_power_snapshot(deps.as_ref(), start + 2000, None, None).unwrap();
+        assert_eq!(now.entries[0].address, carol.to_string());
+        assert_eq!(
+            query_voting_power_snapshot(deps.as_ref(), start - 1, None, None)
+                .unwrap()
+                .entries,
+            vec![]
+        );
+    }
+
+    #[test]
+    fn locker_batch_matches_single_queries() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        for (user, amount) in [("alice", 1_000_000u128), ("bob", 2_000_000)] {
+            let lock = UserLockedBalance {
+                deposited_amount: Uint128::new(amount),
+                end_lock_time: start + 3 * SECONDS_PER_WEEK,
+                start_lock_time: start,
+                timestamp: start,
+            };
+            save_user_lock(deps.as_mut().storage, user, &lock, start).unwrap();
+        }
+        env.block.time = Timestamp::from_seconds(start + 1000);
+
+        let addresses = vec!["bob".to_string(), "carol".to_string(), "alice".to_string()];
+        let batch =
+            query_locker_batch(deps.as_ref(), env.clone(), addresses.clone(), None).unwrap();
+        let single = addresses
+            .into_iter()
+            .map(|address| query_locker(deps.as_ref(), env.clone(), address, None).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(batch, single);
+        assert_eq!(batch[1], LockerResponse::default());
+
+        let err = query_locker_batch(
+            deps.as_ref(),
+            env,
+            vec!["user".to_string(); MAX_LOCKER_BATCH + 1],
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("At most 30 addresses"));
+    }
+
+    #[test]
+    fn withdraw_reports_remaining_lock() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 2 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        add_lock(deps.as_mut(), "user", lock.clone());
+
+        let attr = |res: &Response<PalomaMsg>, key: &str| {
+            res.attributes
+                .iter()
+                .find(|attr| attr.key == key)
+                .unwrap()
+                .value
+                .clone()
+        };
+
+        // Half of the lock duration passed
+        env.block.time = Timestamp::from_seconds(start + SECONDS_PER_WEEK);
+        let res = execute_withdraw(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("user"), &[]),
+            None,
+        )
+        .unwrap();
+        let withdrawn: Uint128 = attr(&res, "withdrawn_amount").parse().unwrap();
+        let remaining: Uint128 = attr(&res, "remaining_locked").parse().unwrap();
+        assert!(!withdrawn.is_zero());
+        assert_eq!(withdrawn + remaining, lock.deposited_amount);
+        assert_eq!(attr(&res, "amount"), withdrawn.to_string());
+        assert_eq!(attr(&res, "lock_end"), lock.end_lock_time.to_string());
+
+        env.block.time = Timestamp::from_seconds(lock.end_lock_time);
+        let res = execute_withdraw(
+            deps.as_mut(),
+            env,
+            message_info(&Addr::unchecked("user"), &[]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(attr(&res, "withdrawn_amount"), remaining.to_string());
+        assert_eq!(attr(&res, "remaining_locked"), "0");
+        assert_eq!(attr(&res, "lock_end"), "0");
+    }
+
+    #[test]
+    fn withdrawable_amount_vests_linearly() {
+        let mut deps = mock_dependencies();
+        let start = 10 * SECONDS_PER_WEEK;
+        let user = deps.api.addr_make("user").to_string();
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 4 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        save_user_lock(deps.as_mut().storage, &user, &lock, start).unwrap();
+
+        let withdrawable =
+            |timestamp: u64| query_withdrawable_at(deps.as_ref(), user.clone(), timestamp);
+        assert_eq!(withdrawable(start).unwrap(), Uint128::zero());
+        assert_eq!(
+            withdrawable(start + SECONDS_PER_WEEK).unwrap(),
+            Uint128::new(250_000)
+        );
+        assert_eq!(
+            withdrawable(lock.end_lock_time).unwrap(),
+            lock.deposited_amount
+        );
+
+        assert!(query_withdrawable_at(deps.as_ref(), "user".to_string(), start).is_err());
+        let other = deps.api.addr_make("other").to_string();
+        assert_eq!(
+            query_withdrawable_at(deps.as_ref(), other, start).unwrap(),
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn max_end_lock_time_is_rejected_without_overflow() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 2 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        add_lock(deps.as_mut(), "user", lock);
+        env.block.time = Timestamp::from_seconds(start + 1000);
+
+        let err = execute_increase_end_lock_time(
+            deps.as_mut(),
+            env,
+            message_info(&Addr::unchecked("user"), &[]),
+            u64::MAX,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::EndLockTimeTooLate { .. }));
+    }
+
+    #[test]
+    fn too_late_end_lock_time_reports_duration_in_weeks() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        env.block.time = Timestamp::from_seconds(start);
+
+        let err = execute_create_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("user"), &coins(1_000_000, "upadex")),
+            start + 60 * SECONDS_PER_WEEK,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::EndLockTimeTooLate {
+                max_weeks: 52,
+                lock_duration_in_weeks: 60,
+            }
+        ));
+
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 2 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        add_lock(deps.as_mut(), "user", lock);
+        let err = execute_increase_end_lock_time(
+            deps.as_mut(),
+            env,
+            message_info(&Addr::unchecked("user"), &[]),
+            start + 53 * SECONDS_PER_WEEK,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::EndLockTimeTooLate {
+                max_weeks: 52,
+                lock_duration_in_weeks: 53,
+            }
+        ));
+    }
+
+    #[test]
+    fn end_lock_time_before_first_shifted_week_is_too_early() {
+        let mut deps = setup();
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        config.week_offset_secs = 4 * 86400;
+        CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(100);
+
+        let err = execute_create_lock(
+            deps.as_mut(),
+            env,
+            message_info(&Addr::unchecked("user"), &coins(1_000_000, "upadex")),
+            1000,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::EndLockTimeTooEarly {}));
+    }
+
+    #[test]
+    fn withdraw_rounding_favors_contract_at_week_boundaries() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK + 1;
+        // Amount and duration are coprime so that every boundary hits a fraction
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_003),
+            end_lock_time: start + 3 * SECONDS_PER_WEEK + 7,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        let duration = lock.end_lock_time - lock.start_lock_time;
+        for ts in (1..=3).flat_map(|week| {
+            let boundary = 10 * SECONDS_PER_WEEK + week * SECONDS_PER_WEEK;
+            [boundary - 1, boundary, boundary + 1]
+        }) {
+            let withdrawable = lock.withdrawable_amount_at_timestamp(ts);
+            assert_eq!(
+                withdrawable + lock.locked_amount_at_timestamp(ts),
+                lock.deposited_amount
+            );
+            // Never more than the exact linear share
+            assert!(
+                withdrawable.u128() * duration as u128
+                    <= lock.deposited_amount.u128() * (ts - start) as u128
+            );
+        }
+
+        add_lock(deps.as_mut(), "user", lock.clone());
+
+        // Partial withdrawals at every boundary never pay out more than deposited
+        let mut total_withdrawn = Uint128::zero();
+        for week in 1..=4 {
+            env.block.time =
+                Timestamp::from_seconds(10 * SECONDS_PER_WEEK + week * SECONDS_PER_WEEK);
+            let res = execute_withdraw(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&Addr::unchecked("user"), &[]),
+                None,
+            )
+            .unwrap();
+            let withdrawn = res
+                .attributes
+                .iter()
+                .find(|attr| attr.key == "withdrawn_amount")
+                .unwrap()
+                .value
+                .parse::<Uint128>()
+                .unwrap();
+            total_withdrawn += withdrawn;
+            let remaining = load_user_lock(deps.as_ref().storage, "user")
+                .unwrap()
+                .deposited_amount;
+            assert_eq!(total_withdrawn + remaining, lock.deposited_amount);
+        }
+        assert_eq!(total_withdrawn, lock.deposited_amount);
+    }
+
+    #[test]
+    fn increase_amount_and_time_updates_lock_once() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 2 * SECONDS_PER_WEEK,
+            start_lock_time: start,
+            timestamp: start,
+        };
+        add_lock(deps.as_mut(), "user", lock.clone());
+
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        let new_end = start + 5 * SECONDS_PER_WEEK;
+
+        let err = execute_increase_amount_and_time(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("user"), &[]),
+            new_end,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientLockIncreaseAmount {}
+        ));
+
+        let err = execute_increase_amount_and_time(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("user"), &coins(500_000, "upadex")),
+            lock.end_lock_time,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::EndLockTimeTooEarly {}));
+
+        // Only the owner can act on behalf of the user
+        let err = execute_increase_amount_and_time(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("attacker"), &coins(1, "upadex")),
+            new_end + 3600,
+            Some("user".to_string()),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        execute_increase_amount_and_time(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("user"), &coins(500_000, "upadex")),
+            new_end + 3600,
+            None,
+        )
+        .unwrap();
+
+        let new_lock = load_user_lock(deps.as_ref().storage, "user").unwrap();
+        assert_eq!(
+            new_lock,
+            UserLockedBalance {
+                deposited_amount: Uint128::new(1_500_000),
+                end_lock_time: new_end,
+                start_lock_time: start + 1000,
+                timestamp: start + 1000,
+            }
+        );
+        let state = STATE.load(deps.as_ref().storage).unwrap();
+        assert_eq!(state.total_deposit, new_lock.deposited_amount);
+        assert_eq!(
+            state
+                .voting_power_coefficients
+                .evaluate_voting_power_at_timestamp(env.block.time.seconds()),
+            new_lock.voting_power_at_timestamp(env.block.time.seconds())
+        );
+    }
+
+    #[test]
+    fn total_deposited_at_reads_snapshots() {
+        let mut deps = mock_dependencies();
+        let start = 10 * SECONDS_PER_WEEK;
+        for (timestamp, total_deposit) in [(start, 100u128), (start + SECONDS_PER_WEEK, 300)] {
+            STATE
+                .save(
+                    deps.as_mut().storage,
+                    &State {
+                        total_deposit: Uint128::new(total_deposit),
+                        timestamp,
+                        ..Default::default()
+                    },
+                    timestamp,
+                )
+                .unwrap();
+        }
+
+        let total_at = |timestamp| query_total_deposited_at(deps.as_ref(), timestamp).unwrap();
+        assert_eq!(total_at(start - 1), Uint128::zero());
+        assert_eq!(total_at(start + 1), Uint128::new(100));
+        assert_eq!(total_at(start + SECONDS_PER_WEEK), Uint128::new(100));
+        assert_eq!(total_at(start + 2 * SECONDS_PER_WEEK), Uint128::new(300));
+    }
+
+    #[test]
+    fn transferred_lock_keeps_id_and_history() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        for user in [&alice, &bob] {
+            execute_create_lock(
+                deps.as_mut(),
+                env.clone(),
+                message_info(user, &coins(1_000_000, "upadex")),
+                start + 4 * SECONDS_PER_WEEK,
+                None,
+            )
+            .unwrap();
+        }
+
+        env.block.time = Timestamp::from_seconds(start + 2000);
+        let err = execute_transfer_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            bob.to_string(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::LockAlreadyExists {}));
+
+        let carol = deps.api.addr_make("carol");
+        execute_transfer_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            carol.to_string(),
+        )
+        .unwrap();
+
+        env.block.time = Timestamp::from_seconds(start + 3000);
+        let lock = query_lock_by_id(deps.as_ref(), env.clone(), 1).unwrap();
+        assert_eq!(lock.owner, carol.to_string());
+        assert_eq!(lock.deposited_amount, Uint128::new(1_000_000));
+        assert_eq!(
+            load_user_lock(deps.as_ref().storage, carol.as_str())
+                .unwrap()
+                .deposited_amount,
+            lock.deposited_amount
+        );
+        assert!(!load_user_lock(deps.as_ref().storage, alice.as_str())
+            .unwrap()
+            .exists());
+
+        // Voting power belonged to alice before the transfer and to carol after it
+        let balance_at = |user: &Addr, timestamp| {
+            query_locker(
+                deps.as_ref(),
+                env.clone(),
+                user.to_string(),
+                Some(timestamp),
+            )
+            .unwrap()
+            .balance
+        };
+        assert!(!balance_at(&alice, start + 1500).is_zero());
+        assert!(balance_at(&carol, start + 1500).is_zero());
+        assert!(balance_at(&alice, start + 2500).is_zero());
+        assert_eq!(lock.balance, balance_at(&carol, start + 3000));
+    }
+
+    #[test]
+    fn only_owner_creates_lock_on_behalf_of_another_address() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let owner = deps.api.addr_make("owner");
+
+        // Would block transfers of locks to bob
+        let err = execute_create_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1, "upadex")),
+            start + 4 * SECONDS_PER_WEEK,
+            Some(bob.to_string()),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // Naming self is the same as no user
+        execute_create_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1_000_000, "upadex")),
+            start + 4 * SECONDS_PER_WEEK,
+            Some(alice.to_string()),
+        )
+        .unwrap();
+        execute_create_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &coins(2_000_000, "upadex")),
+            start + 4 * SECONDS_PER_WEEK,
+            Some(bob.to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            load_user_lock(deps.as_ref().storage, bob.as_str())
+                .unwrap()
+                .deposited_amount,
+            Uint128::new(2_000_000)
+        );
+        assert!(!load_user_lock(deps.as_ref().storage, owner.as_str())
+            .unwrap()
+            .exists());
+    }
+
+    #[test]
+    fn legacy_locks_are_migrated_to_lock_ids() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let alice = deps.api.addr_make("alice").to_string();
+        let bob = deps.api.addr_make("bob").to_string();
+        let lock = UserLockedBalance {
+            deposited_amount: Uint128::new(1_000_000),
+            end_lock_time: start + 4 * SECONDS_PER_WEEK,
+            start_lock_time: start + 100,
+            timestamp: start + 100,
+        };
+        LEGACY_USER_LOCKED_BALANCES
+            .save(deps.as_mut().storage, alice.clone(), &lock, start + 100)
+            .unwrap();
+        // Withdrawn before the migration
+        LEGACY_USER_LOCKED_BALANCES
+            .save(
+                deps.as_mut().storage,
+                bob.clone(),
+                &UserLockedBalance::void_lock_with_timestamp(start + 100),
+                start + 100,
+            )
+            .unwrap();
+
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        let res = migrate(deps.as_mut(), env.clone(), Empty {}).unwrap();
+        assert!(res.attributes.contains(&attr("migrated_locks", "1")));
+        assert_eq!(load_user_lock(deps.as_ref().storage, &alice).unwrap(), lock);
+        assert_eq!(
+            USER_LOCK_IDS
+                .may_load(deps.as_ref().storage, alice.clone())
+                .unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            USER_LOCK_IDS.may_load(deps.as_ref().storage, bob).unwrap(),
+            None
+        );
+
+        // Earlier timestamps are served from the legacy storage
+        for timestamp in [start + 500, start + 1000, start + 2000] {
+            assert_eq!(
+                load_user_lock_at(deps.as_ref().storage, &alice, timestamp).unwrap(),
+                lock
+            );
+        }
+        assert!(
+            !load_user_lock_at(deps.as_ref().storage, &alice, start + 50)
+                .unwrap()
+                .exists()
+        );
+
+        env.block.time = Timestamp::from_seconds(start + 2000);
+        let migrated = query_lock_by_id(deps.as_ref(), env.clone(), 1).unwrap();
+        assert_eq!(migrated.owner, alice);
+        assert_eq!(migrated.deposited_amount, lock.deposited_amount);
+
+        let res = migrate(deps.as_mut(), env, Empty {}).unwrap();
+        assert!(res.attributes.contains(&attr("migrated_locks", "0")));
+        assert_eq!(load_user_lock(deps.as_ref().storage, &alice).unwrap(), lock);
+
+        // Snapshots up to the migration would miss legacy locks
+        for timestamp in [start + 500, start + 1000] {
+            query_voting_power_snapshot(deps.as_ref(), timestamp, None, None).unwrap_err();
+        }
+        let snapshot =
+            query_voting_power_snapshot(deps.as_ref(), start + 2000, None, None).unwrap();
+        assert_eq!(snapshot.entries.len(), 1);
+        assert_eq!(snapshot.entries[0].address, alice);
+    }
+
+    #[test]
+    fn force_withdraw_lock_after_timelock() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let owner = CONFIG.load(deps.as_ref().storage).unwrap().owner.unwrap();
+        let alice = deps.api.addr_make("alice");
+        let recovery = deps.api.addr_make("recovery");
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        execute_create_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1_000_000, "upadex")),
+            start + 20 * SECONDS_PER_WEEK,
+            None,
+        )
+        .unwrap();
+
+        let propose = |deps: DepsMut, sender: &Addr| {
+            execute_force_withdraw_lock(
+                deps,
+                env.clone(),
+                message_info(sender, &[]),
+                1,
+                recovery.to_string(),
+            )
+        };
+        assert!(matches!(
+            propose(deps.as_mut(), &alice).unwrap_err(),
+            ContractError::Unauthorized {}
+        ));
+        propose(deps.as_mut(), &owner).unwrap();
+
+        let claim = |deps: DepsMut, sender: &Addr, timestamp: u64| {
+            let mut env = env.clone();
+            env.block.time = Timestamp::from_seconds(timestamp);
+            execute_claim_force_withdraw(deps, env, message_info(sender, &[]), 1)
+        };
+        let earliest_claim_ts = start + 1000 + FORCE_WITHDRAW_DELAY;
+        assert!(matches!(
+            claim(deps.as_mut(), &recovery, earliest_claim_ts - 1).unwrap_err(),
+            ContractError::ForceWithdrawTimelocked { .. }
+        ));
+        assert!(matches!(
+            claim(deps.as_mut(), &owner, earliest_claim_ts).unwrap_err(),
+            ContractError::Unauthorized {}
+        ));
+
+        let res = claim(deps.as_mut(), &recovery, earliest_claim_ts).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: recovery.to_string(),
+                amount: coins(1_000_000, "upadex"),
+            })
+        );
+        assert!(!load_user_lock(deps.as_ref().storage, alice.as_str())
+            .unwrap()
+            .exists());
+        assert!(matches!(
+            claim(deps.as_mut(), &recovery, earliest_claim_ts).unwrap_err(),
+            ContractError::ForceWithdrawNotProposed {}
+        ));
+    }
+
+    #[test]
+    fn pending_force_withdraw_freezes_lock() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        let start = 10 * SECONDS_PER_WEEK;
+        let owner = CONFIG.load(deps.as_ref().storage).unwrap().owner.unwrap();
+        let alice = deps.api.addr_make("alice");
+        let recovery = deps.api.addr_make("recovery");
+        let attacker = deps.api.addr_make("attacker");
+        env.block.time = Timestamp::from_seconds(start + 1000);
+        execute_create_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1_000_000, "upadex")),
+            start + 20 * SECONDS_PER_WEEK,
+            None,
+        )
+        .unwrap();
+        execute_force_withdraw_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            1,
+            recovery.to_string(),
+        )
+        .unwrap();
+
+        // The compromised key can neither move the lock away nor change it
+        env.block.time = Timestamp::from_seconds(start + 10 * SECONDS_PER_WEEK);
+        let err = execute_transfer_lock(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            attacker.to_string(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ForceWithdrawPending { lock_id: 1 }
+        ));
+        let err = execute_withdraw(deps.as_mut(), env.clone(), message_info(&alice, &[]), None)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ForceWithdrawPending { lock_id: 1 }
+        ));
+        let err = execute_increase_lock_amount(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1, "upadex")),
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ForceWithdrawPending { lock_id: 1 }
+        ));
+        let err = execute_increase_amount_and_time(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(1, "upadex")),
+            start + 30 * SECONDS_PER_WEEK,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ForceWithdrawPending { lock_id: 1 }
+        ));
+        let err = execute_increase_end_lock_time(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            start + 30 * SECONDS_PER_WEEK,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ForceWithdrawPending { lock_id: 1 }
+        ));
+
+        // The lock is unfrozen once the proposal is cancelled
+        execute_cancel_force_withdraw(deps.as_mut(), message_info(&owner, &[]), 1).unwrap();
+        execute_transfer_lock(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            recovery.to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            load_user_lock(deps.as_ref().storage, recovery.as_str())
+                .unwrap()
+                .deposited_amount,
+            Uint128::new(1_000_000)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::state::MINIMUM_STAKE_AMOUNT;
+use crate::state::{MAX_CHECKPOINT_USERS, MINIMUM_STAKE_AMOUNT, SECONDS_PER_WEEK};
 use cosmwasm_std::{OverflowError, StdError};
 use cw_utils::PaymentError;
 use thiserror::Error;
@@ -58,4 +58,19 @@ pub enum ContractError {
 
     #[error("Insufficient amount of Stake")]
     StakeAmountTooSmall {},
+
+    #[error("Week offset must be less than {SECONDS_PER_WEEK} seconds")]
+    InvalidWeekOffset {},
+
+    #[error("Forced withdrawal of the lock isn't proposed")]
+    ForceWithdrawNotProposed {},
+
+    #[error("Forced withdrawal can't be claimed before {earliest_claim_ts}")]
+    ForceWithdrawTimelocked { earliest_claim_ts: u64 },
+
+    #[error("Lock {lock_id} has a pending forced withdrawal")]
+    ForceWithdrawPending { lock_id: u64 },
+
+    #[error("Too many users to checkpoint. Max {MAX_CHECKPOINT_USERS} users per message")]
+    TooManyCheckpointUsers {},
 }
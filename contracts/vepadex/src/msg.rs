@@ -1,5 +1,7 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, CustomMsg, Uint128};
+use cosmwasm_std::{Addr, CustomMsg, Decimal256, Uint128};
+
+use crate::state::ForceWithdrawProposal;
 use cw20::Cw20ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -8,6 +10,10 @@ use serde::{Deserialize, Serialize};
 pub struct InstantiateMsg {
     pub lock_denom: String,
     pub owner: Addr,
+    // Shift of week boundaries in seconds. E.g. 345600 aligns weeks to Monday 00:00 UTC.
+    // Can't be changed after instantiation.
+    #[serde(default)]
+    pub week_offset_secs: u64,
 }
 
 #[cw_serde]
@@ -18,6 +24,7 @@ pub enum ExecuteMsg {
         // unlock_week specifies the week at which to unlock
         // in units of weeks since the epoch
         end_lock_time: u64,
+        // Only the owner can create a lock on behalf of another address
         user: Option<String>,
     },
     IncreaseLockAmount {
@@ -27,12 +34,44 @@ pub enum ExecuteMsg {
         user: Option<String>,
     },
     Checkpoint {},
+    // Checkpoint the global state and snapshot locks of the specified users.
+    // Doesn't modify any lock
+    CheckpointUsers {
+        users: Vec<String>,
+    },
     IncreaseEndLockTime {
         // unlock_week specifies the week at which to unlock
         // in units of weeks since the epoch
         end_lock_time: u64,
         user: Option<String>,
     },
+    // Transfer the sender's lock to the recipient. The recipient must not own a lock.
+    // The lock keeps its id
+    TransferLock {
+        recipient: String,
+    },
+    // Lock more PADEX from the sent funds and extend the lock in one step
+    IncreaseAmountAndTime {
+        end_lock_time: u64,
+        // Only the owner can increase a lock on behalf of another address
+        user: Option<String>,
+    },
+    // Propose to withdraw the whole lock of a compromised user to the recovery address.
+    // Overwrites the pending proposal for the lock. Until the proposal is claimed or cancelled
+    // the lock can't be withdrawn, topped up or transferred. Only the owner can execute this
+    ForceWithdrawLock {
+        lock_id: u64,
+        receiver: String,
+    },
+    // Withdraw the whole lock regardless of its end time.
+    // Only the proposed receiver can execute this once FORCE_WITHDRAW_DELAY has passed
+    ClaimForceWithdraw {
+        lock_id: u64,
+    },
+    // Drop the pending forced withdrawal of the lock. Only the owner can execute this
+    CancelForceWithdraw {
+        lock_id: u64,
+    },
 }
 
 #[cw_serde]
@@ -47,11 +86,57 @@ pub enum QueryMsg {
         address: String,
         timestamp: Option<u64>,
     },
+    // Same as Locker for multiple addresses at once. At most MAX_LOCKER_BATCH addresses.
+    // Responses are in the same order as addresses
+    #[returns(Vec<LockerResponse>)]
+    LockerBatch {
+        addresses: Vec<String>,
+        timestamp: Option<u64>,
+    },
+    // Voting power of the user's lock sampled at the current time and then at every week boundary
+    // till the end of the lock. Returns (timestamp, voting power) pairs.
+    #[returns(Vec<(u64, Uint128)>)]
+    LockDecay {
+        address: String,
+        // Max number of returned points. Capped at MAX_LOCK_DECAY_STEPS
+        steps: Option<u64>,
+    },
+    #[returns(LockResponse)]
+    LockById { id: u64 },
+    // Id of the lock currently owned by the address
+    #[returns(Option<u64>)]
+    LockId { address: String },
+    // Amount of the current lock that could be withdrawn at the timestamp given the linear
+    // vesting of the lock. Projects the current lock and ignores its history.
+    #[returns(Uint128)]
+    WithdrawableAt { address: String, timestamp: u64 },
+    // Total deposited PADEX principal as snapshotted at the timestamp. Pending slope changes
+    // are not applied as they don't affect deposits.
+    #[returns(Uint128)]
+    TotalDepositedAt { timestamp: u64 },
+    // Pending forced withdrawal of the lock
+    #[returns(Option<ForceWithdrawProposal>)]
+    ForceWithdrawProposal { lock_id: u64 },
+    // Parameters of the user's current lock curve at the current block time. See LockCurveResponse
+    #[returns(LockCurveResponse)]
+    LockCurve { address: String },
+    // Voting power of every lock as of the timestamp, paginated by lock id. Reads historical
+    // snapshots thus past results are reproducible. At most MAX_SNAPSHOT_LIMIT locks are scanned
+    // per page. Sum of voting power across all pages equals total_balance of the State query at
+    // the timestamp up to per-lock rounding down. Timestamps up to the legacy locks migration are
+    // rejected.
+    #[returns(VotingPowerSnapshotResponse)]
+    VotingPowerSnapshot {
+        timestamp: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct ConfigResponse {
     pub lock_denom: String,
+    pub week_offset_secs: u64,
 }
 
 #[derive(Default, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -68,6 +153,50 @@ pub struct LockerResponse {
     pub balance: Uint128,
 }
 
+#[cw_serde]
+pub struct LockResponse {
+    pub id: u64,
+    pub owner: String,
+    pub deposited_amount: Uint128,
+    pub locked_amount: Uint128,
+    pub balance: Uint128,
+    pub end_lock_time: u64,
+}
+
+// The locked amount of a lock decays linearly till end_lock_time and the voting power is
+// locked_amount * (end_lock_time - t) / MAX_SECONDS, i.e.
+// voting_power(t) = locked_slope * (end_lock_time - t)^2 / MAX_SECONDS for t < end_lock_time.
+// Amounts are in the lock denom base units, times are in seconds. All fields are zero
+// (end_lock_time aside) for missing or expired locks.
+#[cw_serde]
+pub struct LockCurveResponse {
+    pub timestamp: u64,
+    pub end_lock_time: u64,
+    // Locked amount released per second
+    pub locked_slope: Decimal256,
+    // Locked amount at timestamp
+    pub locked_bias: Uint128,
+    // Voting power decay per second at timestamp. Shrinks linearly towards end_lock_time
+    pub slope: Decimal256,
+    // Voting power at timestamp
+    pub bias: Uint128,
+}
+
+#[cw_serde]
+pub struct VotingPowerSnapshotEntry {
+    pub lock_id: u64,
+    pub address: String,
+    pub voting_power: Uint128,
+}
+
+#[cw_serde]
+pub struct VotingPowerSnapshotResponse {
+    // Locks with non-zero voting power among the scanned ones
+    pub entries: Vec<VotingPowerSnapshotEntry>,
+    // Last scanned lock id to be used as start_after. None when there are no more locks
+    pub last_scanned: Option<u64>,
+}
+
 #[cw_serde]
 pub enum PalomaMsg {
     TokenFactoryMsg {
@@ -1,7 +1,7 @@
 use crate::error::ContractError;
 use crate::msg::PalomaMsg;
 use crate::state::{
-    State, UserLockedBalance, COEFFICIENT_CHANGES, SECONDS_PER_WEEK, STATE, USER_LOCKED_BALANCES,
+    save_user_lock, State, UserLockedBalance, COEFFICIENT_CHANGES, CONFIG, SECONDS_PER_WEEK, STATE,
 };
 
 use cosmwasm_std::{Addr, Coin, CosmosMsg, Response, StdResult, Storage, Uint128};
@@ -62,9 +62,9 @@ pub fn update_user_lock(
     STATE.save(storage, &state, state.timestamp)?;
 
     // Save the new user ve token point
-    USER_LOCKED_BALANCES.save(
+    save_user_lock(
         storage,
-        user,
+        &user,
         &new_user_locked_balance,
         new_user_locked_balance.timestamp,
     )?;
@@ -113,8 +113,13 @@ fn internal_apply_pending_slope_changes_to_state(
     state: &mut State,
     timestamp: u64,
 ) -> StdResult<()> {
+    let config = CONFIG.load(match &imstorage {
+        IMStorage::ImmutableStorage(x) => *x,
+        IMStorage::MutableStorage(x) => *x,
+    })?;
+
     // Get the week that comes before the state's timestamp
-    let mut week_iterator_timestamp = state.timestamp / SECONDS_PER_WEEK * SECONDS_PER_WEEK;
+    let mut week_iterator_timestamp = config.week_start(state.timestamp);
 
     // Go to the next week because we already processed
     // all weeks at or before the state's timestamp
@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
-use cosmwasm_std::{Decimal256, Fraction, Uint128, Uint256};
+use cosmwasm_std::{Addr, Decimal256, Fraction, Order, StdResult, Storage, Uint128, Uint256};
 use cw_storage_plus::{Item, Map, SnapshotItem, SnapshotMap};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -8,6 +8,25 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub lock_denom: String,
+    /// Shift of week boundaries from the Unix epoch week start (Thursday 00:00 UTC) in seconds.
+    /// Lock end times and slope changes are aligned to these boundaries.
+    #[serde(default)]
+    pub week_offset_secs: u64,
+    /// Governance address which can rescue locks of compromised accounts.
+    /// Not set for contracts instantiated before it was introduced
+    #[serde(default)]
+    pub owner: Option<Addr>,
+}
+
+impl Config {
+    /// Round the timestamp down to the start of its week.
+    /// Timestamps before the first boundary after the Unix epoch round down to 0.
+    pub fn week_start(&self, timestamp: u64) -> u64 {
+        let offset = self.week_offset_secs % SECONDS_PER_WEEK;
+        timestamp.saturating_sub(
+            (timestamp % SECONDS_PER_WEEK + SECONDS_PER_WEEK - offset) % SECONDS_PER_WEEK,
+        )
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -73,6 +92,15 @@ pub struct UserLockedBalance {
     pub timestamp: u64,
 }
 
+/// Pending withdrawal of a lock to a recovery address proposed by the owner.
+/// The lock can't be withdrawn, topped up or transferred while the proposal is pending
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ForceWithdrawProposal {
+    pub receiver: Addr,
+    /// The receiver can claim the lock only after this timestamp
+    pub earliest_claim_ts: u64,
+}
+
 #[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     /// Total voting power function definition
@@ -88,6 +116,17 @@ pub const MAX_WEEKS: u64 = 52;
 pub const MAX_SECONDS: u64 = MAX_WEEKS * SECONDS_PER_WEEK; // Order of 10 ** 8
 pub const VOTING_POWER_CONSTANT_DIVISOR: u64 = MAX_SECONDS;
 pub const MINIMUM_STAKE_AMOUNT: Uint128 = Uint128::new(1_000);
+/// Max number of points returned by the lock decay query. Covers the longest possible lock.
+pub const MAX_LOCK_DECAY_STEPS: u64 = MAX_WEEKS + 1;
+/// Max number of users checkpointed in a single message
+pub const MAX_CHECKPOINT_USERS: usize = 30;
+/// Max number of addresses in a single locker batch query
+pub const MAX_LOCKER_BATCH: usize = 30;
+/// Max number of locks scanned by a single voting power snapshot query
+pub const MAX_SNAPSHOT_LIMIT: u32 = 100;
+/// Time between proposing a forced withdrawal and claiming it.
+/// Gives the user and the community time to react to a malicious proposal
+pub const FORCE_WITHDRAW_DELAY: u64 = 2 * SECONDS_PER_WEEK;
 pub const CONFIG: Item<Config> = Item::new("config");
 
 pub const COEFFICIENT_CHANGES: Map<u64, QuadraticEquationCoefficients> =
@@ -100,15 +139,136 @@ pub const STATE: SnapshotItem<State> = SnapshotItem::new(
     cw_storage_plus::Strategy::EveryBlock,
 );
 
-pub const USER_LOCKED_BALANCES: SnapshotMap<String, UserLockedBalance> = SnapshotMap::new(
+/// Locks keyed by lock id. A lock keeps its id when transferred to another owner
+pub const LOCKS: SnapshotMap<u64, UserLockedBalance> = SnapshotMap::new(
+    "locks",
+    "locks__checkpoint",
+    "locks__changelog",
+    cw_storage_plus::Strategy::EveryBlock,
+);
+
+/// Current owner of each lock. Kept for withdrawn locks as well.
+/// Snapshotted to resolve owners of transferred locks at past timestamps
+pub const LOCK_OWNERS: SnapshotMap<u64, String> = SnapshotMap::new(
+    "lock_owners",
+    "lock_owners__checkpoint",
+    "lock_owners__changelog",
+    cw_storage_plus::Strategy::EveryBlock,
+);
+
+/// Owner index. Each address owns at most one non-void lock
+pub const USER_LOCK_IDS: SnapshotMap<String, u64> = SnapshotMap::new(
+    "user_lock_ids",
+    "user_lock_ids__checkpoint",
+    "user_lock_ids__changelog",
+    cw_storage_plus::Strategy::EveryBlock,
+);
+
+/// Last assigned lock id
+pub const LOCK_ID_COUNTER: Item<u64> = Item::new("lock_id_counter");
+
+/// Locks keyed by owner before lock ids were introduced.
+/// Kept after the migration to serve queries of earlier timestamps
+pub const LEGACY_USER_LOCKED_BALANCES: SnapshotMap<String, UserLockedBalance> = SnapshotMap::new(
     "user_locked_balance",
     "user_locked_balance__checkpoint",
     "user_locked_balance__changelog",
     cw_storage_plus::Strategy::EveryBlock,
 );
 
+/// Time when legacy locks were moved to [`LOCKS`]. Not set for contracts created with lock ids
+pub const LEGACY_LOCKS_MIGRATED_AT: Item<u64> = Item::new("legacy_locks_migrated_at");
+
+/// Forced withdrawals proposed by the owner. key: lock id
+pub const FORCE_WITHDRAW_PROPOSALS: Map<u64, ForceWithdrawProposal> =
+    Map::new("force_withdraw_proposals");
+
 pub const STAKE_COINS: Map<String, Uint128> = Map::new("stake_coins");
 
+/// Load the current lock of the user. Returns an undefined lock if the user doesn't own one.
+pub fn load_user_lock(storage: &dyn Storage, user: &str) -> StdResult<UserLockedBalance> {
+    match USER_LOCK_IDS.may_load(storage, user.to_string())? {
+        Some(id) => LOCKS.load(storage, id),
+        None => Ok(UserLockedBalance::default()),
+    }
+}
+
+/// Load the lock the user owned at the timestamp
+pub fn load_user_lock_at(
+    storage: &dyn Storage,
+    user: &str,
+    timestamp: u64,
+) -> StdResult<UserLockedBalance> {
+    if LEGACY_LOCKS_MIGRATED_AT
+        .may_load(storage)?
+        .is_some_and(|migrated_at| timestamp <= migrated_at)
+    {
+        return Ok(LEGACY_USER_LOCKED_BALANCES
+            .may_load_at_height(storage, user.to_string(), timestamp)?
+            .unwrap_or_default());
+    }
+
+    match USER_LOCK_IDS.may_load_at_height(storage, user.to_string(), timestamp)? {
+        Some(id) => Ok(LOCKS
+            .may_load_at_height(storage, id, timestamp)?
+            .unwrap_or_default()),
+        None => Ok(UserLockedBalance::default()),
+    }
+}
+
+/// Save the user's lock. A new lock id is assigned if the user doesn't own a lock yet.
+/// Void locks are detached from the owner so the next lock of the user gets a new id.
+/// Returns the lock id.
+pub fn save_user_lock(
+    storage: &mut dyn Storage,
+    user: &str,
+    lock: &UserLockedBalance,
+    timestamp: u64,
+) -> StdResult<u64> {
+    let id = match USER_LOCK_IDS.may_load(storage, user.to_string())? {
+        Some(id) => id,
+        None => {
+            let id = LOCK_ID_COUNTER.may_load(storage)?.unwrap_or_default() + 1;
+            LOCK_ID_COUNTER.save(storage, &id)?;
+            LOCK_OWNERS.save(storage, id, &user.to_string(), timestamp)?;
+            USER_LOCK_IDS.save(storage, user.to_string(), &id, timestamp)?;
+            id
+        }
+    };
+
+    LOCKS.save(storage, id, lock, timestamp)?;
+    if lock.is_void_or_undefined() {
+        USER_LOCK_IDS.remove(storage, user.to_string(), timestamp)?;
+    }
+
+    Ok(id)
+}
+
+/// Move existing locks keyed by owner to [`LOCKS`] and assign them ids.
+/// Void legacy locks are skipped. Users which already own a lock with an id are skipped as well,
+/// so running the migration again is a no-op.
+/// Returns the number of moved locks.
+pub fn migrate_legacy_locks(storage: &mut dyn Storage, timestamp: u64) -> StdResult<u64> {
+    if LEGACY_LOCKS_MIGRATED_AT.exists(storage) {
+        return Ok(0);
+    }
+
+    let legacy_locks = LEGACY_USER_LOCKED_BALANCES
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut migrated = 0;
+    for (user, lock) in legacy_locks {
+        if lock.is_void_or_undefined() || USER_LOCK_IDS.may_load(storage, user.clone())?.is_some() {
+            continue;
+        }
+        save_user_lock(storage, &user, &lock, timestamp)?;
+        migrated += 1;
+    }
+    LEGACY_LOCKS_MIGRATED_AT.save(storage, &timestamp)?;
+
+    Ok(migrated)
+}
+
 impl UserLockedBalance {
     /// Return whether or not a lock exists. If a lock exists, it is not void or undefined.
     /// void locks are used to represent the lack of a lock rather than an option type.
@@ -153,35 +313,50 @@ impl UserLockedBalance {
         self.end_lock_time <= timestamp
     }
 
-    /// Return the duration of the lock upon creation
+    /// Return the duration of the lock upon creation.
+    /// Zero for degenerate locks where end_lock_time doesn't exceed start_lock_time.
     fn initial_lock_duration(&self) -> u64 {
-        // This is always positive and can't be zero
-        self.end_lock_time - self.start_lock_time
+        self.end_lock_time.saturating_sub(self.start_lock_time)
     }
 
-    /// Must be called with a timestamp after or equaling start lock time
+    /// Returns 0 when called with a timestamp before start lock time
     fn elapsed_lock_time_at_timestamp(&self, timestamp: u64) -> u64 {
-        // This is only ever called when LockedBalance is valid.
-        timestamp - self.start_lock_time
+        timestamp.saturating_sub(self.start_lock_time)
+    }
+
+    /// Whether the lock can't be used as a denominator in voting power math.
+    /// Such locks neither hold locked amount nor voting power.
+    fn has_zero_duration(&self) -> bool {
+        self.initial_lock_duration() == 0
     }
 
     /// Get the remaining locked_amount for a point at a given timestamp
     /// At start_lock_time time, the locked amount equals the deposited amount
     /// At end_lock_time time, the locked amount is 0
     pub fn locked_amount_at_timestamp(&self, timestamp: u64) -> Uint128 {
-        if self.is_void_or_undefined() || self.expired_at_timestamp(timestamp) {
+        if self.is_void_or_undefined()
+            || self.expired_at_timestamp(timestamp)
+            || self.has_zero_duration()
+        {
             return Uint128::zero();
         }
 
         // Doing subtraction from deposited_amount in order to make sure we overestimate locked amount
-        // instead of underestimating it.
-        Uint128::try_from(
-            Uint256::from(self.deposited_amount)
-                - Uint256::from(self.deposited_amount)
-                    * Uint256::from(self.elapsed_lock_time_at_timestamp(timestamp))
-                    / Uint256::from(self.initial_lock_duration()),
-        )
-        .unwrap()
+        // instead of underestimating it. Vested part is rounded down by the integer division.
+        // Elapsed time is less than the lock duration here thus the result never exceeds deposited_amount.
+        let vested = Uint256::from(self.deposited_amount).multiply_ratio(
+            self.elapsed_lock_time_at_timestamp(timestamp),
+            self.initial_lock_duration(),
+        );
+        Uint128::try_from(Uint256::from(self.deposited_amount) - vested).unwrap()
+    }
+
+    /// Get the amount which can be withdrawn at a given timestamp.
+    /// Vested amount is rounded down, so the contract never pays out more than the exact
+    /// linear share. Withdrawable and locked amounts always sum up to the deposited amount.
+    pub fn withdrawable_amount_at_timestamp(&self, timestamp: u64) -> Uint128 {
+        self.deposited_amount
+            .saturating_sub(self.locked_amount_at_timestamp(timestamp))
     }
 
     // Get the voting power for a point at a given timestamp
@@ -232,7 +407,7 @@ impl UserLockedBalance {
     // - da / (elt - slt) * t
 
     fn voting_power_constant_coefficient(&self) -> Decimal256 {
-        if self.is_void_or_undefined() {
+        if self.is_void_or_undefined() || self.has_zero_duration() {
             return Decimal256::zero();
         }
 
@@ -241,39 +416,39 @@ impl UserLockedBalance {
             Uint128::from(self.end_lock_time)
                 * Uint128::from(self.end_lock_time)
                 * self.deposited_amount,
-            // Denominator is always positive
+            // Denominator is positive as zero duration locks are filtered out above
             Uint128::from(self.initial_lock_duration()),
         )
     }
 
     fn voting_power_linear_coefficient(&self) -> Decimal256 {
-        if self.is_void_or_undefined() {
+        if self.is_void_or_undefined() || self.has_zero_duration() {
             return Decimal256::zero();
         }
 
         // First do all multiplications, then divisions
         Decimal256::from_ratio(
             Uint128::from(2 * self.end_lock_time) * self.deposited_amount,
-            // Denominator is always positive
+            // Denominator is positive as zero duration locks are filtered out above
             Uint128::from(self.initial_lock_duration()),
         )
     }
 
     fn voting_power_quad_coefficient(&self) -> Decimal256 {
-        if self.is_void_or_undefined() {
+        if self.is_void_or_undefined() || self.has_zero_duration() {
             return Decimal256::zero();
         }
 
         // First do all multiplications, then divisions
         Decimal256::from_ratio(
             self.deposited_amount,
-            // Denominator is always positive
+            // Denominator is positive as zero duration locks are filtered out above
             Uint128::from(self.initial_lock_duration()),
         )
     }
 
     pub fn voting_power_coefficients(&self) -> QuadraticEquationCoefficients {
-        if self.is_void_or_undefined() {
+        if self.is_void_or_undefined() || self.has_zero_duration() {
             return QuadraticEquationCoefficients::default();
         }
 
@@ -301,7 +476,8 @@ impl QuadraticEquationCoefficients {
             // default to 0
             .unwrap_or_default()
             // Scales everything down by VOTING_POWER_CONSTANT_DIVISOR
-                / Uint256::from(VOTING_POWER_CONSTANT_DIVISOR),
+            .checked_div(Uint256::from(VOTING_POWER_CONSTANT_DIVISOR))
+            .unwrap_or_default(),
         )
         .unwrap()
     }